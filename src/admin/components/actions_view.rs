@@ -14,7 +14,8 @@ use crate::{
         templates::LIST_VIEW_TEMPLATE,
         AppComponent,
     },
-    commands::{get_list_with_contents, remove_action, SgtFile},
+    commands::{get_action_aliases, get_action_options, remove_action, SgtFile},
+    store::read_store,
 };
 
 use super::{
@@ -87,6 +88,8 @@ impl AppMessageHandler for ActionsView {
 
             "actions__edit_selection" => {
                 if let Ok(item) = serde_json::from_str::<Action>(&value.to_string()) {
+                    let options = get_action_options(&item.name);
+
                     state.floating_window.set(FloatingWindow::EditAction);
                     context.set_focus("id", "edit_action_window");
 
@@ -101,6 +104,18 @@ impl AppMessageHandler for ActionsView {
                     if let Some(id) = component_ids.get("edit_action_option_input") {
                         let _ = context.emitter.emit(*id, item.option.unwrap_or("".to_string()));
                     }
+
+                    if let Some(id) = component_ids.get("edit_action_cooldown_input") {
+                        let _ = context.emitter.emit(*id, options.cooldown.get_seconds());
+                    }
+
+                    if let Some(id) = component_ids.get("edit_action_cooldown_reply_input") {
+                        let _ = context.emitter.emit(*id, options.cooldown.reply);
+                    }
+
+                    if let Some(id) = component_ids.get("edit_action_permissions_input") {
+                        let _ = context.emitter.emit(*id, options.permissions.to_string());
+                    }
                 }
             }
 
@@ -246,25 +261,34 @@ pub struct Action {
     pub command: String,
     pub option: Option<String>,
     pub index: usize,
+    /// The other names the action can be used with, shown after its name
+    #[serde(default)]
+    pub aliases: Vec<String>,
 }
 
 impl Action {
     fn from_sgtfile(value: &SgtFile) -> Self {
-        let contents = value.contents.to_ref().to_string();
-        let values = contents.split_once(" ");
+        let name = value.name.to_ref().to_string();
+        let aliases = get_action_aliases(&name);
+
+        Action::new(name, &value.contents.to_ref(), aliases)
+    }
 
-        match values {
+    fn new(name: String, contents: &str, aliases: Vec<String>) -> Self {
+        match contents.split_once(" ") {
             Some((command, option)) => Action {
                 index: 0,
-                name: value.name.to_ref().to_string(),
+                name,
                 command: String::from(command),
                 option: Some(String::from(option)),
+                aliases,
             },
             None => Action {
                 index: 0,
-                name: value.name.to_ref().to_string(),
-                command: contents,
+                name,
+                command: contents.to_string(),
                 option: None,
+                aliases,
             },
         }
     }
@@ -283,8 +307,14 @@ impl From<Action> for Item {
             None => value.command,
         };
 
+        let name = if value.aliases.is_empty() {
+            value.name
+        } else {
+            format!("{} ({})", value.name, value.aliases.join(", "))
+        };
+
         Item {
-            name: value.name.into(),
+            name: name.into(),
             details: details.into(),
             index: value.index.into(),
             color: "#333333".to_string().into(),
@@ -301,13 +331,14 @@ impl ListComponent<'_, Action> for ActionsView {
     }
 
     fn load(&mut self, _state: &mut super::list_view::ListViewState) {
-        match get_list_with_contents("irc_actions") {
-            Ok(files) => {
-                let rewards: Vec<Action> = files
-                    .iter()
+        match read_store() {
+            Ok(store) => {
+                let rewards: Vec<Action> = store
+                    .irc_actions
+                    .into_iter()
                     .enumerate()
-                    .map(|(index, command)| {
-                        let mut reward = Action::from_sgtfile(command);
+                    .map(|(index, (name, action))| {
+                        let mut reward = Action::new(name, &action.command, action.aliases);
                         reward.index = index;
 
                         reward
@@ -16,7 +16,8 @@ use crate::{
         templates::LIST_VIEW_TEMPLATE,
         AppComponent,
     },
-    commands::{get_list_with_contents, remove_chat_command, SgtFile},
+    commands::{get_command_count, get_command_options, remove_chat_command, SgtFile},
+    store::read_store,
 };
 
 use super::{
@@ -90,6 +91,8 @@ impl AppMessageHandler for CommandsView {
 
             "commands__edit_selection" => {
                 if let Ok(item) = serde_json::from_str::<Cmd>(&value.to_string()) {
+                    let options = get_command_options(&item.name);
+
                     state.floating_window.set(FloatingWindow::EditCommand);
                     context.set_focus("id", "edit_command_window");
 
@@ -100,6 +103,22 @@ impl AppMessageHandler for CommandsView {
                     if let Some(id) = component_ids.get("cmd_output_input") {
                         let _ = context.emitter.emit(*id, item.contents);
                     }
+
+                    if let Some(id) = component_ids.get("cmd_cooldown_input") {
+                        let _ = context.emitter.emit(*id, options.cooldown.get_seconds());
+                    }
+
+                    if let Some(id) = component_ids.get("cmd_cooldown_reply_input") {
+                        let _ = context.emitter.emit(*id, options.cooldown.reply);
+                    }
+
+                    if let Some(id) = component_ids.get("cmd_permissions_input") {
+                        let _ = context.emitter.emit(*id, options.permissions.to_string());
+                    }
+
+                    if let Some(id) = component_ids.get("cmd_count_input") {
+                        let _ = context.emitter.emit(*id, get_command_count(&item.name).to_string());
+                    }
                 }
             }
 
@@ -245,6 +264,12 @@ pub struct Cmd {
     pub name: String,
     pub contents: String,
     pub index: usize,
+    /// The other names the command can be used with, shown after its name
+    #[serde(default)]
+    pub aliases: Vec<String>,
+    /// The count of counters, shown after their name
+    #[serde(default)]
+    pub count: Option<u64>,
 }
 
 impl From<SgtFile> for Cmd {
@@ -253,14 +278,25 @@ impl From<SgtFile> for Cmd {
             name: value.name.to_ref().to_string(),
             contents: value.contents.to_ref().to_string(),
             index: 0,
+            aliases: vec![],
+            count: None,
         }
     }
 }
 
 impl From<Cmd> for Item {
     fn from(value: Cmd) -> Self {
+        let mut name = value.name;
+        if !value.aliases.is_empty() {
+            name = format!("{name} ({})", value.aliases.join(", "));
+        }
+
+        if let Some(count) = value.count {
+            name = format!("{name} = {count}");
+        }
+
         Item {
-            name: value.name.into(),
+            name: name.into(),
             details: value.contents.into(),
             index: value.index.into(),
             color: "#333333".to_string().into(),
@@ -277,15 +313,18 @@ impl ListComponent<'_, Cmd> for CommandsView {
     }
 
     fn load(&mut self, _state: &mut super::list_view::ListViewState) {
-        match get_list_with_contents("chat_commands") {
-            Ok(commands) => {
-                let cmds: Vec<Cmd> = commands
-                    .iter()
+        match read_store() {
+            Ok(store) => {
+                let cmds: Vec<Cmd> = store
+                    .chat_commands
+                    .into_iter()
                     .enumerate()
-                    .map(|(index, command)| Cmd {
-                        name: command.name.to_ref().clone(),
-                        contents: command.contents.to_ref().clone(),
+                    .map(|(index, (name, command))| Cmd {
+                        contents: command.message,
                         index,
+                        aliases: command.aliases,
+                        count: command.options.counter.then_some(command.count),
+                        name,
                     })
                     .collect();
 
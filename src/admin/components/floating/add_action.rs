@@ -1,4 +1,4 @@
-use std::collections::HashMap;
+use std::{collections::HashMap, error::Error};
 
 use anathema::{
     component::{Component, ComponentId, Emitter, KeyCode},
@@ -14,7 +14,7 @@ use crate::{
         templates::ADD_ACTION_TEMPLATE,
         AppComponent,
     },
-    commands::add_action,
+    commands::{add_action, get_action_options},
 };
 
 #[derive(Default)]
@@ -44,6 +44,9 @@ impl AddAction {
             "add_action_name_input",
             "add_action_command_input",
             "add_action_option_input",
+            "add_action_cooldown_input",
+            "add_action_cooldown_reply_input",
+            "add_action_permissions_input",
         ];
 
         inputs.iter().for_each(|ident| {
@@ -85,11 +88,7 @@ impl AppMessageHandler for AddAction {
             "add_action__submit" => {
                 let action: Action = value.into();
 
-                let command = action.command.to_ref().clone();
-                let option = action.option.to_ref().clone();
-                let cli = format!("{command} {option}");
-
-                match add_action(&action.name.to_ref(), cli.trim()) {
+                match action.save() {
                     Ok(_) => {
                         if let Some(id) = component_ids.get("actions_view") {
                             let _ = MessageSender::send_message(
@@ -128,9 +127,52 @@ pub struct Action {
     pub name: Value<String>,
     pub command: Value<String>,
     pub option: Value<String>,
+    pub cooldown: Value<String>,
+    pub cooldown_reply: Value<String>,
+    pub permissions: Value<String>,
     pub common: Value<String>,
 }
 
+impl Action {
+    pub fn new() -> Self {
+        Action {
+            name: String::from("").into(),
+            command: String::from("").into(),
+            option: String::from("").into(),
+            cooldown: String::from("").into(),
+            cooldown_reply: String::from("").into(),
+            permissions: String::from("").into(),
+            common: String::from("::::::::::::::::::::").into(),
+        }
+    }
+
+    /// The fields joined the way they're sent to the app when submitted
+    pub fn get_common(&self) -> String {
+        format!(
+            "{}::::{}::::{}::::{}::::{}::::{}",
+            *self.name.to_ref(),
+            *self.command.to_ref(),
+            *self.option.to_ref(),
+            *self.cooldown.to_ref(),
+            *self.cooldown_reply.to_ref(),
+            *self.permissions.to_ref()
+        )
+    }
+
+    /// Saves the action with its cooldown and permissions from a submitted window
+    pub fn save(&self) -> Result<(), Box<dyn Error>> {
+        let name = self.name.to_ref().to_string();
+
+        let mut options = get_action_options(&name);
+        options.cooldown.set_seconds(&self.cooldown.to_ref())?;
+        options.cooldown.reply = self.cooldown_reply.to_ref().to_string();
+        options.permissions = self.permissions.to_ref().parse()?;
+
+        let cli = format!("{} {}", *self.command.to_ref(), *self.option.to_ref());
+        add_action(&name, cli.trim(), &options)
+    }
+}
+
 impl State for Action {
     fn to_common(&self) -> Option<CommonVal<'_>> {
         let str = self.common.to_ref().to_string().clone().into_boxed_str();
@@ -141,23 +183,21 @@ impl State for Action {
 
 impl From<CommonVal<'_>> for Action {
     fn from(value: CommonVal) -> Self {
-        let serialized_announcement = value.to_string();
-        let fields: Vec<&str> = serialized_announcement.split("::::").collect();
+        let serialized_action = value.to_string();
+        let fields: Vec<&str> = serialized_action.split("::::").collect();
 
         match fields.as_slice() {
-            [name, command, option] => Action {
+            [name, command, option, cooldown, cooldown_reply, permissions] => Action {
                 name: String::from(*name).into(),
                 command: String::from(*command).into(),
                 option: String::from(*option).into(),
-                common: serialized_announcement.into(),
+                cooldown: String::from(*cooldown).into(),
+                cooldown_reply: String::from(*cooldown_reply).into(),
+                permissions: String::from(*permissions).into(),
+                common: serialized_action.into(),
             },
 
-            _ => Action {
-                name: String::from("").into(),
-                command: String::from("").into(),
-                option: String::from("").into(),
-                common: String::from("::::").into(),
-            },
+            _ => Action::new(),
         }
     }
 }
@@ -165,13 +205,7 @@ impl From<CommonVal<'_>> for Action {
 impl AddActionState {
     pub fn new() -> Self {
         AddActionState {
-            action: Action {
-                name: String::from("").into(),
-                command: String::from("").into(),
-                option: String::from("").into(),
-                common: String::from("::::").into(),
-            }
-            .into(),
+            action: Action::new().into(),
         }
     }
 }
@@ -199,6 +233,9 @@ impl Component for AddAction {
             state.action.to_mut().name.set(String::from(""));
             state.action.to_mut().command.set(String::from(""));
             state.action.to_mut().option.set(String::from(""));
+            state.action.to_mut().cooldown.set(String::from(""));
+            state.action.to_mut().cooldown_reply.set(String::from(""));
+            state.action.to_mut().permissions.set(String::from(""));
 
             self.clear_inputs(context.emitter.clone());
         }
@@ -213,42 +250,17 @@ impl Component for AddAction {
         mut context: anathema::prelude::Context<'_, Self::State>,
     ) {
         match ident {
-            "add_action__name_update" => {
-                state.action.to_mut().name.set(value.to_string());
-
-                let common = format!(
-                    "{}::::{}::::{}",
-                    value,
-                    *state.action.to_ref().command.to_ref(),
-                    *state.action.to_ref().option.to_ref()
-                );
-                state.action.to_mut().common.set(common);
-            }
+            "add_action__name_update" => state.action.to_mut().name.set(value.to_string()),
 
-            "add_action__command_update" => {
-                state.action.to_mut().command.set(value.to_string());
+            "add_action__command_update" => state.action.to_mut().command.set(value.to_string()),
 
-                let common = format!(
-                    "{}::::{}::::{}",
-                    *state.action.to_ref().name.to_ref(),
-                    value,
-                    *state.action.to_ref().option.to_ref()
-                );
-                state.action.to_mut().common.set(common);
-            }
+            "add_action__option_update" => state.action.to_mut().option.set(value.to_string()),
 
-            "add_action__option_update" => {
-                let timing = value.to_string();
-                state.action.to_mut().option.set(timing);
-
-                let common = format!(
-                    "{}::::{}::::{}",
-                    *state.action.to_ref().name.to_ref(),
-                    *state.action.to_ref().command.to_ref(),
-                    value
-                );
-                state.action.to_mut().common.set(common);
-            }
+            "add_action__cooldown_update" => state.action.to_mut().cooldown.set(value.to_string()),
+
+            "add_action__cooldown_reply_update" => state.action.to_mut().cooldown_reply.set(value.to_string()),
+
+            "add_action__permissions_update" => state.action.to_mut().permissions.set(value.to_string()),
 
             "add_action__name_focus_change" => {
                 context.set_focus("id", "add_action_window");
@@ -262,8 +274,23 @@ impl Component for AddAction {
                 context.set_focus("id", "add_action_window");
             }
 
+            "add_action__cooldown_focus_change" => {
+                context.set_focus("id", "add_action_window");
+            }
+
+            "add_action__cooldown_reply_focus_change" => {
+                context.set_focus("id", "add_action_window");
+            }
+
+            "add_action__permissions_focus_change" => {
+                context.set_focus("id", "add_action_window");
+            }
+
             _ => {}
         }
+
+        let common = state.action.to_ref().get_common();
+        state.action.to_mut().common.set(common);
     }
 
     fn on_key(
@@ -285,6 +312,12 @@ impl Component for AddAction {
 
                 'o' => context.set_focus("id", "add_action_option_input"),
 
+                'l' => context.set_focus("id", "add_action_cooldown_input"),
+
+                'r' => context.set_focus("id", "add_action_cooldown_reply_input"),
+
+                'p' => context.set_focus("id", "add_action_permissions_input"),
+
                 _ => {}
             },
 
@@ -1,4 +1,4 @@
-use std::collections::HashMap;
+use std::{collections::HashMap, error::Error};
 
 use anathema::{
     component::{Component, ComponentId, Emitter, KeyCode},
@@ -14,7 +14,7 @@ use crate::{
         templates::ADD_COMMAND_TEMPLATE,
         AppComponent,
     },
-    commands::add_chat_command,
+    commands::{add_chat_command, get_command_options, set_command_count, set_command_options, CommandOptions},
 };
 
 #[derive(Default)]
@@ -40,7 +40,14 @@ impl AddCommand {
     }
 
     fn clear_inputs(&self, emitter: Emitter) {
-        let inputs = ["add_cmd_name_input", "add_cmd_output_input"];
+        let inputs = [
+            "add_cmd_name_input",
+            "add_cmd_output_input",
+            "add_cmd_cooldown_input",
+            "add_cmd_cooldown_reply_input",
+            "add_cmd_permissions_input",
+            "add_cmd_count_input",
+        ];
 
         inputs.iter().for_each(|ident| {
             if let Some(id) = self.component_ids.get(*ident) {
@@ -85,7 +92,7 @@ impl AppMessageHandler for AddCommand {
             "add_command__submit" => {
                 let command: Command = value.into();
 
-                match add_chat_command(&command.name.to_ref(), &command.output.to_ref(), None) {
+                match command.save() {
                     Ok(_) => {
                         if let Some(id) = component_ids.get("commands_view") {
                             let _ = MessageSender::send_message(
@@ -119,9 +126,68 @@ pub struct AddCommandState {
 pub struct Command {
     pub name: Value<String>,
     pub output: Value<String>,
+    pub cooldown: Value<String>,
+    pub cooldown_reply: Value<String>,
+    pub permissions: Value<String>,
+    pub count: Value<String>,
     pub common: Value<String>,
 }
 
+impl Command {
+    pub fn new() -> Self {
+        Command {
+            name: String::from("").into(),
+            output: String::from("").into(),
+            cooldown: String::from("").into(),
+            cooldown_reply: String::from("").into(),
+            permissions: String::from("").into(),
+            count: String::from("").into(),
+            common: String::from("::::::::::::::::::::").into(),
+        }
+    }
+
+    /// The fields joined the way they're sent to the app when submitted
+    pub fn get_common(&self) -> String {
+        format!(
+            "{}::::{}::::{}::::{}::::{}::::{}",
+            *self.name.to_ref(),
+            *self.output.to_ref(),
+            *self.cooldown.to_ref(),
+            *self.cooldown_reply.to_ref(),
+            *self.permissions.to_ref(),
+            *self.count.to_ref()
+        )
+    }
+
+    /// The command's options with the cooldown and permissions from the window, anything else is kept as it was
+    pub fn get_options(&self) -> Result<CommandOptions, Box<dyn Error>> {
+        let mut options = get_command_options(&self.name.to_ref());
+        options.cooldown.set_seconds(&self.cooldown.to_ref())?;
+        options.cooldown.reply = self.cooldown_reply.to_ref().to_string();
+        options.permissions = self.permissions.to_ref().parse()?;
+
+        Ok(options)
+    }
+
+    /// Saves the command and its options from a submitted window, an empty count is left as it was
+    pub fn save(&self) -> Result<(), Box<dyn Error>> {
+        let name = self.name.to_ref().to_string();
+        let options = self.get_options()?;
+
+        let count = self.count.to_ref().trim().to_string();
+        let count: Option<u64> = if count.is_empty() { None } else { Some(count.parse()?) };
+
+        add_chat_command(&name, &self.output.to_ref(), None)?;
+        set_command_options(&name, &options)?;
+
+        if let Some(count) = count {
+            set_command_count(&name, count)?;
+        }
+
+        Ok(())
+    }
+}
+
 impl State for Command {
     fn to_common(&self) -> Option<CommonVal<'_>> {
         let str = self.common.to_ref().to_string().clone().into_boxed_str();
@@ -132,18 +198,21 @@ impl State for Command {
 
 impl From<CommonVal<'_>> for Command {
     fn from(value: CommonVal) -> Self {
-        if let Some((name, output)) = value.to_string().split_once("::::") {
-            return Command {
-                name: name.to_string().into(),
-                output: output.to_string().into(),
-                common: format!("{}::::{}", name, output).into(),
-            };
-        }
+        let serialized_command = value.to_string();
+        let fields: Vec<&str> = serialized_command.split("::::").collect();
+
+        match fields.as_slice() {
+            [name, output, cooldown, cooldown_reply, permissions, count] => Command {
+                name: String::from(*name).into(),
+                output: String::from(*output).into(),
+                cooldown: String::from(*cooldown).into(),
+                cooldown_reply: String::from(*cooldown_reply).into(),
+                permissions: String::from(*permissions).into(),
+                count: String::from(*count).into(),
+                common: serialized_command.into(),
+            },
 
-        Command {
-            name: String::from("").into(),
-            output: String::from("").into(),
-            common: String::from("::::").into(),
+            _ => Command::new(),
         }
     }
 }
@@ -151,12 +220,7 @@ impl From<CommonVal<'_>> for Command {
 impl AddCommandState {
     pub fn new() -> Self {
         AddCommandState {
-            command: Command {
-                name: String::from("").into(),
-                output: String::from("").into(),
-                common: String::from("::::").into(),
-            }
-            .into(),
+            command: Command::new().into(),
         }
     }
 }
@@ -183,6 +247,10 @@ impl Component for AddCommand {
         if let ComponentMessages::AddCommandClear = component_message {
             state.command.to_mut().name.set(String::from(""));
             state.command.to_mut().output.set(String::from(""));
+            state.command.to_mut().cooldown.set(String::from(""));
+            state.command.to_mut().cooldown_reply.set(String::from(""));
+            state.command.to_mut().permissions.set(String::from(""));
+            state.command.to_mut().count.set(String::from(""));
 
             self.clear_inputs(context.emitter.clone());
         }
@@ -197,19 +265,17 @@ impl Component for AddCommand {
         mut context: anathema::prelude::Context<'_, Self::State>,
     ) {
         match ident {
-            "name_update" => {
-                state.command.to_mut().name.set(value.to_string());
+            "name_update" => state.command.to_mut().name.set(value.to_string()),
 
-                let common = format!("{}::::{}", value, *state.command.to_ref().output.to_ref());
-                state.command.to_mut().common.set(common);
-            }
+            "output_update" => state.command.to_mut().output.set(value.to_string()),
 
-            "output_update" => {
-                state.command.to_mut().output.set(value.to_string());
+            "cooldown_update" => state.command.to_mut().cooldown.set(value.to_string()),
 
-                let common = format!("{}::::{}", *state.command.to_ref().name.to_ref(), value);
-                state.command.to_mut().common.set(common);
-            }
+            "cooldown_reply_update" => state.command.to_mut().cooldown_reply.set(value.to_string()),
+
+            "permissions_update" => state.command.to_mut().permissions.set(value.to_string()),
+
+            "count_update" => state.command.to_mut().count.set(value.to_string()),
 
             "name_focus_change" => {
                 context.set_focus("id", "add_command_window");
@@ -219,8 +285,27 @@ impl Component for AddCommand {
                 context.set_focus("id", "add_command_window");
             }
 
+            "cooldown_focus_change" => {
+                context.set_focus("id", "add_command_window");
+            }
+
+            "cooldown_reply_focus_change" => {
+                context.set_focus("id", "add_command_window");
+            }
+
+            "permissions_focus_change" => {
+                context.set_focus("id", "add_command_window");
+            }
+
+            "count_focus_change" => {
+                context.set_focus("id", "add_command_window");
+            }
+
             _ => {}
         }
+
+        let common = state.command.to_ref().get_common();
+        state.command.to_mut().common.set(common);
     }
 
     fn on_key(
@@ -242,6 +327,14 @@ impl Component for AddCommand {
 
                 'o' => context.set_focus("id", "command_output_input"),
 
+                'l' => context.set_focus("id", "command_cooldown_input"),
+
+                'r' => context.set_focus("id", "command_cooldown_reply_input"),
+
+                'p' => context.set_focus("id", "command_permissions_input"),
+
+                't' => context.set_focus("id", "command_count_input"),
+
                 _ => {}
             },
 
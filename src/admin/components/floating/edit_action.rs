@@ -7,14 +7,11 @@ use anathema::{
     state::{CommonVal, State, Value},
 };
 
-use crate::{
-    admin::{
-        components::{app::AppMessageHandler, MessageSender},
-        messages::{ActionsViewReload, ComponentMessages},
-        templates::EDIT_ACTION_TEMPLATE,
-        AppComponent,
-    },
-    commands::add_action,
+use crate::admin::{
+    components::{app::AppMessageHandler, MessageSender},
+    messages::{ActionsViewReload, ComponentMessages},
+    templates::EDIT_ACTION_TEMPLATE,
+    AppComponent,
 };
 
 use super::add_action::Action;
@@ -46,6 +43,9 @@ impl EditAction {
             "edit_action_name_input",
             "edit_action_command_input",
             "edit_action_option_input",
+            "edit_action_cooldown_input",
+            "edit_action_cooldown_reply_input",
+            "edit_action_permissions_input",
         ];
 
         inputs.iter().for_each(|ident| {
@@ -87,11 +87,7 @@ impl AppMessageHandler for EditAction {
             "edit_action__submit" => {
                 let action: Action = value.into();
 
-                let command = action.command.to_ref().clone();
-                let option = action.option.to_ref().clone();
-                let cli = format!("{command} {option}");
-
-                match add_action(&action.name.to_ref(), cli.trim()) {
+                match action.save() {
                     Ok(_) => {
                         if let Some(id) = component_ids.get("actions_view") {
                             let _ = MessageSender::send_message(
@@ -128,13 +124,7 @@ pub struct EditActionState {
 impl EditActionState {
     pub fn new() -> Self {
         EditActionState {
-            action: Action {
-                name: String::from("").into(),
-                command: String::from("").into(),
-                option: String::from("").into(),
-                common: String::from("::::").into(),
-            }
-            .into(),
+            action: Action::new().into(),
         }
     }
 }
@@ -162,6 +152,9 @@ impl Component for EditAction {
             state.action.to_mut().name.set(String::from(""));
             state.action.to_mut().command.set(String::from(""));
             state.action.to_mut().option.set(String::from(""));
+            state.action.to_mut().cooldown.set(String::from(""));
+            state.action.to_mut().cooldown_reply.set(String::from(""));
+            state.action.to_mut().permissions.set(String::from(""));
 
             self.clear_inputs(context.emitter.clone());
         }
@@ -176,42 +169,17 @@ impl Component for EditAction {
         mut context: anathema::prelude::Context<'_, Self::State>,
     ) {
         match ident {
-            "edit_action__name_update" => {
-                state.action.to_mut().name.set(value.to_string());
-
-                let common = format!(
-                    "{}::::{}::::{}",
-                    value,
-                    *state.action.to_ref().command.to_ref(),
-                    *state.action.to_ref().option.to_ref()
-                );
-                state.action.to_mut().common.set(common);
-            }
+            "edit_action__name_update" => state.action.to_mut().name.set(value.to_string()),
 
-            "edit_action__command_update" => {
-                state.action.to_mut().command.set(value.to_string());
+            "edit_action__command_update" => state.action.to_mut().command.set(value.to_string()),
 
-                let common = format!(
-                    "{}::::{}::::{}",
-                    *state.action.to_ref().name.to_ref(),
-                    value,
-                    *state.action.to_ref().option.to_ref()
-                );
-                state.action.to_mut().common.set(common);
-            }
+            "edit_action__option_update" => state.action.to_mut().option.set(value.to_string()),
 
-            "edit_action__option_update" => {
-                let timing = value.to_string();
-                state.action.to_mut().option.set(timing);
-
-                let common = format!(
-                    "{}::::{}::::{}",
-                    *state.action.to_ref().name.to_ref(),
-                    *state.action.to_ref().command.to_ref(),
-                    value
-                );
-                state.action.to_mut().common.set(common);
-            }
+            "edit_action__cooldown_update" => state.action.to_mut().cooldown.set(value.to_string()),
+
+            "edit_action__cooldown_reply_update" => state.action.to_mut().cooldown_reply.set(value.to_string()),
+
+            "edit_action__permissions_update" => state.action.to_mut().permissions.set(value.to_string()),
 
             "edit_action__name_focus_change" => {
                 context.set_focus("id", "edit_action_window");
@@ -225,8 +193,23 @@ impl Component for EditAction {
                 context.set_focus("id", "edit_action_window");
             }
 
+            "edit_action__cooldown_focus_change" => {
+                context.set_focus("id", "edit_action_window");
+            }
+
+            "edit_action__cooldown_reply_focus_change" => {
+                context.set_focus("id", "edit_action_window");
+            }
+
+            "edit_action__permissions_focus_change" => {
+                context.set_focus("id", "edit_action_window");
+            }
+
             _ => {}
         }
+
+        let common = state.action.to_ref().get_common();
+        state.action.to_mut().common.set(common);
     }
 
     fn on_key(
@@ -248,6 +231,12 @@ impl Component for EditAction {
 
                 'o' => context.set_focus("id", "edit_action_option_input"),
 
+                'l' => context.set_focus("id", "edit_action_cooldown_input"),
+
+                'r' => context.set_focus("id", "edit_action_cooldown_reply_input"),
+
+                'p' => context.set_focus("id", "edit_action_permissions_input"),
+
                 _ => {}
             },
 
@@ -14,7 +14,8 @@ use crate::{
         templates::EDIT_COMMAND_TEMPLATE,
         AppComponent,
     },
-    commands::add_chat_command,
+    commands::template::preview_template,
+    utils::read_auth_token,
 };
 
 use super::add_command::Command;
@@ -42,19 +43,30 @@ impl EditCommand {
 #[derive(Default, State)]
 pub struct EditCommandState {
     command: Value<Command>,
+    preview: Value<String>,
 }
 
 impl EditCommandState {
     pub fn new() -> Self {
         EditCommandState {
-            command: Command {
-                name: String::from("").into(),
-                output: String::from("").into(),
-                common: String::from("::::").into(),
-            }
-            .into(),
+            command: Command::new().into(),
+            preview: String::from("").into(),
         }
     }
+
+    /// Renders the output as a template with made up values for the user and arguments
+    fn update_preview(&mut self) {
+        let channel = read_auth_token()
+            .ok()
+            .and_then(|token_status| token_status.username)
+            .unwrap_or(String::from("channel"));
+
+        let command = self.command.to_ref();
+        let preview = preview_template(&command.name.to_ref(), &command.output.to_ref(), &channel);
+        drop(command);
+
+        self.preview.set(preview);
+    }
 }
 
 impl AppMessageHandler for EditCommand {
@@ -82,13 +94,29 @@ impl AppMessageHandler for EditCommand {
                     let _ = context.emitter.emit(*id, String::from(""));
                 }
 
+                if let Some(id) = component_ids.get("cmd_cooldown_input") {
+                    let _ = context.emitter.emit(*id, String::from(""));
+                }
+
+                if let Some(id) = component_ids.get("cmd_cooldown_reply_input") {
+                    let _ = context.emitter.emit(*id, String::from(""));
+                }
+
+                if let Some(id) = component_ids.get("cmd_permissions_input") {
+                    let _ = context.emitter.emit(*id, String::from(""));
+                }
+
+                if let Some(id) = component_ids.get("cmd_count_input") {
+                    let _ = context.emitter.emit(*id, String::from(""));
+                }
+
                 fun(state, context);
             }
 
             "edit_command__submit" => {
                 let command: Command = value.into();
 
-                match add_chat_command(&command.name.to_ref(), &command.output.to_ref(), None) {
+                match command.save() {
                     Ok(_) => {
                         if let Some(id) = component_ids.get("commands_view") {
                             let _ = MessageSender::send_message(
@@ -131,18 +159,22 @@ impl Component for EditCommand {
         match ident {
             "name_update" => {
                 state.command.to_mut().name.set(value.to_string());
-
-                let common = format!("{}::::{}", value, *state.command.to_ref().output.to_ref());
-                state.command.to_mut().common.set(common);
+                state.update_preview();
             }
 
             "output_update" => {
                 state.command.to_mut().output.set(value.to_string());
-
-                let common = format!("{}::::{}", *state.command.to_ref().name.to_ref(), value);
-                state.command.to_mut().common.set(common);
+                state.update_preview();
             }
 
+            "cooldown_update" => state.command.to_mut().cooldown.set(value.to_string()),
+
+            "cooldown_reply_update" => state.command.to_mut().cooldown_reply.set(value.to_string()),
+
+            "permissions_update" => state.command.to_mut().permissions.set(value.to_string()),
+
+            "count_update" => state.command.to_mut().count.set(value.to_string()),
+
             "name_focus_change" => {
                 context.set_focus("id", "add_command_window");
             }
@@ -151,8 +183,27 @@ impl Component for EditCommand {
                 context.set_focus("id", "add_command_window");
             }
 
+            "cooldown_focus_change" => {
+                context.set_focus("id", "add_command_window");
+            }
+
+            "cooldown_reply_focus_change" => {
+                context.set_focus("id", "add_command_window");
+            }
+
+            "permissions_focus_change" => {
+                context.set_focus("id", "add_command_window");
+            }
+
+            "count_focus_change" => {
+                context.set_focus("id", "add_command_window");
+            }
+
             _ => {}
         }
+
+        let common = state.command.to_ref().get_common();
+        state.command.to_mut().common.set(common);
     }
 
     fn on_key(
@@ -174,6 +225,14 @@ impl Component for EditCommand {
 
                 'o' => context.set_focus("id", "edit_cmd_output_input"),
 
+                'l' => context.set_focus("id", "edit_cmd_cooldown_input"),
+
+                'r' => context.set_focus("id", "edit_cmd_cooldown_reply_input"),
+
+                'p' => context.set_focus("id", "edit_cmd_permissions_input"),
+
+                't' => context.set_focus("id", "edit_cmd_count_input"),
+
                 _ => {}
             },
 
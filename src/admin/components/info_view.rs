@@ -11,7 +11,7 @@ use crate::{
     admin::{messages::ComponentMessages, templates::INFO_VIEW_TEMPLATE, AppComponent},
     commands::{get_list, get_list_commands},
     twitch::{announcements::get_announcements, pubsub::send_to_error_log},
-    utils::read_auth_token,
+    utils::{read_auth_token, read_bot_token},
 };
 
 #[derive(Default)]
@@ -38,6 +38,7 @@ impl AppComponent for InfoView {}
 #[derive(Default, State)]
 pub struct InfoViewState {
     username: Value<String>,
+    bot_username: Value<String>,
     socket_server_port: Value<u16>,
     commands_count: Value<String>,
     announcements_count: Value<String>,
@@ -49,6 +50,7 @@ impl InfoViewState {
     pub fn new() -> Self {
         InfoViewState {
             username: String::from("[Anonymous]").into(),
+            bot_username: String::from("Not set").into(),
             socket_server_port: 8765.into(),
             commands_count: String::from("0").into(),
             announcements_count: String::from("0").into(),
@@ -92,7 +94,15 @@ impl InfoView {
             .username
             .set(get_field_value(read_auth_token(), "username", |token_status| {
                 token_status.username.unwrap_or("Not logged in".to_string())
-            }))
+            }));
+
+        // Most setups don't have a bot account, so a missing token isn't an error
+        state.bot_username.set(
+            read_bot_token()
+                .ok()
+                .and_then(|token_status| token_status.username)
+                .unwrap_or("Not set".to_string()),
+        );
 
         // TODO: Update socket server port after its been changed to be configurable
     }
@@ -73,8 +73,25 @@ impl Admin {
         vec![
             ("add_cmd_name_input".to_string(), "add_command_window".to_string()),
             ("add_cmd_output_input".to_string(), "add_command_window".to_string()),
+            ("add_cmd_cooldown_input".to_string(), "add_command_window".to_string()),
+            (
+                "add_cmd_cooldown_reply_input".to_string(),
+                "add_command_window".to_string(),
+            ),
+            (
+                "add_cmd_permissions_input".to_string(),
+                "add_command_window".to_string(),
+            ),
+            ("add_cmd_count_input".to_string(), "add_command_window".to_string()),
             ("cmd_name_input".to_string(), "edit_command_window".to_string()),
             ("cmd_output_input".to_string(), "edit_command_window".to_string()),
+            ("cmd_cooldown_input".to_string(), "edit_command_window".to_string()),
+            (
+                "cmd_cooldown_reply_input".to_string(),
+                "edit_command_window".to_string(),
+            ),
+            ("cmd_permissions_input".to_string(), "edit_command_window".to_string()),
+            ("cmd_count_input".to_string(), "edit_command_window".to_string()),
             (
                 "announce_name_input".to_string(),
                 "edit_announcement_window".to_string(),
@@ -95,12 +112,33 @@ impl Admin {
             ("add_action_name_input".to_string(), "add_action_window".to_string()),
             ("add_action_command_input".to_string(), "add_action_window".to_string()),
             ("add_action_option_input".to_string(), "add_action_window".to_string()),
+            ("add_action_cooldown_input".to_string(), "add_action_window".to_string()),
+            (
+                "add_action_cooldown_reply_input".to_string(),
+                "add_action_window".to_string(),
+            ),
+            (
+                "add_action_permissions_input".to_string(),
+                "add_action_window".to_string(),
+            ),
             ("edit_action_name_input".to_string(), "edit_action_window".to_string()),
             (
                 "edit_action_shell_command_input".to_string(),
                 "edit_action_window".to_string(),
             ),
             ("edit_action_option_input".to_string(), "edit_action_window".to_string()),
+            (
+                "edit_action_cooldown_input".to_string(),
+                "edit_action_window".to_string(),
+            ),
+            (
+                "edit_action_cooldown_reply_input".to_string(),
+                "edit_action_window".to_string(),
+            ),
+            (
+                "edit_action_permissions_input".to_string(),
+                "edit_action_window".to_string(),
+            ),
             ("add_reward_name_input".to_string(), "add_reward_window".to_string()),
             ("add_reward_command_input".to_string(), "add_reward_window".to_string()),
             (
@@ -0,0 +1,177 @@
+use std::{
+    collections::{HashMap, HashSet},
+    error::Error,
+    sync::{Mutex, OnceLock},
+    time::{Duration, Instant},
+};
+
+use serde::{Deserialize, Serialize};
+
+static COOLDOWNS: OnceLock<Mutex<CooldownTracker>> = OnceLock::new();
+
+/// Seconds to wait between uses of a chat command or irc-action, for everyone and for each chatter
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+pub struct Cooldown {
+    #[serde(default)]
+    pub global: u64,
+    #[serde(default)]
+    pub user: u64,
+    /// Sent to chatters that use it while it's cooling down, {remaining} is the time left
+    #[serde(default)]
+    pub reply: String,
+}
+
+impl Cooldown {
+    /// The "global/user" seconds edited in the admin, like "30/10"
+    pub fn get_seconds(&self) -> String {
+        if self.global == 0 && self.user == 0 {
+            return String::from("");
+        }
+
+        format!("{}/{}", self.global, self.user)
+    }
+
+    /// Reads "global/user" seconds, a single number is only the global cooldown
+    pub fn set_seconds(&mut self, seconds: &str) -> Result<(), Box<dyn Error>> {
+        let seconds = seconds.trim();
+        let (global, user) = seconds.split_once('/').unwrap_or((seconds, ""));
+
+        self.global = parse_seconds(global)?;
+        self.user = parse_seconds(user)?;
+
+        Ok(())
+    }
+}
+
+fn parse_seconds(seconds: &str) -> Result<u64, Box<dyn Error>> {
+    let seconds = seconds.trim();
+    if seconds.is_empty() {
+        return Ok(0);
+    }
+
+    Ok(seconds.parse()?)
+}
+
+/// A use that was turned down because of a cooldown
+#[derive(Debug, PartialEq)]
+pub struct OnCooldown {
+    pub remaining: Duration,
+    /// Only the first turned down use of each chatter gets the cooldown reply
+    pub notify: bool,
+}
+
+/// When each command was last used, by anyone and by each chatter
+#[derive(Default)]
+pub struct CooldownTracker {
+    last_used: HashMap<String, Instant>,
+    last_used_by: HashMap<(String, String), Instant>,
+    notified: HashSet<(String, String)>,
+}
+
+impl CooldownTracker {
+    /// Records a use of key by user, unless one of its cooldowns hasn't run out yet
+    pub fn try_use(&mut self, key: &str, user: &str, cooldown: &Cooldown, now: Instant) -> Result<(), OnCooldown> {
+        let user_key = (key.to_string(), user.to_string());
+
+        let global_remaining = self
+            .last_used
+            .get(key)
+            .map(|used| get_remaining(*used, cooldown.global, now))
+            .unwrap_or_default();
+
+        let user_remaining = self
+            .last_used_by
+            .get(&user_key)
+            .map(|used| get_remaining(*used, cooldown.user, now))
+            .unwrap_or_default();
+
+        let remaining = global_remaining.max(user_remaining);
+        if !remaining.is_zero() {
+            let notify = self.notified.insert(user_key);
+
+            return Err(OnCooldown { remaining, notify });
+        }
+
+        self.last_used.insert(key.to_string(), now);
+        self.last_used_by.insert(user_key, now);
+        self.notified.retain(|(notified_key, _)| notified_key != key);
+
+        Ok(())
+    }
+}
+
+fn get_remaining(used: Instant, seconds: u64, now: Instant) -> Duration {
+    (used + Duration::from_secs(seconds)).saturating_duration_since(now)
+}
+
+/// Records a use in the cooldowns shared by everything that reads chat
+pub fn use_cooldown(key: &str, user: &str, cooldown: &Cooldown) -> Result<(), OnCooldown> {
+    let cooldowns = COOLDOWNS.get_or_init(|| Mutex::new(CooldownTracker::default()));
+
+    match cooldowns.lock() {
+        Ok(mut cooldowns) => cooldowns.try_use(key, user, cooldown, Instant::now()),
+
+        // A panic while holding the lock shouldn't turn every command off
+        Err(_) => Ok(()),
+    }
+}
+
+#[test]
+fn test_cooldown_tracker() {
+    let cooldown = Cooldown {
+        global: 10,
+        user: 30,
+        reply: String::from(""),
+    };
+    let mut tracker = CooldownTracker::default();
+    let start = Instant::now();
+    let after = |seconds| start + Duration::from_secs(seconds);
+
+    assert_eq!(tracker.try_use("hug", "dallas", &cooldown, start), Ok(()));
+
+    // The global cooldown holds everyone back, each chatter is only notified once
+    assert_eq!(
+        tracker.try_use("hug", "ronni", &cooldown, after(4)),
+        Err(OnCooldown {
+            remaining: Duration::from_secs(6),
+            notify: true
+        })
+    );
+    assert_eq!(
+        tracker.try_use("hug", "ronni", &cooldown, after(5)),
+        Err(OnCooldown {
+            remaining: Duration::from_secs(5),
+            notify: false
+        })
+    );
+    assert_eq!(tracker.try_use("lurk", "ronni", &cooldown, after(5)), Ok(()));
+    assert_eq!(tracker.try_use("hug", "ronni", &cooldown, after(10)), Ok(()));
+
+    // The user cooldown outlasts the global one
+    assert_eq!(
+        tracker.try_use("hug", "dallas", &cooldown, after(25)),
+        Err(OnCooldown {
+            remaining: Duration::from_secs(5),
+            notify: true
+        })
+    );
+    assert_eq!(tracker.try_use("hug", "dallas", &cooldown, after(30)), Ok(()));
+}
+
+#[test]
+fn test_cooldown_seconds() {
+    let mut cooldown = Cooldown::default();
+    assert_eq!(cooldown.get_seconds(), "");
+
+    cooldown.set_seconds("30/10").unwrap();
+    assert_eq!((cooldown.global, cooldown.user), (30, 10));
+    assert_eq!(cooldown.get_seconds(), "30/10");
+
+    cooldown.set_seconds(" 45 ").unwrap();
+    assert_eq!((cooldown.global, cooldown.user), (45, 0));
+
+    cooldown.set_seconds("/5").unwrap();
+    assert_eq!((cooldown.global, cooldown.user), (0, 5));
+
+    assert!(cooldown.set_seconds("soon").is_err());
+}
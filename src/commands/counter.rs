@@ -0,0 +1,159 @@
+use std::{
+    collections::HashMap,
+    sync::{Mutex, OnceLock, PoisonError},
+    thread::sleep,
+    time::Duration,
+};
+
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    store::{read_store, Store},
+    twitch::ChannelMessages,
+    websocket::send_to_overlay,
+};
+
+// How often chat looks for counts changed by the CLI or the admin
+const WATCH_INTERVAL: Duration = Duration::from_secs(2);
+
+static SENT_COUNTS: OnceLock<Mutex<HashMap<String, u64>>> = OnceLock::new();
+
+/// A counter's new count, sent to the overlay whenever it changes
+#[derive(Clone, Debug, PartialEq, Deserialize, Serialize)]
+pub struct Counter {
+    pub name: String,
+    pub count: u64,
+}
+
+/// How a chatter changed a counter, like !deaths+ !deaths- or !deaths=5
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum CounterChange {
+    Increment,
+    Decrement,
+    Set(u64),
+}
+
+impl CounterChange {
+    /// Splits a command like deaths+ into the counter's name and the change, None when it isn't one
+    pub fn parse(command: &str) -> Option<(&str, CounterChange)> {
+        let (name, change) = if let Some(name) = command.strip_suffix('+') {
+            (name, CounterChange::Increment)
+        } else if let Some(name) = command.strip_suffix('-') {
+            (name, CounterChange::Decrement)
+        } else {
+            let (name, count) = command.split_once('=')?;
+
+            (name, CounterChange::Set(count.parse().ok()?))
+        };
+
+        if name.is_empty() {
+            return None;
+        }
+
+        Some((name, change))
+    }
+
+    /// Counters don't go below zero
+    pub fn apply(&self, count: u64) -> u64 {
+        match self {
+            CounterChange::Increment => count.saturating_add(1),
+            CounterChange::Decrement => count.saturating_sub(1),
+            CounterChange::Set(count) => *count,
+        }
+    }
+}
+
+/// Sends a counter's count to the overlay, unless it's the count that was sent last
+pub fn send_counter(name: &str, count: u64) {
+    let sent_counts = SENT_COUNTS.get_or_init(|| Mutex::new(HashMap::new()));
+    let mut sent_counts = sent_counts.lock().unwrap_or_else(PoisonError::into_inner);
+    if sent_counts.get(name) == Some(&count) {
+        return;
+    }
+
+    sent_counts.insert(name.to_string(), count);
+    send_to_overlay(ChannelMessages::Counter(Counter {
+        name: name.to_string(),
+        count,
+    }));
+}
+
+/// The count of every counter in the store
+pub fn get_counts(store: &Store) -> HashMap<String, u64> {
+    store
+        .chat_commands
+        .iter()
+        .filter(|(_, command)| command.options.counter)
+        .map(|(name, command)| (name.clone(), command.count))
+        .collect()
+}
+
+/// Sends counts changed by `sergeant commands count` or the admin to the overlay, chat's own
+/// changes are sent as they're made and aren't sent again
+pub fn watch_counters() {
+    let mut counts = read_store().map(|store| get_counts(&store)).unwrap_or_default();
+
+    loop {
+        sleep(WATCH_INTERVAL);
+
+        let Ok(store) = read_store() else {
+            continue;
+        };
+
+        let new_counts = get_counts(&store);
+        for (name, count) in &new_counts {
+            if counts.get(name) != Some(count) {
+                send_counter(name, *count);
+            }
+        }
+
+        counts = new_counts;
+    }
+}
+
+#[test]
+fn test_get_counts() {
+    use crate::store::ChatCommand;
+
+    let mut store = Store::default();
+    let mut deaths = ChatCommand {
+        message: String::from("{count} deaths"),
+        count: 5,
+        ..Default::default()
+    };
+    deaths.options.counter = true;
+    store.chat_commands.insert(String::from("deaths"), deaths);
+
+    let hugs = ChatCommand {
+        message: String::from("hug #{count}"),
+        count: 12,
+        ..Default::default()
+    };
+    store.chat_commands.insert(String::from("hug"), hugs);
+
+    assert_eq!(get_counts(&store), HashMap::from([(String::from("deaths"), 5)]));
+}
+
+#[test]
+fn test_counter_change() {
+    assert_eq!(
+        CounterChange::parse("deaths+"),
+        Some(("deaths", CounterChange::Increment))
+    );
+    assert_eq!(
+        CounterChange::parse("deaths-"),
+        Some(("deaths", CounterChange::Decrement))
+    );
+    assert_eq!(
+        CounterChange::parse("deaths=5"),
+        Some(("deaths", CounterChange::Set(5)))
+    );
+    assert_eq!(CounterChange::parse("deaths"), None);
+    assert_eq!(CounterChange::parse("deaths=many"), None);
+    assert_eq!(CounterChange::parse("deaths=-1"), None);
+    assert_eq!(CounterChange::parse("+"), None);
+
+    assert_eq!(CounterChange::Increment.apply(4), 5);
+    assert_eq!(CounterChange::Decrement.apply(0), 0);
+    assert_eq!(CounterChange::Set(5).apply(12), 5);
+}
@@ -0,0 +1,140 @@
+use std::sync::OnceLock;
+
+use serde::{Deserialize, Serialize};
+
+pub const DEFAULT_PREFIX: &str = "!";
+
+// 7TV adds this tag character to repeated messages so Twitch doesn't drop them as duplicates
+const DUPLICATE_BYPASS: &str = "\u{e0000}";
+
+static COMMAND_MATCHER: OnceLock<CommandMatcher> = OnceLock::new();
+
+/// Splits chat messages into a command and its arguments
+#[derive(Clone, Debug, PartialEq)]
+pub struct CommandMatcher {
+    pub prefix: String,
+    pub ignore_case: bool,
+}
+
+impl Default for CommandMatcher {
+    fn default() -> Self {
+        CommandMatcher {
+            prefix: DEFAULT_PREFIX.to_string(),
+            ignore_case: false,
+        }
+    }
+}
+
+impl CommandMatcher {
+    /// The command and arguments of a message, None when it doesn't start with the prefix
+    pub fn parse<'a>(&self, message: &'a str) -> Option<(&'a str, Vec<&'a str>)> {
+        let message = message.trim_start().strip_prefix(&self.prefix)?;
+
+        let mut words = message.split_whitespace().filter(|word| *word != DUPLICATE_BYPASS);
+        let command = words.next()?;
+
+        // "! lurk" isn't a command, the name has to follow the prefix
+        if !message.starts_with(command) {
+            return None;
+        }
+
+        Some((command, words.collect()))
+    }
+
+    pub fn is_match(&self, command: &str, name: &str) -> bool {
+        if self.ignore_case {
+            command.to_lowercase() == name.to_lowercase()
+        } else {
+            command == name
+        }
+    }
+
+    /// How a command is typed in chat, like !lurk
+    pub fn get_trigger(&self, name: &str) -> String {
+        format!("{}{name}", self.prefix)
+    }
+}
+
+/// Sets how chat commands are matched, has to be called before chat starts
+pub fn set_command_matcher(matcher: CommandMatcher) {
+    let _ = COMMAND_MATCHER.set(matcher);
+}
+
+pub fn get_command_matcher() -> &'static CommandMatcher {
+    COMMAND_MATCHER.get_or_init(CommandMatcher::default)
+}
+
+/// How many arguments a command takes, with a usage hint for chatters that get it wrong
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+pub struct Arguments {
+    #[serde(default)]
+    pub min: usize,
+    /// Any number of arguments when not set
+    #[serde(default)]
+    pub max: Option<usize>,
+    /// Shown after the command, like "<user> [message]"
+    #[serde(default)]
+    pub usage: String,
+}
+
+impl Arguments {
+    pub fn accepts(&self, count: usize) -> bool {
+        count >= self.min && self.max.is_none_or(|max| count <= max)
+    }
+
+    /// The hint sent to chatters that used the wrong number of arguments, None without a usage
+    pub fn get_usage_hint(&self, trigger: &str) -> Option<String> {
+        if self.usage.is_empty() {
+            return None;
+        }
+
+        Some(format!("Usage: {trigger} {}", self.usage))
+    }
+}
+
+#[test]
+fn test_command_matcher_parse() {
+    let matcher = CommandMatcher::default();
+
+    assert_eq!(matcher.parse("!lurk"), Some(("lurk", vec![])));
+    assert_eq!(matcher.parse("!so @friend  now"), Some(("so", vec!["@friend", "now"])));
+    assert_eq!(
+        matcher.parse("!sr https://example.com \u{e0000}"),
+        Some(("sr", vec!["https://example.com"]))
+    );
+    assert_eq!(matcher.parse("hello !lurk"), None);
+    assert_eq!(matcher.parse("! lurk"), None);
+    assert_eq!(matcher.parse("!"), None);
+
+    let matcher = CommandMatcher {
+        prefix: String::from("?"),
+        ignore_case: true,
+    };
+
+    assert_eq!(matcher.parse("!lurk"), None);
+    assert_eq!(matcher.parse("?Lurk now"), Some(("Lurk", vec!["now"])));
+    assert!(matcher.is_match("Lurk", "lurk"));
+    assert!(!CommandMatcher::default().is_match("Lurk", "lurk"));
+    assert_eq!(matcher.get_trigger("lurk"), "?lurk");
+}
+
+#[test]
+fn test_arguments() {
+    let arguments = Arguments {
+        min: 1,
+        max: Some(2),
+        usage: String::from("<user> [message]"),
+    };
+
+    assert!(!arguments.accepts(0));
+    assert!(arguments.accepts(1));
+    assert!(arguments.accepts(2));
+    assert!(!arguments.accepts(3));
+    assert_eq!(
+        arguments.get_usage_hint("!so"),
+        Some(String::from("Usage: !so <user> [message]"))
+    );
+
+    assert!(Arguments::default().accepts(10));
+    assert_eq!(Arguments::default().get_usage_hint("!so"), None);
+}
@@ -5,9 +5,24 @@ use std::{error::Error, fs, thread::sleep};
 
 use base64::prelude::*;
 
-use crate::{twitch::pubsub::send_to_error_log, utils::get_data_directory};
-
-const TWITCH_SCOPES: [&str; 17] = [
+pub mod cooldown;
+pub mod counter;
+pub mod matcher;
+pub mod permissions;
+pub mod template;
+
+use cooldown::Cooldown;
+use counter::CounterChange;
+use matcher::Arguments;
+use permissions::Permissions;
+
+use crate::{
+    store::{read_store, update_store, ChatAnnouncement, ChatCommand, Store},
+    twitch::emote_providers::DEFAULT_PROVIDERS,
+    utils::get_data_directory,
+};
+
+const TWITCH_SCOPES: [&str; 20] = [
     "channel:read:subscriptions",
     "chat:read",
     "chat:edit",
@@ -25,8 +40,24 @@ const TWITCH_SCOPES: [&str; 17] = [
     "user:read:chat",
     "user:bot",
     "channel:bot",
+    "user:read:whispers",
+    "user:manage:whispers",
+    "moderator:read:followers",
 ];
 
+// The bot account only chats and whispers, everything else stays on the broadcaster's token
+const BOT_SCOPES: [&str; 6] = [
+    "chat:read",
+    "chat:edit",
+    "user:read:chat",
+    "user:write:chat",
+    "user:bot",
+    "user:manage:whispers",
+];
+
+pub const TOKEN_FILE: &str = "oath_token.txt";
+pub const BOT_TOKEN_FILE: &str = "bot_token.txt";
+
 const TWITCH_CREATE_TOKEN: &str = "https://twitchtokengenerator.com/api/create/[APP_NAME]/[SCOPES]";
 const TWITCH_TOKEN_STATUS: &str = "https://twitchtokengenerator.com/api/status/[ID]";
 
@@ -55,45 +86,125 @@ pub struct TokenStatus {
 }
 
 pub fn add_chat_command(command_name: &str, message: &str, timing: Option<usize>) -> Result<(), Box<dyn Error>> {
-    let mut target_dir = "chat_commands";
+    update_store(|store| {
+        match timing {
+            Some(timing) => {
+                let announcement = ChatAnnouncement {
+                    timing: timing as u64,
+                    message: message.to_string(),
+                };
+                store.chat_announcements.insert(command_name.to_string(), announcement);
+            }
 
-    let file_contents = if let Some(timing) = timing {
-        target_dir = "chat_announcements";
-        format!("{}\n{}", timing, message)
-    } else {
-        message.to_string()
-    };
+            // Changing the message of a command keeps its options, count and aliases
+            None => {
+                let command = store.chat_commands.entry(command_name.to_string()).or_default();
+                command.message = message.to_string();
+            }
+        }
 
-    let mut command_path = get_data_directory(Some(target_dir))?;
+        Ok(())
+    })
+}
 
-    if !command_path.exists() {
-        std::fs::create_dir_all(&command_path)?;
+pub fn get_chat_command(command_name: &str) -> Result<String, Box<dyn Error>> {
+    match read_store()?.chat_commands.remove(command_name) {
+        Some(command) => Ok(command.message),
+        None => Err(format!("No chat command named {command_name}").into()),
     }
+}
 
-    command_path.push(command_name);
+/// Settings for a chat command that aren't part of its message
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+pub struct CommandOptions {
+    /// Answer the chatter with a whisper instead of in chat
+    #[serde(default)]
+    pub whisper: bool,
+    /// Answer as a threaded reply to the message that used it instead of a regular chat message
+    #[serde(default)]
+    pub reply: bool,
+    #[serde(default)]
+    pub cooldown: Cooldown,
+    #[serde(default)]
+    pub permissions: Permissions,
+    #[serde(default)]
+    pub arguments: Arguments,
+    /// {count} only changes when moderators use !name+ !name- or !name=5, not every time it's used
+    #[serde(default)]
+    pub counter: bool,
+}
 
-    fs::write(command_path, file_contents)?;
+/// The options of !commands are set like the options of a chat command named commands
+pub fn set_command_options(command_name: &str, options: &CommandOptions) -> Result<(), Box<dyn Error>> {
+    update_store(|store| {
+        match store.chat_commands.get_mut(command_name) {
+            Some(command) => command.options = options.clone(),
+            None if command_name == "commands" => store.command_list = options.clone(),
+            None => return Err(format!("No chat command named {command_name}").into()),
+        }
 
-    Ok(())
+        Ok(())
+    })
 }
 
-pub fn get_list(directory: &str) -> Result<Vec<String>, Box<dyn Error>> {
-    let command_path = get_data_directory(Some(directory))?;
-    let mut commands = vec![];
-    let dir_entries = fs::read_dir(command_path)?;
+/// The options of a chat command, commands that were added without any use the defaults
+pub fn get_command_options(command_name: &str) -> CommandOptions {
+    let Ok(mut store) = read_store() else {
+        return CommandOptions::default();
+    };
 
-    for entry in dir_entries {
-        let entry = entry?;
-        let path = entry.path();
-        if path.is_file() {
-            let file_name = path.file_name();
-            if let Some(file_name) = file_name {
-                commands.push(file_name.to_string_lossy().to_string());
-            }
-        }
+    match store.chat_commands.remove(command_name) {
+        Some(command) => command.options,
+        None if command_name == "commands" => store.command_list,
+        None => CommandOptions::default(),
     }
+}
+
+/// How many times a command has used {count}, zero until it's first used
+pub fn get_command_count(command_name: &str) -> u64 {
+    read_store()
+        .ok()
+        .and_then(|store| store.chat_commands.get(command_name).map(|command| command.count))
+        .unwrap_or_default()
+}
+
+pub fn increment_command_count(command_name: &str) -> Result<u64, Box<dyn Error>> {
+    update_store(|store| {
+        let command = get_command_mut(store, command_name)?;
+        command.count += 1;
+
+        Ok(command.count)
+    })
+}
+
+pub fn set_command_count(command_name: &str, count: u64) -> Result<(), Box<dyn Error>> {
+    update_store(|store| {
+        get_command_mut(store, command_name)?.count = count;
+
+        Ok(())
+    })
+}
+
+/// Changes a counter's count as it's saved now, not as it was when the message came in, so changes
+/// made at the same time aren't lost. Returns the command with its new count
+pub fn change_command_count(command_name: &str, change: CounterChange) -> Result<ChatCommand, Box<dyn Error>> {
+    update_store(|store| {
+        let command = get_command_mut(store, command_name)?;
+        command.count = change.apply(command.count);
+
+        Ok(command.clone())
+    })
+}
+
+fn get_command_mut<'a>(store: &'a mut Store, command_name: &str) -> Result<&'a mut ChatCommand, Box<dyn Error>> {
+    store
+        .chat_commands
+        .get_mut(command_name)
+        .ok_or_else(|| format!("No chat command named {command_name}").into())
+}
 
-    Ok(commands)
+pub fn get_list(item_type: &str) -> Result<Vec<String>, Box<dyn Error>> {
+    Ok(read_store()?.get_items(item_type)?.into_keys().collect())
 }
 
 pub fn get_list_commands() -> Result<Vec<String>, Box<dyn Error>> {
@@ -106,81 +217,165 @@ pub struct SgtFile {
     pub contents: Value<String>,
 }
 
-pub fn get_list_with_contents(directory: &str) -> Result<Vec<SgtFile>, Box<dyn Error>> {
-    let command_path = get_data_directory(Some(directory))?;
-    let mut commands: Vec<SgtFile> = vec![];
-    let dir_entries = fs::read_dir(command_path)?;
+pub fn get_list_with_contents(item_type: &str) -> Result<Vec<SgtFile>, Box<dyn Error>> {
+    let items = read_store()?.get_items(item_type)?;
 
-    for entry in dir_entries {
-        let entry = entry?;
-        let path = entry.path();
-        if !path.is_file() {
-            continue;
+    Ok(items
+        .into_iter()
+        .map(|(name, contents)| SgtFile {
+            name: name.into(),
+            contents: contents.into(),
+        })
+        .collect())
+}
+
+/// Removes a chat command with its options, count and aliases, or an announcement
+pub fn remove_chat_command(command_name: &str) -> Result<(), Box<dyn Error>> {
+    update_store(|store| {
+        if store.chat_commands.remove(command_name).is_none() {
+            store.chat_announcements.remove(command_name);
         }
 
-        let file_name = path.file_name();
-        if file_name.is_none() {
-            continue;
+        Ok(())
+    })
+}
+
+/// Points alias at a chat command, so !alias answers like the command does
+pub fn add_command_alias(alias: &str, command_name: &str) -> Result<(), Box<dyn Error>> {
+    // !commands is answered before chat commands are looked up, so it can't be an alias
+    if alias == "commands" {
+        return Err("commands can't be an alias".into());
+    }
+
+    update_store(|store| {
+        if store.chat_commands.contains_key(alias) {
+            return Err(format!("{alias} is already one of the chat commands").into());
         }
 
-        let file_name = file_name.unwrap();
-        let name = file_name.to_string_lossy().to_string();
-        let contents = fs::read_to_string(path);
-        if contents.is_err() {
-            continue;
+        for command in store.chat_commands.values_mut() {
+            command.aliases.retain(|aliased| aliased != alias);
         }
 
-        let contents = contents.unwrap();
+        add_alias(alias, &mut get_command_mut(store, command_name)?.aliases);
 
-        commands.push(SgtFile {
-            name: name.into(),
-            contents: contents.into(),
-        });
-    }
+        Ok(())
+    })
+}
 
-    Ok(commands)
+pub fn remove_command_alias(alias: &str) -> Result<(), Box<dyn Error>> {
+    update_store(|store| {
+        for command in store.chat_commands.values_mut() {
+            command.aliases.retain(|aliased| aliased != alias);
+        }
+
+        Ok(())
+    })
 }
 
-pub fn remove_chat_command(command_name: &str) -> Result<(), Box<dyn Error>> {
-    let mut command_path = get_data_directory(Some("chat_commands"))?;
-    command_path.push(command_name);
-    if command_path.exists() {
-        return Ok(fs::remove_file(command_path)?);
+pub fn list_command_aliases() {
+    if let Ok(store) = read_store() {
+        list_aliases(
+            store
+                .chat_commands
+                .iter()
+                .map(|(name, command)| (name, &command.aliases)),
+        );
     }
+}
 
-    let mut command_path = get_data_directory(Some("chat_announcements"))?;
-    command_path.push(command_name);
-    if command_path.exists() {
-        return Ok(fs::remove_file(command_path)?);
-    }
+/// Points alias at an irc-action, so !alias runs the same CLI command
+pub fn add_action_alias(alias: &str, action_name: &str) -> Result<(), Box<dyn Error>> {
+    update_store(|store| {
+        if store.irc_actions.contains_key(alias) {
+            return Err(format!("{alias} is already one of the irc actions").into());
+        }
 
-    Ok(())
+        for action in store.irc_actions.values_mut() {
+            action.aliases.retain(|aliased| aliased != alias);
+        }
+
+        let action = store
+            .irc_actions
+            .get_mut(action_name)
+            .ok_or_else(|| format!("No irc actions named {action_name}"))?;
+        add_alias(alias, &mut action.aliases);
+
+        Ok(())
+    })
 }
 
-pub fn get_list_announcements() -> Result<Vec<String>, Box<dyn Error>> {
-    let command_path = get_data_directory(Some("chat_announcements"))?;
-    let mut commands = vec![];
-    let dir_entries = fs::read_dir(command_path)?;
-
-    for entry in dir_entries {
-        let entry = entry?;
-        let path = entry.path();
-        if path.is_file() {
-            let file_name = path.file_name();
-            if let Some(file_name) = file_name {
-                commands.push(file_name.to_string_lossy().to_string());
-            }
+pub fn remove_action_alias(alias: &str) -> Result<(), Box<dyn Error>> {
+    update_store(|store| {
+        for action in store.irc_actions.values_mut() {
+            action.aliases.retain(|aliased| aliased != alias);
         }
+
+        Ok(())
+    })
+}
+
+pub fn list_action_aliases() {
+    if let Ok(store) = read_store() {
+        list_aliases(store.irc_actions.iter().map(|(name, action)| (name, &action.aliases)));
     }
+}
 
-    Ok(commands)
+/// The aliases that point at a chat command
+pub fn get_command_aliases(command_name: &str) -> Vec<String> {
+    read_store()
+        .ok()
+        .and_then(|mut store| store.chat_commands.remove(command_name))
+        .map(|command| command.aliases)
+        .unwrap_or_default()
 }
 
-pub fn authenticate_with_twitch() -> Result<(), Box<dyn Error>> {
+/// The aliases that point at an irc-action
+pub fn get_action_aliases(action_name: &str) -> Vec<String> {
+    read_store()
+        .ok()
+        .and_then(|mut store| store.irc_actions.remove(action_name))
+        .map(|action| action.aliases)
+        .unwrap_or_default()
+}
+
+fn add_alias(alias: &str, aliases: &mut Vec<String>) {
+    aliases.push(alias.to_string());
+    aliases.sort();
+}
+
+fn list_aliases<'a>(items: impl Iterator<Item = (&'a String, &'a Vec<String>)>) {
+    let mut aliases: Vec<(&String, &String)> = items
+        .flat_map(|(name, aliases)| aliases.iter().map(move |alias| (alias, name)))
+        .collect();
+    aliases.sort();
+
+    if aliases.is_empty() {
+        println!("Currently no aliases have been added.");
+        return;
+    }
+
+    println!("Available aliases:");
+    for (alias, name) in aliases {
+        println!("- {alias} -> {name}");
+    }
+}
+
+pub fn get_list_announcements() -> Result<Vec<String>, Box<dyn Error>> {
+    get_list("chat_announcements")
+}
+
+/// Logs in the broadcaster, or the bot account when bot is true, and stores its token
+pub fn authenticate_with_twitch(bot: bool) -> Result<(), Box<dyn Error>> {
+    let scopes = if bot {
+        BOT_SCOPES.join("+")
+    } else {
+        TWITCH_SCOPES.join("+")
+    };
+
     let app_name = BASE64_STANDARD.encode(clap::crate_name!());
     let url = TWITCH_CREATE_TOKEN
         .replace("[APP_NAME]", &app_name)
-        .replace("[SCOPES]", &TWITCH_SCOPES.join("+"));
+        .replace("[SCOPES]", &scopes);
 
     let token_response = ureq::get(&url).call();
     if token_response.is_err() {
@@ -209,7 +404,11 @@ pub fn authenticate_with_twitch() -> Result<(), Box<dyn Error>> {
 
         let token_status = serde_json::from_str::<TokenStatus>(&token_status_response?.into_string()?)?;
         if token_status.success {
-            store_token(token_status)?;
+            if bot {
+                store_bot_token(token_status)?;
+            } else {
+                store_token(token_status)?;
+            }
             break;
         }
 
@@ -222,8 +421,16 @@ pub fn authenticate_with_twitch() -> Result<(), Box<dyn Error>> {
 }
 
 pub fn store_token(token_status: TokenStatus) -> Result<(), Box<dyn Error>> {
+    write_token(TOKEN_FILE, token_status)
+}
+
+pub fn store_bot_token(token_status: TokenStatus) -> Result<(), Box<dyn Error>> {
+    write_token(BOT_TOKEN_FILE, token_status)
+}
+
+fn write_token(file_name: &str, token_status: TokenStatus) -> Result<(), Box<dyn Error>> {
     let mut token_dir = get_data_directory(Some("token"))?;
-    token_dir.push("oath_token.txt");
+    token_dir.push(file_name);
 
     fs::write(token_dir, serde_json::to_string(&token_status)?)?;
 
@@ -244,78 +451,144 @@ fn list(list_type: String) {
     }
 }
 
-fn get_item(item_name: &str, item_type: &str) -> Result<String, Box<dyn Error>> {
-    let mut item_path = get_data_directory(Some(item_type))?;
-    item_path.push(item_name);
+pub fn add_reward(reward_name: &str, cli: &str) -> Result<(), Box<dyn Error>> {
+    update_store(|store| {
+        store.chat_rewards.insert(reward_name.to_string(), cli.to_string());
 
-    if item_path.exists() {
-        let item = fs::read_to_string(item_path.clone())?;
-        send_to_error_log(
-            format!("Read item from {item_path:?}, item: '{item}'"),
-            String::from(""),
-        );
+        Ok(())
+    })
+}
 
-        return Ok(item);
+pub fn get_reward(reward_name: &str) -> Result<String, Box<dyn Error>> {
+    match read_store()?.chat_rewards.remove(reward_name) {
+        Some(reward) => Ok(reward),
+        None => Err(format!("No chat reward named {reward_name}").into()),
     }
+}
 
-    let human_readable = item_type.replace('_', " ");
-    Err(format!("No {human_readable} found").into())
+pub fn list_rewards() {
+    list("chat_rewards".to_string())
 }
 
-pub fn remove_item(item_name: &str, item_type: &str) -> Result<(), Box<dyn Error>> {
-    let mut item_path = get_data_directory(Some(item_type))?;
-    item_path.push(item_name);
-    if item_path.exists() {
-        return Ok(fs::remove_file(item_path)?);
-    }
+pub fn remove_reward(reward_name: &str) -> Result<(), Box<dyn Error>> {
+    update_store(|store| {
+        store.chat_rewards.remove(reward_name);
 
-    Ok(())
+        Ok(())
+    })
 }
 
-pub fn add_item(item_name: &str, cli: &str, item_type: &str) -> Result<(), Box<dyn Error>> {
-    let file_contents = cli.to_string();
+/// Removes an irc-action with its options and aliases
+pub fn remove_action(action_name: &str) -> Result<(), Box<dyn Error>> {
+    update_store(|store| {
+        store.irc_actions.remove(action_name);
 
-    let mut item_path = get_data_directory(Some(item_type))?;
+        Ok(())
+    })
+}
 
-    if !item_path.exists() {
-        std::fs::create_dir_all(&item_path)?;
-    }
+/// Changing the CLI command of an irc-action keeps its options and aliases
+pub fn add_action(action_name: &str, cli: &str, options: &ActionOptions) -> Result<(), Box<dyn Error>> {
+    update_store(|store| {
+        let action = store.irc_actions.entry(action_name.to_string()).or_default();
+        action.command = cli.to_string();
+        action.options = options.clone();
 
-    item_path.push(item_name);
+        Ok(())
+    })
+}
 
-    fs::write(item_path, file_contents)?;
+/// Settings for an irc-action that aren't part of its CLI command
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+pub struct ActionOptions {
+    #[serde(default)]
+    pub cooldown: Cooldown,
+    #[serde(default)]
+    pub permissions: Permissions,
+    #[serde(default)]
+    pub arguments: Arguments,
+}
 
-    Ok(())
+pub fn get_action_options(action_name: &str) -> ActionOptions {
+    read_store()
+        .ok()
+        .and_then(|mut store| store.irc_actions.remove(action_name))
+        .map(|action| action.options)
+        .unwrap_or_default()
 }
 
-pub fn add_reward(reward_name: &str, cli: &str) -> Result<(), Box<dyn Error>> {
-    add_item(reward_name, cli, "chat_rewards")
+pub fn list_actions() {
+    list("irc_actions".to_string())
 }
 
-pub fn get_reward(reward_name: &str) -> Result<String, Box<dyn Error>> {
-    get_item(reward_name, "chat_rewards")
+pub fn get_action(action_name: &str) -> Result<String, Box<dyn Error>> {
+    match read_store()?.irc_actions.remove(action_name) {
+        Some(action) => Ok(action.command),
+        None => Err(format!("No irc actions named {action_name}").into()),
+    }
 }
 
-pub fn list_rewards() {
-    list("chat_rewards".to_string())
+pub fn add_bits_action(min_bits: u64, cli: &str) -> Result<(), Box<dyn Error>> {
+    update_store(|store| {
+        store.bits_actions.insert(min_bits.to_string(), cli.to_string());
+
+        Ok(())
+    })
 }
 
-pub fn remove_reward(reward_name: &str) -> Result<(), Box<dyn Error>> {
-    remove_item(reward_name, "chat_rewards")
+pub fn list_bits_actions() {
+    list("bits_actions".to_string())
 }
 
-pub fn remove_action(action_name: &str) -> Result<(), Box<dyn Error>> {
-    remove_item(action_name, "irc_actions")
+pub fn remove_bits_action(min_bits: u64) -> Result<(), Box<dyn Error>> {
+    update_store(|store| {
+        store.bits_actions.remove(&min_bits.to_string());
+
+        Ok(())
+    })
 }
 
-pub fn add_action(action_name: &str, cli: &str) -> Result<(), Box<dyn Error>> {
-    add_item(action_name, cli, "irc_actions")
+fn get_emote_providers_channel(channel: &str) -> String {
+    channel.trim_start_matches('#').to_lowercase()
 }
 
-pub fn list_actions() {
-    list("irc_actions".to_string())
+pub fn set_emote_providers(channel: &str, providers: &[String]) -> Result<(), Box<dyn Error>> {
+    update_store(|store| {
+        store
+            .emote_providers
+            .insert(get_emote_providers_channel(channel), providers.to_vec());
+
+        Ok(())
+    })
 }
 
-pub fn get_action(action_name: &str) -> Result<String, Box<dyn Error>> {
-    get_item(action_name, "irc_actions")
+/// The emote providers enabled for a channel, channels that haven't been configured use all of them
+pub fn get_emote_providers(channel: &str) -> Vec<String> {
+    read_store()
+        .ok()
+        .and_then(|mut store| store.emote_providers.remove(&get_emote_providers_channel(channel)))
+        .unwrap_or_else(|| DEFAULT_PROVIDERS.iter().map(|provider| provider.to_string()).collect())
+}
+
+pub fn list_emote_providers() {
+    if let Ok(list) = get_list_with_contents("emote_providers") {
+        if list.is_empty() {
+            println!("Currently no emote providers have been configured, channels use all providers.");
+
+            return;
+        }
+
+        println!("Available emote providers:");
+        for item in list {
+            println!("- {}: {}", item.name.to_ref(), item.contents.to_ref());
+        }
+    }
+}
+
+pub fn remove_emote_providers(channel: &str) -> Result<(), Box<dyn Error>> {
+    update_store(|store| {
+        store.emote_providers.remove(&get_emote_providers_channel(channel));
+
+        Ok(())
+    })
 }
@@ -0,0 +1,197 @@
+use std::{fmt, str::FromStr};
+
+use serde::{Deserialize, Serialize};
+
+use crate::twitch::parse::ChatMessage;
+
+pub const ROLES: [&str; 6] = ["everyone", "follower", "subscriber", "vip", "moderator", "broadcaster"];
+
+/// Who can use a chat command or irc-action, each role also lets in the roles after it
+#[derive(Clone, Copy, Debug, Default, PartialEq, PartialOrd, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Role {
+    #[default]
+    Everyone,
+    Follower,
+    Subscriber,
+    Vip,
+    Moderator,
+    Broadcaster,
+}
+
+impl FromStr for Role {
+    type Err = String;
+
+    fn from_str(role: &str) -> Result<Self, Self::Err> {
+        match role.to_lowercase().as_str() {
+            "everyone" => Ok(Role::Everyone),
+            "follower" => Ok(Role::Follower),
+            "subscriber" => Ok(Role::Subscriber),
+            "vip" => Ok(Role::Vip),
+            "moderator" => Ok(Role::Moderator),
+            "broadcaster" => Ok(Role::Broadcaster),
+            _ => Err(format!("Unknown role: {role}")),
+        }
+    }
+}
+
+impl fmt::Display for Role {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(ROLES[*self as usize])
+    }
+}
+
+impl Role {
+    /// The highest role the chatter's badges show, followers can't be told apart from tags
+    pub fn of(chatter: &ChatMessage) -> Self {
+        if chatter.broadcaster {
+            Role::Broadcaster
+        } else if chatter.moderator {
+            Role::Moderator
+        } else if chatter.vip {
+            Role::Vip
+        } else if chatter.subscriber {
+            Role::Subscriber
+        } else {
+            Role::Everyone
+        }
+    }
+}
+
+/// The role needed to use a chat command or irc-action, with logins that are always or never let through
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+pub struct Permissions {
+    #[serde(default)]
+    pub role: Role,
+    #[serde(default)]
+    pub allow: Vec<String>,
+    #[serde(default)]
+    pub deny: Vec<String>,
+}
+
+impl Permissions {
+    /// Denied logins are turned away before allowed ones are let through, is_following is
+    /// only asked when followers are let in and the chatter has no higher role
+    pub fn is_allowed(&self, chatter: &ChatMessage, is_following: impl FnOnce() -> bool) -> bool {
+        // The nickname is the display name, which can be changed to something other than the login
+        let login = get_login(&chatter.login);
+
+        if self.deny.contains(&login) {
+            return false;
+        }
+
+        if self.allow.contains(&login) {
+            return true;
+        }
+
+        let role = Role::of(chatter);
+        if role >= self.role {
+            return true;
+        }
+
+        self.role == Role::Follower && is_following()
+    }
+}
+
+/// The "role +allowed -denied" form edited in the admin, like "moderator +dallas -ronni"
+impl fmt::Display for Permissions {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let mut parts = vec![];
+        if self.role != Role::Everyone {
+            parts.push(self.role.to_string());
+        }
+
+        parts.extend(self.allow.iter().map(|login| format!("+{login}")));
+        parts.extend(self.deny.iter().map(|login| format!("-{login}")));
+
+        f.write_str(&parts.join(" "))
+    }
+}
+
+impl FromStr for Permissions {
+    type Err = String;
+
+    fn from_str(permissions: &str) -> Result<Self, Self::Err> {
+        let mut parsed = Permissions::default();
+
+        for part in permissions.split_whitespace() {
+            if let Some(login) = part.strip_prefix('+') {
+                parsed.allow.push(get_login(login));
+            } else if let Some(login) = part.strip_prefix('-') {
+                parsed.deny.push(get_login(login));
+            } else {
+                parsed.role = part.parse()?;
+            }
+        }
+
+        Ok(parsed)
+    }
+}
+
+/// Logins are compared in lowercase and without the @ of a mention
+pub fn get_login(login: &str) -> String {
+    login.trim().trim_start_matches('@').to_lowercase()
+}
+
+#[cfg(test)]
+fn get_chatter(login: &str, badges: &[&str]) -> ChatMessage {
+    ChatMessage {
+        nickname: login.to_string(),
+        login: login.to_lowercase(),
+        subscriber: badges.contains(&"subscriber"),
+        vip: badges.contains(&"vip"),
+        moderator: badges.contains(&"moderator"),
+        broadcaster: badges.contains(&"broadcaster"),
+        ..Default::default()
+    }
+}
+
+#[test]
+fn test_permissions_roles() {
+    let permissions = Permissions {
+        role: Role::Vip,
+        ..Default::default()
+    };
+
+    assert!(!permissions.is_allowed(&get_chatter("viewer", &[]), || true));
+    assert!(!permissions.is_allowed(&get_chatter("sub", &["subscriber"]), || true));
+    assert!(permissions.is_allowed(&get_chatter("vip", &["vip"]), || false));
+    assert!(permissions.is_allowed(&get_chatter("mod", &["moderator"]), || false));
+    assert!(permissions.is_allowed(&get_chatter("s9tpepper_", &["broadcaster"]), || false));
+
+    let followers = Permissions {
+        role: Role::Follower,
+        ..Default::default()
+    };
+
+    assert!(followers.is_allowed(&get_chatter("viewer", &[]), || true));
+    assert!(!followers.is_allowed(&get_chatter("viewer", &[]), || false));
+    // Subscribers are let in without looking up whether they follow
+    assert!(followers.is_allowed(&get_chatter("sub", &["subscriber"]), || unreachable!()));
+}
+
+#[test]
+fn test_permissions_lists() {
+    let permissions: Permissions = "moderator +@Dallas -ronni".parse().unwrap();
+
+    assert_eq!(permissions.role, Role::Moderator);
+    assert_eq!(permissions.allow, vec!["dallas"]);
+    assert_eq!(permissions.deny, vec!["ronni"]);
+    assert_eq!(permissions.to_string(), "moderator +dallas -ronni");
+
+    assert!(permissions.is_allowed(&get_chatter("Dallas", &[]), || false));
+    assert!(!permissions.is_allowed(&get_chatter("ronni", &["moderator"]), || false));
+
+    // A display name matching a denied login doesn't turn away someone else
+    let denied: Permissions = "-ronni".parse().unwrap();
+    let renamed = ChatMessage {
+        nickname: String::from("ronni"),
+        ..get_chatter("not_ronni", &[])
+    };
+    assert!(denied.is_allowed(&renamed, || false));
+    assert!(!denied.is_allowed(&get_chatter("Ronni", &[]), || false));
+
+    assert_eq!(Permissions::from_str("").unwrap(), Permissions::default());
+    assert_eq!(Permissions::default().to_string(), "");
+    assert!(Permissions::from_str("admins").is_err());
+}
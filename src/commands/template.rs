@@ -0,0 +1,409 @@
+use std::{
+    error::Error,
+    time::{Duration, SystemTime, UNIX_EPOCH},
+};
+
+use serde::Deserialize;
+use time::{format_description::well_known::Rfc3339, OffsetDateTime};
+
+use crate::twitch::{
+    api::TwitchApiResponse,
+    parse::ChatMessage,
+    pubsub::{send_to_error_log, Credentials},
+};
+
+use super::{get_command_count, get_command_options, increment_command_count};
+
+const STREAMS_URL: &str = "https://api.twitch.tv/helix/streams";
+const FOLLOWERS_URL: &str = "https://api.twitch.tv/helix/channels/followers";
+const USERS_URL: &str = "https://api.twitch.tv/helix/users";
+
+/// What a chat command template is rendered for, worked out from the message that triggered it
+pub struct TemplateContext<'a> {
+    pub command: &'a str,
+    pub user: &'a str,
+    pub user_id: &'a str,
+    pub channel: &'a str,
+    pub args: Vec<&'a str>,
+}
+
+impl<'a> TemplateContext<'a> {
+    pub fn from_message(command: &'a str, trigger: &'a ChatMessage, args: Vec<&'a str>) -> Self {
+        TemplateContext {
+            command,
+            user: &trigger.nickname,
+            user_id: &trigger.user_id,
+            channel: trigger.channel.trim_start_matches('#'),
+            args,
+        }
+    }
+}
+
+/// Looks up the variables that can change between renders, so templates can be previewed
+/// and tested without calling Twitch
+pub trait TemplateLookup {
+    /// How long the channel has been live, None when it's offline
+    fn uptime(&mut self, channel: &str) -> Option<Duration>;
+    /// How long the user has followed the channel, None when they don't
+    fn followage(&mut self, channel: &str, user_id: &str) -> Option<Duration>;
+    fn count(&mut self, command: &str) -> u64;
+    /// A random index below choices
+    fn random(&mut self, choices: usize) -> usize;
+}
+
+/// Renders a template, variables that aren't known are left as they were written:
+/// {user} {args} {arg1} {channel} {uptime} {followage} {random:a|b|c} {count}
+pub fn render_template(template: &str, context: &TemplateContext, lookup: &mut impl TemplateLookup) -> String {
+    let mut rendered = String::new();
+    let mut rest = template;
+
+    // Looking up {count} can count the use, so it's looked up once however often it's written
+    let mut count = None;
+
+    while let Some(start) = rest.find('{') {
+        rendered.push_str(&rest[..start]);
+        rest = &rest[start..];
+
+        let Some(end) = rest.find('}') else {
+            break;
+        };
+
+        let variable = &rest[1..end];
+        match render_variable(variable, context, lookup, &mut count) {
+            Some(value) => rendered.push_str(&value),
+            None => rendered.push_str(&rest[..=end]),
+        }
+
+        rest = &rest[end + 1..];
+    }
+
+    rendered.push_str(rest);
+
+    rendered
+}
+
+fn render_variable(
+    variable: &str,
+    context: &TemplateContext,
+    lookup: &mut impl TemplateLookup,
+    count: &mut Option<u64>,
+) -> Option<String> {
+    if let Some(choices) = variable.strip_prefix("random:") {
+        let choices: Vec<&str> = choices.split('|').collect();
+
+        return Some(choices[lookup.random(choices.len())].to_string());
+    }
+
+    // Missing arguments render as nothing, so optional arguments don't leave {arg2} behind
+    if let Some(index) = variable
+        .strip_prefix("arg")
+        .and_then(|index| index.parse::<usize>().ok())
+    {
+        let arg = index.checked_sub(1).and_then(|index| context.args.get(index));
+
+        return Some(arg.map(|arg| arg.to_string()).unwrap_or_default());
+    }
+
+    let value = match variable {
+        "user" => context.user.to_string(),
+        "args" => context.args.join(" "),
+        "channel" => context.channel.to_string(),
+        "count" => count.get_or_insert_with(|| lookup.count(context.command)).to_string(),
+
+        "uptime" => match lookup.uptime(context.channel) {
+            Some(uptime) => format_duration(uptime),
+            None => format!("{} is offline", context.channel),
+        },
+
+        "followage" => match lookup.followage(context.channel, context.user_id) {
+            Some(followage) => format_duration(followage),
+            None => format!("{} is not following {}", context.user, context.channel),
+        },
+
+        _ => return None,
+    };
+
+    Some(value)
+}
+
+/// The two largest units of a duration, like "2 hours 14 minutes" or "1 year 3 months"
+pub fn format_duration(duration: Duration) -> String {
+    const UNITS: [(&str, u64); 6] = [
+        ("year", 365 * 24 * 60 * 60),
+        ("month", 30 * 24 * 60 * 60),
+        ("day", 24 * 60 * 60),
+        ("hour", 60 * 60),
+        ("minute", 60),
+        ("second", 1),
+    ];
+
+    let mut seconds = duration.as_secs();
+    let mut parts = vec![];
+
+    for (unit, unit_seconds) in UNITS {
+        let amount = seconds / unit_seconds;
+        seconds %= unit_seconds;
+
+        if amount > 0 {
+            let plural = if amount == 1 { "" } else { "s" };
+            parts.push(format!("{amount} {unit}{plural}"));
+        } else if !parts.is_empty() {
+            break;
+        }
+
+        if parts.len() == 2 {
+            break;
+        }
+    }
+
+    if parts.is_empty() {
+        return "0 seconds".to_string();
+    }
+
+    parts.join(" ")
+}
+
+fn get_random_seed() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|time| time.subsec_nanos().into())
+        .unwrap_or_default()
+}
+
+fn get_time_since(timestamp: &str) -> Option<Duration> {
+    let started = OffsetDateTime::parse(timestamp, &Rfc3339).ok()?;
+
+    (OffsetDateTime::now_utc() - started).try_into().ok()
+}
+
+#[derive(Deserialize)]
+struct Stream {
+    started_at: String,
+}
+
+#[derive(Deserialize)]
+struct Follower {
+    followed_at: String,
+}
+
+/// Looks the variables up with the Helix API with the broadcaster's token, {count} goes up every use
+pub struct HelixLookup {
+    credentials: Option<Credentials>,
+    count: Option<u64>,
+}
+
+impl HelixLookup {
+    /// Without credentials, like in read-only chat, channels look offline and nobody follows
+    pub fn new(credentials: Option<Credentials>) -> Self {
+        HelixLookup {
+            credentials,
+            count: None,
+        }
+    }
+
+    /// Renders {count} as a count that's already known, like a counter's, instead of going up
+    pub fn with_count(mut self, count: u64) -> Self {
+        self.count = Some(count);
+
+        self
+    }
+
+    fn get<T: for<'de> Deserialize<'de>>(&self, url: &str, query: &[(&str, &str)]) -> Result<Vec<T>, Box<dyn Error>> {
+        let Some(credentials) = &self.credentials else {
+            return Err("No credentials to call the Twitch API with".into());
+        };
+
+        let response = ureq::get(url)
+            .set(
+                "Authorization",
+                &format!("Bearer {}", credentials.oauth_token.replace("oauth:", "")),
+            )
+            .set("Client-Id", &credentials.client_id)
+            .query_pairs(query.to_vec())
+            .call()?;
+
+        let response: TwitchApiResponse<Vec<T>> = serde_json::from_reader(response.into_reader())?;
+
+        Ok(response.data)
+    }
+
+    fn get_followed_at(&self, channel: &str, user_id: &str) -> Result<Option<String>, Box<dyn Error>> {
+        #[derive(Deserialize)]
+        struct ChannelUser {
+            id: String,
+        }
+
+        let Some(broadcaster) = self.get::<ChannelUser>(USERS_URL, &[("login", channel)])?.pop() else {
+            return Ok(None);
+        };
+
+        let followers = self.get::<Follower>(
+            FOLLOWERS_URL,
+            &[("broadcaster_id", &broadcaster.id), ("user_id", user_id)],
+        )?;
+
+        Ok(followers.into_iter().next().map(|follower| follower.followed_at))
+    }
+}
+
+impl TemplateLookup for HelixLookup {
+    fn uptime(&mut self, channel: &str) -> Option<Duration> {
+        match self.get::<Stream>(STREAMS_URL, &[("user_login", channel)]) {
+            Ok(streams) => streams.first().and_then(|stream| get_time_since(&stream.started_at)),
+            Err(error) => {
+                send_to_error_log(error.to_string(), format!("Could not get the uptime of {channel}"));
+
+                None
+            }
+        }
+    }
+
+    fn followage(&mut self, channel: &str, user_id: &str) -> Option<Duration> {
+        match self.get_followed_at(channel, user_id) {
+            Ok(followed_at) => followed_at.and_then(|followed_at| get_time_since(&followed_at)),
+            Err(error) => {
+                send_to_error_log(error.to_string(), format!("Could not get the followage of {user_id}"));
+
+                None
+            }
+        }
+    }
+
+    fn count(&mut self, command: &str) -> u64 {
+        if let Some(count) = self.count {
+            return count;
+        }
+
+        increment_command_count(command).unwrap_or_else(|error| {
+            send_to_error_log(error.to_string(), format!("Could not update the count of {command}"));
+
+            0
+        })
+    }
+
+    fn random(&mut self, choices: usize) -> usize {
+        get_random_seed() as usize % choices
+    }
+}
+
+/// Made up values for previewing a template in the admin, nothing is saved
+pub struct PreviewLookup {
+    counter: bool,
+}
+
+impl TemplateLookup for PreviewLookup {
+    fn uptime(&mut self, _channel: &str) -> Option<Duration> {
+        Some(Duration::from_secs(2 * 60 * 60 + 14 * 60))
+    }
+
+    fn followage(&mut self, _channel: &str, _user_id: &str) -> Option<Duration> {
+        Some(Duration::from_secs(400 * 24 * 60 * 60))
+    }
+
+    fn count(&mut self, command: &str) -> u64 {
+        // Using a counter shows its count, other commands count the use
+        if self.counter {
+            return get_command_count(command);
+        }
+
+        get_command_count(command) + 1
+    }
+
+    fn random(&mut self, _choices: usize) -> usize {
+        0
+    }
+}
+
+/// How a template renders for a made up chatter, shown while editing a command
+pub fn preview_template(command: &str, template: &str, channel: &str) -> String {
+    let context = TemplateContext {
+        command,
+        user: "viewer",
+        user_id: "",
+        channel,
+        args: vec!["arg1", "arg2"],
+    };
+
+    let mut lookup = PreviewLookup {
+        counter: get_command_options(command).counter,
+    };
+
+    render_template(template, &context, &mut lookup)
+}
+
+#[cfg(test)]
+struct TestLookup {
+    count: u64,
+}
+
+#[cfg(test)]
+impl TemplateLookup for TestLookup {
+    fn uptime(&mut self, channel: &str) -> Option<Duration> {
+        (channel == "s9tpepper_").then(|| Duration::from_secs(3 * 60 * 60 + 5 * 60 + 9))
+    }
+
+    fn followage(&mut self, _channel: &str, user_id: &str) -> Option<Duration> {
+        (user_id == "1337").then(|| Duration::from_secs(45 * 24 * 60 * 60))
+    }
+
+    fn count(&mut self, _command: &str) -> u64 {
+        self.count += 1;
+
+        self.count
+    }
+
+    fn random(&mut self, choices: usize) -> usize {
+        choices - 1
+    }
+}
+
+#[test]
+fn test_render_template() {
+    let context = TemplateContext {
+        command: "hug",
+        user: "Dallas",
+        user_id: "1337",
+        channel: "s9tpepper_",
+        args: vec!["@ronni", "tightly"],
+    };
+    let mut lookup = TestLookup { count: 41 };
+
+    assert_eq!(
+        render_template("{user} hugs {arg1} {arg2}, hug #{count}", &context, &mut lookup),
+        "Dallas hugs @ronni tightly, hug #42"
+    );
+    assert_eq!(
+        render_template("#{count}, yes #{count}", &context, &mut lookup),
+        "#43, yes #43"
+    );
+    assert_eq!(
+        render_template("{args}|{arg3}|{arg0}", &context, &mut lookup),
+        "@ronni tightly||"
+    );
+    assert_eq!(
+        render_template("{channel} has been live for {uptime}", &context, &mut lookup),
+        "s9tpepper_ has been live for 3 hours 5 minutes"
+    );
+    assert_eq!(
+        render_template("Following for {followage}", &context, &mut lookup),
+        "Following for 1 month 15 days"
+    );
+    assert_eq!(render_template("{random:heads|tails}", &context, &mut lookup), "tails");
+
+    // Unknown variables and stray braces are left alone
+    assert_eq!(
+        render_template("{unknown} {user} {not closed", &context, &mut lookup),
+        "{unknown} Dallas {not closed"
+    );
+}
+
+#[test]
+fn test_format_duration() {
+    assert_eq!(format_duration(Duration::from_secs(0)), "0 seconds");
+    assert_eq!(format_duration(Duration::from_secs(61)), "1 minute 1 second");
+    assert_eq!(format_duration(Duration::from_secs(2 * 60 * 60)), "2 hours");
+    assert_eq!(
+        format_duration(Duration::from_secs(400 * 24 * 60 * 60)),
+        "1 year 1 month"
+    );
+}
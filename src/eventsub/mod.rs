@@ -15,11 +15,12 @@ use crate::twitch::ChannelMessages;
 use self::{
     notifications::{
         channel_ad_break_begin_notification, channel_chat_notification, chat_clear_user_messages_notification,
+        user_whisper_message_notification,
     },
     structs::{Subscription, SubscriptionEvent},
     subscriptions::{
-        channel_ad_break_begin, channel_chat_clear_user_messages, CHANNEL_AD_BREAK_BEGIN, CHANNEL_CHAT_NOTIFICATION,
-        CHAT_CLEAR_USER_MESSAGES,
+        channel_ad_break_begin, channel_chat_clear_user_messages, user_whisper_message, CHANNEL_AD_BREAK_BEGIN,
+        CHANNEL_CHAT_NOTIFICATION, CHAT_CLEAR_USER_MESSAGES, USER_WHISPER_MESSAGE,
     },
 };
 
@@ -115,6 +116,10 @@ fn listen(
                                         }
                                     }
 
+                                    USER_WHISPER_MESSAGE => {
+                                        user_whisper_message_notification(msg.payload.event, tx.clone());
+                                    }
+
                                     &_ => {}
                                 }
                             }
@@ -141,4 +146,5 @@ fn listen(
 fn create_subscriptions(message: Message, oauth_token: Arc<String>, client_id: Arc<String>) {
     channel_ad_break_begin(&message, oauth_token.clone(), client_id.clone());
     channel_chat_clear_user_messages(&message, oauth_token.clone(), client_id.clone());
+    user_whisper_message(&message, oauth_token.clone(), client_id.clone());
 }
@@ -2,7 +2,7 @@ use std::sync::mpsc::Sender;
 
 use crate::twitch::{
     self,
-    parse::{ClearMessageByUser, RedeemMessage, TwitchMessage},
+    parse::{ClearMessageByUser, RedeemMessage, TwitchMessage, WhisperMessage},
     ChannelMessages,
 };
 
@@ -50,3 +50,77 @@ pub fn channel_chat_notification(
         let _ = socket_tx.send(ChannelMessages::Notifications(Box::new(event)));
     }
 }
+
+pub fn user_whisper_message_notification(event: Option<SubscriptionEvent>, tx: Sender<ChannelMessages>) {
+    let Some(event) = event else {
+        return;
+    };
+
+    let Some(whisper) = event.whisper else {
+        return;
+    };
+
+    let whisper_message = WhisperMessage {
+        from_login: event.from_user_login.unwrap_or_default(),
+        to_login: event.to_user_login.unwrap_or_default(),
+        display_name: event.from_user_name.unwrap_or_default(),
+        user_id: event.from_user_id.unwrap_or_default(),
+        message_id: event.whisper_id.unwrap_or_default(),
+        color: "#FF9912".to_string(),
+        message: whisper.text,
+        ..Default::default()
+    };
+
+    let twitch_message = TwitchMessage::WhisperMessage {
+        message: whisper_message,
+    };
+    let _ = tx.send(twitch::ChannelMessages::TwitchMessage(twitch_message));
+}
+
+#[test]
+fn test_user_whisper_message_notification() {
+    let notification = r#"{
+        "metadata": {
+            "message_id": "befa7b53-d79d-478f-86b9-120f112b044e",
+            "message_type": "notification",
+            "message_timestamp": "2024-04-12T19:22:36.938Z"
+        },
+        "payload": {
+            "subscription": {
+                "id": "7297f7eb-3bf5-461f-8ae6-7cd7781ebce3",
+                "status": "enabled",
+                "type": "user.whisper.message",
+                "version": "1",
+                "condition": { "user_id": "961536166" },
+                "transport": { "method": "websocket", "session_id": "AgoQHR3s6Mb4T8GFB1l3DlPfiRIGY2VsbC1h" },
+                "created_at": "2024-04-12T19:20:12.381Z"
+            },
+            "event": {
+                "from_user_id": "423374343",
+                "from_user_login": "dallas",
+                "from_user_name": "Dallas",
+                "to_user_id": "961536166",
+                "to_user_login": "s9tpepper_",
+                "to_user_name": "s9tpepper_",
+                "whisper_id": "some-whisper-id",
+                "whisper": { "text": "how many points do I have?" }
+            }
+        }
+    }"#;
+
+    let message = serde_json::from_str::<super::Message>(notification).unwrap();
+    let (tx, rx) = std::sync::mpsc::channel();
+    user_whisper_message_notification(message.payload.event, tx);
+
+    match rx.try_recv() {
+        Ok(ChannelMessages::TwitchMessage(TwitchMessage::WhisperMessage { message })) => {
+            assert_eq!(message.from_login, "dallas");
+            assert_eq!(message.display_name, "Dallas");
+            assert_eq!(message.user_id, "423374343");
+            assert_eq!(message.to_login, "s9tpepper_");
+            assert_eq!(message.message, "how many points do I have?");
+            assert!(!message.outgoing);
+        }
+        other => panic!("Expected a WhisperMessage, got {other:?}"),
+    }
+}
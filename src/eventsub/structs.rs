@@ -155,13 +155,22 @@ pub struct Amount {
     pub currency: String,
 }
 
+#[derive(Clone, Debug, Deserialize, Serialize)]
+pub struct Whisper {
+    pub text: String,
+}
+
+// Whisper events aren't sent for a channel, so the broadcaster fields have defaults
 #[derive(Clone, Debug, Deserialize, Serialize)]
 pub struct SubscriptionEvent {
     pub announcement: Option<Announcement>,
     pub badges: Option<Vec<BadgeInfo>>,
     pub bits_badge_tier: Option<BitsBadgeTier>,
+    #[serde(default)]
     pub broadcaster_user_id: String,
+    #[serde(default)]
     pub broadcaster_user_login: String,
+    #[serde(default)]
     pub broadcaster_user_name: String,
     pub charity_donation: Option<CharityDonation>,
     pub chatter_user_id: Option<String>,
@@ -171,7 +180,11 @@ pub struct SubscriptionEvent {
     pub color: Option<String>,
     pub community_sub_gift: Option<CommunitySubGift>,
     pub duration_seconds: Option<u64>,
+    pub from_user_id: Option<String>,
+    pub from_user_login: Option<String>,
+    pub from_user_name: Option<String>,
     pub gift_paid_upgrade: Option<GiftPaidUpgrade>,
+    #[serde(default)]
     pub is_automatic: bool,
     pub message: Option<SubscriptionEventMessage>,
     pub message_id: Option<String>,
@@ -183,6 +196,7 @@ pub struct SubscriptionEvent {
     pub requester_user_login: Option<String>,
     pub requester_user_name: Option<String>,
     pub resub: Option<Resub>,
+    #[serde(default)]
     pub started_at: String,
     pub sub: Option<Sub>,
     pub sub_gift: Option<SubGift>,
@@ -190,5 +204,8 @@ pub struct SubscriptionEvent {
     pub target_user_id: Option<String>,
     pub target_user_login: Option<String>,
     pub target_user_name: Option<String>,
+    pub to_user_login: Option<String>,
     pub unraid: Option<Unraid>,
+    pub whisper: Option<Whisper>,
+    pub whisper_id: Option<String>,
 }
@@ -12,6 +12,7 @@ const SUBSCRIPTIONS: &str = "https://api.twitch.tv/helix/eventsub/subscriptions"
 pub const CHAT_CLEAR_USER_MESSAGES: &str = "channel.chat.clear_user_messages";
 pub const CHANNEL_AD_BREAK_BEGIN: &str = "channel.ad_break.begin";
 pub const CHANNEL_CHAT_NOTIFICATION: &str = "channel.chat.notification";
+pub const USER_WHISPER_MESSAGE: &str = "user.whisper.message";
 
 fn request_subscription(
     r#type: String,
@@ -101,3 +102,21 @@ pub fn channel_chat_notification(message: &Message, oauth_token: Arc<String>, cl
         );
     }
 }
+
+pub fn user_whisper_message(message: &Message, oauth_token: Arc<String>, client_id: Arc<String>) {
+    if let Ok(user) = get_user(&oauth_token, &client_id) {
+        let condition = Condition {
+            broadcaster_user_id: None,
+            moderator_user_id: None,
+            user_id: Some(user.id),
+        };
+
+        request_subscription(
+            String::from_str(USER_WHISPER_MESSAGE).unwrap(),
+            condition,
+            message,
+            oauth_token,
+            client_id,
+        );
+    }
+}
@@ -2,6 +2,7 @@ pub mod admin;
 pub mod commands;
 pub mod eventsub;
 pub mod scrollview;
+pub mod store;
 pub mod tui;
 pub mod twitch;
 pub mod utils;
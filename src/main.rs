@@ -1,30 +1,122 @@
-use clap::{Parser, Subcommand};
+use clap::{Args, Parser, Subcommand};
 use dotenv::dotenv;
 use sergeant::admin::admin;
 use sergeant::eventsub::start_eventsub;
+use sergeant::tui::image_protocol::set_image_protocol;
 use sergeant::tui::{install_hooks, restore, App};
-use sergeant::twitch::api::{refresh_token, validate};
-use sergeant::twitch::irc::TwitchIrcClient;
+use sergeant::twitch::api::{refresh_bot_token, refresh_token, validate};
+use sergeant::twitch::asset_cache::{clear_cache, print_cache_stats};
+use sergeant::twitch::bot::BotCredentials;
+use sergeant::twitch::irc::{get_channels, set_irc_url, TwitchIrcClient};
+use sergeant::twitch::transport::get_transport_url;
 use sergeant::twitch::{
-    announcements::start_announcements, irc::TwitchIRC, parse::get_badges, pubsub::connect_to_pub_sub, ChannelMessages,
+    announcements::start_announcements,
+    irc::TwitchIRC,
+    parse::get_badges,
+    pubsub::{connect_to_pub_sub, send_to_error_log},
+    ratelimit::SendQueue,
+    ChannelMessages,
 };
 
+use sergeant::commands::cooldown::Cooldown;
+use sergeant::commands::counter::watch_counters;
+use sergeant::commands::matcher::{set_command_matcher, Arguments, CommandMatcher, DEFAULT_PREFIX};
+use sergeant::commands::permissions::{get_login, Permissions, Role, ROLES};
 use sergeant::commands::{
-    add_action, add_chat_command, add_reward, authenticate_with_twitch, get_list_announcements, get_list_commands,
-    list_actions, list_rewards, remove_action, remove_chat_command, remove_reward, TokenStatus,
+    add_action, add_action_alias, add_bits_action, add_chat_command, add_command_alias, add_reward,
+    authenticate_with_twitch, get_command_count, get_command_options, get_list_announcements, get_list_commands,
+    list_action_aliases, list_actions, list_bits_actions, list_command_aliases, list_emote_providers, list_rewards,
+    remove_action, remove_action_alias, remove_bits_action, remove_chat_command, remove_command_alias,
+    remove_emote_providers, remove_reward, set_command_count, set_command_options, set_emote_providers, ActionOptions,
+    CommandOptions, TokenStatus,
 };
 
-use sergeant::utils::read_auth_token;
+use sergeant::utils::{read_auth_token, read_bot_token};
 use sergeant::websocket::start_websocket;
 use std::{
     error::Error,
     process::exit,
-    sync::{mpsc::channel, Arc},
+    sync::{mpsc::channel, Arc, Mutex},
     thread,
 };
 
 type AsyncResult<T> = Result<T, Box<dyn Error>>;
 
+#[derive(Args)]
+struct CooldownArgs {
+    /// Seconds before anyone can use it again
+    #[arg(long, default_value_t = 0)]
+    cooldown: u64,
+
+    /// Seconds before the same chatter can use it again
+    #[arg(long, default_value_t = 0)]
+    user_cooldown: u64,
+
+    /// Sent to chatters that use it while it's cooling down, {remaining} is the time left
+    #[arg(long, default_value = "")]
+    cooldown_reply: String,
+}
+
+impl From<CooldownArgs> for Cooldown {
+    fn from(args: CooldownArgs) -> Self {
+        Cooldown {
+            global: args.cooldown,
+            user: args.user_cooldown,
+            reply: args.cooldown_reply,
+        }
+    }
+}
+
+#[derive(Args)]
+struct PermissionArgs {
+    /// The lowest role that can use it, each role also lets in the roles after it
+    #[arg(long, default_value = "everyone", value_parser = ROLES)]
+    role: String,
+
+    /// Comma separated logins that can always use it, whatever their role
+    #[arg(long, value_delimiter = ',')]
+    allow: Vec<String>,
+
+    /// Comma separated logins that can never use it
+    #[arg(long, value_delimiter = ',')]
+    deny: Vec<String>,
+}
+
+#[derive(Args)]
+struct ArgumentArgs {
+    /// The fewest arguments it takes
+    #[arg(long, default_value_t = 0)]
+    min_args: usize,
+
+    /// The most arguments it takes, any number when not set
+    #[arg(long)]
+    max_args: Option<usize>,
+
+    /// Sent as "Usage: !name <usage>" to chatters that use the wrong number of arguments
+    #[arg(long, default_value = "")]
+    usage: String,
+}
+
+impl From<ArgumentArgs> for Arguments {
+    fn from(args: ArgumentArgs) -> Self {
+        Arguments {
+            min: args.min_args,
+            max: args.max_args,
+            usage: args.usage,
+        }
+    }
+}
+
+impl From<PermissionArgs> for Permissions {
+    fn from(args: PermissionArgs) -> Self {
+        Permissions {
+            role: args.role.parse().unwrap_or(Role::Everyone),
+            allow: args.allow.iter().map(|login| get_login(login)).collect(),
+            deny: args.deny.iter().map(|login| get_login(login)).collect(),
+        }
+    }
+}
+
 #[derive(Subcommand)]
 enum SubCmds {
     /// List commands
@@ -40,6 +132,64 @@ enum SubCmds {
 
         /// The timing for the message in minutes
         timing: Option<usize>,
+
+        /// Answer the chatter with a whisper instead of in chat
+        #[arg(long, default_value_t = false, conflicts_with = "timing")]
+        whisper: bool,
+
+        /// Answer as a threaded reply to the message that used it
+        #[arg(long, default_value_t = false, conflicts_with = "timing")]
+        reply: bool,
+
+        /// Make it a counter, moderators change {count} with !name+ !name- and !name=5
+        #[arg(long, default_value_t = false, conflicts_with = "timing")]
+        counter: bool,
+
+        #[command(flatten)]
+        cooldown: CooldownArgs,
+
+        #[command(flatten)]
+        permissions: PermissionArgs,
+
+        #[command(flatten)]
+        arguments: ArgumentArgs,
+    },
+
+    /// Set the cooldowns of a chat command, use the name commands for !commands
+    Cooldown {
+        /// The name of the command
+        name: String,
+
+        #[command(flatten)]
+        cooldown: CooldownArgs,
+    },
+
+    /// Set who can use a chat command, use the name commands for !commands
+    Permissions {
+        /// The name of the command
+        name: String,
+
+        #[command(flatten)]
+        permissions: PermissionArgs,
+    },
+
+    /// Set whether a chat command answers as a threaded reply, use the name commands for !commands
+    Reply {
+        /// The name of the command
+        name: String,
+
+        /// true to answer as a threaded reply, false for a regular chat message
+        #[arg(action = clap::ArgAction::Set)]
+        reply: bool,
+    },
+
+    /// Show the {count} of a command, or set it
+    Count {
+        /// The name of the command
+        name: String,
+
+        /// The count to set it to
+        count: Option<u64>,
     },
 
     /// Remove a command
@@ -47,6 +197,33 @@ enum SubCmds {
         /// The name of the command to remove
         name: String,
     },
+
+    /// Add, remove, and list other names a command can be used with
+    Alias {
+        #[command(subcommand)]
+        cmd: AliasSubCmds,
+    },
+}
+
+#[derive(Subcommand)]
+enum AliasSubCmds {
+    /// List aliases
+    List,
+
+    /// Add an alias
+    Add {
+        /// The other name, without the command prefix
+        alias: String,
+
+        /// The name it was added as
+        name: String,
+    },
+
+    /// Remove an alias
+    Remove {
+        /// The alias to remove
+        alias: String,
+    },
 }
 
 #[derive(Subcommand)]
@@ -61,6 +238,15 @@ enum IrcActionSubCmds {
 
         /// The cli command to execute for as the IRC action
         cli: String,
+
+        #[command(flatten)]
+        cooldown: CooldownArgs,
+
+        #[command(flatten)]
+        permissions: PermissionArgs,
+
+        #[command(flatten)]
+        arguments: ArgumentArgs,
     },
 
     /// Remove an IRC action
@@ -68,6 +254,64 @@ enum IrcActionSubCmds {
         /// The name of the IRC message type to remove
         name: String,
     },
+
+    /// Add, remove, and list other names an IRC action can be used with
+    Alias {
+        #[command(subcommand)]
+        cmd: AliasSubCmds,
+    },
+}
+
+#[derive(Subcommand)]
+enum BitsActionSubCmds {
+    /// List bits actions
+    List,
+
+    /// Add a bits action command
+    Add {
+        /// The minimum amount of bits a cheer needs to trigger the action
+        min_bits: u64,
+
+        /// The cli command to execute for the bits action
+        cli: String,
+    },
+
+    /// Remove a bits action
+    Remove {
+        /// The minimum amount of bits of the bits action to remove
+        min_bits: u64,
+    },
+}
+
+#[derive(Subcommand)]
+enum EmoteProviderSubCmds {
+    /// List the emote providers configured per channel
+    List,
+
+    /// Set the emote providers for a channel
+    Set {
+        /// The channel to configure
+        channel: String,
+
+        /// The emote providers to use in the channel, in order of priority
+        #[arg(required = true, value_parser = ["7tv", "bttv", "ffz"])]
+        providers: Vec<String>,
+    },
+
+    /// Remove the emote providers for a channel, the channel goes back to using all of them
+    Remove {
+        /// The channel to remove the configuration for
+        channel: String,
+    },
+}
+
+#[derive(Subcommand)]
+enum CacheSubCmds {
+    /// Show how many emotes and badges are cached and how much space they use
+    Stats,
+
+    /// Remove every cached emote and badge
+    Clear,
 }
 
 #[derive(Subcommand)]
@@ -91,6 +335,8 @@ enum RewardSubCmds {
     },
 }
 
+// Parsed once at startup, the size of the chat options doesn't matter
+#[allow(clippy::large_enum_variant)]
 #[derive(Subcommand)]
 enum Cmds {
     /// Open the admin dashboard
@@ -113,6 +359,46 @@ enum Cmds {
         /// Set to turn off announcements
         #[arg(long, short = 's', env = "SKIP_ANNOUNCEMENTS", default_value_t = false)]
         skip_announcements: bool,
+
+        /// A channel to join, repeat to join several channels. Defaults to your own channel
+        #[arg(long = "channel")]
+        channels: Vec<String>,
+
+        /// How to draw emotes, detected from the terminal when not set
+        #[arg(long, env = "IMAGE_PROTOCOL", value_parser = ["iterm", "kitty", "sixel", "text"])]
+        image_protocol: Option<String>,
+
+        /// How to connect to Twitch chat, IRC over TLS works on networks that block WebSockets
+        #[arg(long, env = "TWITCH_IRC_TRANSPORT", value_parser = ["websocket", "irc"])]
+        transport: Option<String>,
+
+        /// The IRC server to connect to, ws://, wss://, irc:// or ircs://. Defaults to Twitch's
+        #[arg(long, env = "TWITCH_IRC_URL")]
+        irc_url: Option<String>,
+
+        /// Watch a channel's chat anonymously, no login needed. Commands, actions and announcements are off
+        #[arg(long, value_name = "CHANNEL")]
+        read_only: Option<String>,
+
+        /// The Twitch username of the bot account that sends replies, defaults to login --bot
+        #[arg(long, env = "BOT_NAME")]
+        bot_name: Option<String>,
+
+        /// The bot account's OAuth Token
+        #[arg(long, env = "BOT_OAUTH_TOKEN")]
+        bot_token: Option<String>,
+
+        /// The bot account's app client ID
+        #[arg(long, env = "BOT_CLIENT_ID")]
+        bot_client_id: Option<String>,
+
+        /// What chat commands and irc-actions start with
+        #[arg(long, env = "COMMAND_PREFIX", default_value = DEFAULT_PREFIX)]
+        command_prefix: String,
+
+        /// Match chat commands and irc-actions whatever their case, so !Lurk runs !lurk
+        #[arg(long, env = "IGNORE_COMMAND_CASE", default_value_t = false)]
+        ignore_command_case: bool,
     },
 
     /// Manage chat commands
@@ -133,6 +419,24 @@ enum Cmds {
         cmd: IrcActionSubCmds,
     },
 
+    /// Manage the 7TV, BTTV and FFZ emote providers used per channel
+    EmoteProviders {
+        #[command(subcommand)]
+        cmd: EmoteProviderSubCmds,
+    },
+
+    /// Manage the emote and badge image cache
+    Cache {
+        #[command(subcommand)]
+        cmd: CacheSubCmds,
+    },
+
+    /// Manage actions triggered by cheering bits
+    BitsActions {
+        #[command(subcommand)]
+        cmd: BitsActionSubCmds,
+    },
+
     // Send a chat message
     // SendMessage {
     //     /// The message body
@@ -140,7 +444,11 @@ enum Cmds {
     //     message: String,
     // },
     /// Login to Twitch and get a token
-    Login,
+    Login {
+        /// Login the bot account that sends chat replies instead of the broadcaster
+        #[arg(long, default_value_t = false)]
+        bot: bool,
+    },
 }
 
 #[derive(Parser)]
@@ -162,7 +470,38 @@ fn main() {
             oauth_token,
             client_id,
             skip_announcements,
+            channels,
+            image_protocol,
+            transport,
+            irc_url,
+            read_only,
+            bot_name,
+            bot_token,
+            bot_client_id,
+            command_prefix,
+            ignore_command_case,
         } => {
+            set_command_matcher(CommandMatcher {
+                prefix: command_prefix,
+                ignore_case: ignore_command_case,
+            });
+
+            if let Some(image_protocol) = image_protocol.and_then(|protocol| protocol.parse().ok()) {
+                set_image_protocol(image_protocol);
+            }
+
+            let transport_url = transport.as_deref().and_then(get_transport_url);
+            if let Some(irc_url) = irc_url.as_deref().or(transport_url) {
+                set_irc_url(irc_url);
+            }
+
+            if let Some(read_only) = read_only {
+                let channels: Vec<String> = [read_only].into_iter().chain(channels).collect();
+                let _ = start_read_only_chat(get_channels("", &channels));
+
+                return;
+            }
+
             let (name, token, id, refresh) = get_credentials(twitch_name, oauth_token, client_id, None).unwrap();
 
             let name = Arc::new(name);
@@ -170,31 +509,167 @@ fn main() {
             let token = Arc::new(token);
             let refresh = Arc::new(refresh);
 
-            let _ = start_chat(name, token, id, refresh, skip_announcements);
+            let channels = Arc::new(get_channels(&name, &channels));
+
+            let bot_credentials = get_bot_credentials(bot_name, bot_token, bot_client_id);
+
+            let _ = start_chat(name, token, id, refresh, channels, bot_credentials, skip_announcements);
         }
 
         Cmds::Commands { cmd } => match cmd {
             SubCmds::List => {
                 list_commands();
             }
-            SubCmds::Add { name, message, timing } => {
-                add_command(&name, &message, timing);
+            SubCmds::Add {
+                name,
+                message,
+                timing,
+                whisper,
+                reply,
+                counter,
+                cooldown,
+                permissions,
+                arguments,
+            } => {
+                let options = CommandOptions {
+                    whisper,
+                    reply,
+                    counter,
+                    cooldown: cooldown.into(),
+                    permissions: permissions.into(),
+                    arguments: arguments.into(),
+                };
+
+                add_command(&name, &message, timing, &options);
+            }
+            SubCmds::Cooldown { name, cooldown } => {
+                let options = CommandOptions {
+                    cooldown: cooldown.into(),
+                    ..get_command_options(&name)
+                };
+
+                set_options(&name, &options);
+            }
+            SubCmds::Permissions { name, permissions } => {
+                let options = CommandOptions {
+                    permissions: permissions.into(),
+                    ..get_command_options(&name)
+                };
+
+                set_options(&name, &options);
+            }
+            SubCmds::Reply { name, reply } => {
+                let options = CommandOptions {
+                    reply,
+                    ..get_command_options(&name)
+                };
+
+                set_options(&name, &options);
+            }
+            SubCmds::Count { name, count: None } => {
+                println!("{}", get_command_count(&name));
+            }
+            SubCmds::Count {
+                name,
+                count: Some(count),
+            } => {
+                if set_command_count(&name, count).is_err() {
+                    exit(1)
+                }
             }
             SubCmds::Remove { name } => {
                 remove_command(&name);
             }
+            SubCmds::Alias { cmd } => match cmd {
+                AliasSubCmds::List => {
+                    list_command_aliases();
+                }
+                AliasSubCmds::Add { alias, name } => {
+                    if let Err(error) = add_command_alias(&alias, &name) {
+                        eprintln!("Could not add the alias: {error}");
+                        exit(1)
+                    }
+                }
+                AliasSubCmds::Remove { alias } => {
+                    let _ = remove_command_alias(&alias);
+                }
+            },
         },
 
         Cmds::IrcActions { cmd } => match cmd {
             IrcActionSubCmds::List => {
                 list_actions();
             }
-            IrcActionSubCmds::Add { name, cli } => {
-                let _ = add_action(&name, &cli);
+            IrcActionSubCmds::Add {
+                name,
+                cli,
+                cooldown,
+                permissions,
+                arguments,
+            } => {
+                let options = ActionOptions {
+                    cooldown: cooldown.into(),
+                    permissions: permissions.into(),
+                    arguments: arguments.into(),
+                };
+
+                if let Err(error) = add_action(&name, &cli, &options) {
+                    eprintln!("Could not add the irc action: {error}");
+                    exit(1)
+                }
             }
             IrcActionSubCmds::Remove { name } => {
                 let _ = remove_action(&name);
             }
+            IrcActionSubCmds::Alias { cmd } => match cmd {
+                AliasSubCmds::List => {
+                    list_action_aliases();
+                }
+                AliasSubCmds::Add { alias, name } => {
+                    if let Err(error) = add_action_alias(&alias, &name) {
+                        eprintln!("Could not add the alias: {error}");
+                        exit(1)
+                    }
+                }
+                AliasSubCmds::Remove { alias } => {
+                    let _ = remove_action_alias(&alias);
+                }
+            },
+        },
+
+        Cmds::BitsActions { cmd } => match cmd {
+            BitsActionSubCmds::List => {
+                list_bits_actions();
+            }
+            BitsActionSubCmds::Add { min_bits, cli } => {
+                let _ = add_bits_action(min_bits, &cli);
+            }
+            BitsActionSubCmds::Remove { min_bits } => {
+                let _ = remove_bits_action(min_bits);
+            }
+        },
+
+        Cmds::EmoteProviders { cmd } => match cmd {
+            EmoteProviderSubCmds::List => {
+                list_emote_providers();
+            }
+            EmoteProviderSubCmds::Set { channel, providers } => {
+                let _ = set_emote_providers(&channel, &providers);
+            }
+            EmoteProviderSubCmds::Remove { channel } => {
+                let _ = remove_emote_providers(&channel);
+            }
+        },
+
+        Cmds::Cache { cmd } => match cmd {
+            CacheSubCmds::Stats => {
+                print_cache_stats();
+            }
+            CacheSubCmds::Clear => {
+                if let Err(error) = clear_cache() {
+                    eprintln!("Could not clear the cache: {error}");
+                }
+            }
         },
 
         Cmds::Rewards { cmd } => match cmd {
@@ -212,8 +687,8 @@ fn main() {
         // Cmds::SendMessage { message } => {
         //     send_message(&message);
         // }
-        Cmds::Login => {
-            start_login_flow();
+        Cmds::Login { bot } => {
+            start_login_flow(bot);
         }
     };
 }
@@ -248,16 +723,75 @@ fn get_credentials(
     }
 }
 
+// The bot account is optional, without one the broadcaster sends every reply
+fn get_bot_credentials(
+    bot_name: Option<String>,
+    bot_token: Option<String>,
+    bot_client_id: Option<String>,
+) -> Option<BotCredentials> {
+    if let (Some(name), Some(oauth_token), Some(client_id)) = (bot_name, bot_token, bot_client_id) {
+        return Some(BotCredentials {
+            name,
+            oauth_token,
+            client_id,
+        });
+    }
+
+    let mut token_status = read_bot_token().ok().filter(|token_status| token_status.success)?;
+
+    let token = token_status.token.clone()?;
+    if validate(&token).is_err() {
+        // Chat still works without the bot account, messages are sent as the broadcaster
+        match refresh_bot_token(&token_status.refresh.clone()?) {
+            Ok(refreshed) => token_status = refreshed,
+            Err(error) => {
+                send_to_error_log(
+                    error.to_string(),
+                    "Bot token refresh failed, please login again with login --bot.".into(),
+                );
+
+                return None;
+            }
+        }
+    }
+
+    Some(BotCredentials {
+        name: token_status.username?,
+        oauth_token: format!("oauth:{}", token_status.token?),
+        client_id: token_status.client_id?,
+    })
+}
+
 // Starts the admin TUI for viewing and editing your commands/etc
 fn start_admin() {
     admin()
 }
 
+// Watches chat as an anonymous user, nothing that needs a token is started
+fn start_read_only_chat(channels: Vec<String>) -> AsyncResult<()> {
+    let (chat_tx, rx) = channel::<ChannelMessages>();
+    let (socket_tx, _) = channel::<ChannelMessages>();
+
+    let irc_channels = channels.clone();
+    thread::spawn(move || {
+        let mut twitch_irc = TwitchIRC::read_only(&irc_channels, chat_tx);
+        twitch_irc.listen();
+    });
+
+    install_hooks()?;
+    App::new_read_only(&channels).run(rx, socket_tx)?;
+    restore()?;
+
+    Ok(())
+}
+
 fn start_chat(
     twitch_name: Arc<String>,
     oauth_token: Arc<String>,
     client_id: Arc<String>,
     refresh: Arc<String>,
+    channels: Arc<Vec<String>>,
+    bot_credentials: Option<BotCredentials>,
     skip_announcements: bool,
 ) -> AsyncResult<()> {
     let validate_token_response = validate(&oauth_token);
@@ -300,18 +834,32 @@ fn start_chat(
         connect_to_pub_sub(token, id, pubsub_tx).unwrap();
     });
 
-    let token = oauth_token.clone();
+    // Announcements are queued with chat's messages, chat's connection sends them within its rate limit
+    let send_queue = Arc::new(Mutex::new(SendQueue::default()));
+
     let name = twitch_name.clone();
-    let id = client_id.clone();
+    let bot_name = bot_credentials
+        .as_ref()
+        .map(|bot_credentials| bot_credentials.name.clone());
+    let announcements_queue = send_queue.clone();
     thread::spawn(move || {
-        let _ = start_announcements(&name, &token, &id, announce_tx, skip_announcements);
+        let _ = start_announcements(&name, bot_name, announcements_queue, announce_tx, skip_announcements);
     });
 
+    // Counts changed with the CLI or the admin reach the overlay too
+    thread::spawn(watch_counters);
+
+    // Chat is read with the broadcaster's token, replies go out as the bot account when there is one
     let id = client_id.clone();
     let token = oauth_token.clone();
     let name = twitch_name.clone();
+    let irc_channels = channels.clone();
     thread::spawn(move || {
-        let mut twitch_irc = TwitchIRC::new(&name, &token, &id, chat_tx);
+        let mut twitch_irc = TwitchIRC::new(&name, &token, &id, &irc_channels, chat_tx);
+        if let Some(bot_credentials) = bot_credentials {
+            twitch_irc.connect_bot(&bot_credentials);
+        }
+        twitch_irc.share_send_queue(send_queue);
         twitch_irc.listen();
     });
 
@@ -328,7 +876,7 @@ fn start_chat(
     });
 
     install_hooks()?;
-    App::new(&twitch_name).run(rx, socket_tx.clone())?;
+    App::new(&twitch_name, &channels).run(rx, socket_tx.clone())?;
     restore()?;
 
     Ok(())
@@ -355,11 +903,22 @@ fn list_commands() {
     list_announcements();
 }
 
-fn add_command(command_name: &str, message: &str, timing: Option<usize>) {
+fn add_command(command_name: &str, message: &str, timing: Option<usize>, options: &CommandOptions) {
     let result = add_chat_command(command_name, message, timing);
     if result.is_err() {
         exit(1)
     }
+
+    // Announcements are always sent to chat, to everyone and without a cooldown
+    if timing.is_none() {
+        set_options(command_name, options);
+    }
+}
+
+fn set_options(command_name: &str, options: &CommandOptions) {
+    if set_command_options(command_name, options).is_err() {
+        exit(1)
+    }
 }
 
 fn remove_command(command_name: &str) {
@@ -393,8 +952,8 @@ fn list_announcements() {
 //     todo!();
 // }
 
-fn start_login_flow() {
-    let result = authenticate_with_twitch();
+fn start_login_flow(bot: bool) {
+    let result = authenticate_with_twitch(bot);
     if result.is_err() {
         exit(5);
     }
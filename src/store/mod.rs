@@ -0,0 +1,601 @@
+use std::{
+    collections::BTreeMap,
+    error::Error,
+    fs::{self, File},
+    path::Path,
+    process,
+};
+
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    commands::{ActionOptions, CommandOptions},
+    twitch::{emote_providers::DEFAULT_PROVIDERS, pubsub::send_to_error_log},
+    utils::get_data_directory,
+};
+
+/// Bumped whenever the layout of the store changes, stores from newer versions aren't read
+pub const STORE_VERSION: u32 = 1;
+
+const STORE_FILE: &str = "sergeant.toml";
+const LOCK_FILE: &str = "sergeant.toml.lock";
+
+/// The tables of the store that hold items by name, like the chat commands
+pub const ITEM_TYPES: [&str; 6] = [
+    "chat_commands",
+    "chat_announcements",
+    "chat_rewards",
+    "irc_actions",
+    "bits_actions",
+    "emote_providers",
+];
+
+/// Every command, announcement, reward and action with their options, kept in one TOML file
+#[derive(Clone, Debug, Default, PartialEq, Deserialize, Serialize)]
+#[serde(deny_unknown_fields)]
+pub struct Store {
+    pub version: u32,
+    /// The options of !commands, which lists the chat commands
+    #[serde(default, skip_serializing_if = "is_default")]
+    pub command_list: CommandOptions,
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub chat_commands: BTreeMap<String, ChatCommand>,
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub chat_announcements: BTreeMap<String, ChatAnnouncement>,
+    /// The CLI command run for each channel point reward, by the reward's title
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub chat_rewards: BTreeMap<String, String>,
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub irc_actions: BTreeMap<String, IrcAction>,
+    /// The CLI command run for cheers of at least as many bits as its name
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub bits_actions: BTreeMap<String, String>,
+    /// The emote providers of each channel that doesn't use all of them
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub emote_providers: BTreeMap<String, Vec<String>>,
+}
+
+#[derive(Clone, Debug, Default, PartialEq, Deserialize, Serialize)]
+#[serde(deny_unknown_fields)]
+pub struct ChatCommand {
+    pub message: String,
+    #[serde(default, skip_serializing_if = "is_default")]
+    pub options: CommandOptions,
+    /// How many times {count} has been used, or the counter's count
+    #[serde(default, skip_serializing_if = "is_default")]
+    pub count: u64,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub aliases: Vec<String>,
+}
+
+#[derive(Clone, Debug, Default, PartialEq, Deserialize, Serialize)]
+#[serde(deny_unknown_fields)]
+pub struct ChatAnnouncement {
+    /// Minutes between each time the message is sent
+    pub timing: u64,
+    pub message: String,
+}
+
+#[derive(Clone, Debug, Default, PartialEq, Deserialize, Serialize)]
+#[serde(deny_unknown_fields)]
+pub struct IrcAction {
+    /// The CLI command and where its output goes, like "cmd:sub_command chat"
+    pub command: String,
+    #[serde(default, skip_serializing_if = "is_default")]
+    pub options: ActionOptions,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub aliases: Vec<String>,
+}
+
+fn is_default<T: Default + PartialEq>(value: &T) -> bool {
+    *value == T::default()
+}
+
+impl Store {
+    /// Reads the store, the first time it's read it's made from the old data directories
+    pub fn load(data_dir: &Path) -> Result<Self, Box<dyn Error>> {
+        let path = data_dir.join(STORE_FILE);
+        if !path.exists() {
+            let store = Store::migrate(data_dir)?;
+            store.save(data_dir)?;
+
+            send_to_error_log(format!("Migrated the data directories to {path:?}"), String::from(""));
+
+            return Ok(store);
+        }
+
+        Store::parse(&fs::read_to_string(path)?)
+    }
+
+    pub fn parse(contents: &str) -> Result<Self, Box<dyn Error>> {
+        // Newer stores can have tables this version doesn't know, so the version is checked first
+        let table: toml::Table = toml::from_str(contents)?;
+        let version = table
+            .get("version")
+            .and_then(toml::Value::as_integer)
+            .unwrap_or_default();
+        if version > STORE_VERSION as i64 {
+            return Err(format!(
+                "The store is version {version}, this version of sergeant reads up to version {STORE_VERSION}"
+            )
+            .into());
+        }
+
+        let store: Store = table.try_into()?;
+
+        store.validate()?;
+
+        Ok(store)
+    }
+
+    /// Writes the store to a new file before replacing the old one, so it's never left half written
+    pub fn save(&self, data_dir: &Path) -> Result<(), Box<dyn Error>> {
+        self.validate()?;
+
+        // Each process writes its own new file, so two saves never write over each other's
+        let path = data_dir.join(STORE_FILE);
+        let new_path = path.with_extension(format!("toml.{}.new", process::id()));
+
+        fs::write(&new_path, toml::to_string(self)?)?;
+        fs::rename(new_path, path)?;
+
+        Ok(())
+    }
+
+    /// Makes a store out of the directories chat commands, announcements, rewards and irc-actions
+    /// used to be kept in, items that aren't valid or can't be read are left out
+    pub fn migrate(data_dir: &Path) -> Result<Self, Box<dyn Error>> {
+        let mut store = Store {
+            version: STORE_VERSION,
+            ..Default::default()
+        };
+
+        for (name, message) in read_directory(data_dir, "chat_commands")? {
+            store.migrate_item("chat_commands", &name, |store| {
+                let command = ChatCommand {
+                    message,
+                    ..Default::default()
+                };
+                store.chat_commands.insert(name.clone(), command);
+
+                Ok(())
+            });
+        }
+
+        for (name, contents) in read_directory(data_dir, "chat_announcements")? {
+            store.migrate_item("chat_announcements", &name, |store| {
+                let (timing, message) = contents
+                    .split_once('\n')
+                    .ok_or("the first line has to be the timing in minutes")?;
+
+                let announcement = ChatAnnouncement {
+                    timing: timing.trim().parse()?,
+                    message: message.to_string(),
+                };
+                store.chat_announcements.insert(name.clone(), announcement);
+
+                Ok(())
+            });
+        }
+
+        for (name, command) in read_directory(data_dir, "chat_rewards")? {
+            store.migrate_item("chat_rewards", &name, |store| {
+                store.chat_rewards.insert(name.clone(), command);
+
+                Ok(())
+            });
+        }
+
+        for (name, command) in read_directory(data_dir, "irc_actions")? {
+            store.migrate_item("irc_actions", &name, |store| {
+                let action = IrcAction {
+                    command,
+                    ..Default::default()
+                };
+                store.irc_actions.insert(name.clone(), action);
+
+                Ok(())
+            });
+        }
+
+        Ok(store)
+    }
+
+    /// Adds an item read from the data directories, it's left out when the store isn't valid with it
+    fn migrate_item(
+        &mut self,
+        item_type: &str,
+        name: &str,
+        migrate: impl FnOnce(&mut Store) -> Result<(), Box<dyn Error>>,
+    ) {
+        let mut store = self.clone();
+
+        match migrate(&mut store).and_then(|_| store.validate()) {
+            Ok(()) => *self = store,
+            Err(error) => send_to_error_log(error.to_string(), format!("Could not migrate {item_type}/{name}")),
+        }
+    }
+
+    /// The chat command typed as its name or one of its aliases, names are compared with is_match
+    pub fn find_chat_command(&self, is_match: impl Fn(&str) -> bool) -> Option<(&String, &ChatCommand)> {
+        find_item(&self.chat_commands, is_match, |command| &command.aliases)
+    }
+
+    /// The irc-action typed as its name or one of its aliases, names are compared with is_match
+    pub fn find_irc_action(&self, is_match: impl Fn(&str) -> bool) -> Option<(&String, &IrcAction)> {
+        find_item(&self.irc_actions, is_match, |action| &action.aliases)
+    }
+
+    /// The items of one type with what they hold as text, like an irc-action's CLI command
+    pub fn get_items(&self, item_type: &str) -> Result<BTreeMap<String, String>, Box<dyn Error>> {
+        let items = match item_type {
+            "chat_commands" => get_contents(&self.chat_commands, |command| command.message.clone()),
+            "chat_announcements" => get_contents(&self.chat_announcements, |announcement| {
+                format!("{}\n{}", announcement.timing, announcement.message)
+            }),
+            "chat_rewards" => self.chat_rewards.clone(),
+            "irc_actions" => get_contents(&self.irc_actions, |action| action.command.clone()),
+            "bits_actions" => self.bits_actions.clone(),
+            "emote_providers" => get_contents(&self.emote_providers, |providers| providers.join(",")),
+            _ => return Err(format!("The store has no {item_type}").into()),
+        };
+
+        Ok(items)
+    }
+
+    fn validate(&self) -> Result<(), Box<dyn Error>> {
+        for item_type in ITEM_TYPES {
+            for (name, contents) in self.get_items(item_type)? {
+                check_item(item_type, &name, &contents)
+                    .map_err(|error| format!("{} {name} is not valid: {error}", item_type.replace('_', " ")))?;
+            }
+        }
+
+        check_aliases(&self.chat_commands, |command| &command.aliases)
+            .map_err(|error| format!("The aliases of the chat commands are not valid: {error}"))?;
+
+        check_aliases(&self.irc_actions, |action| &action.aliases)
+            .map_err(|error| format!("The aliases of the irc-actions are not valid: {error}"))?;
+
+        Ok(())
+    }
+}
+
+fn find_item<T>(
+    items: &BTreeMap<String, T>,
+    is_match: impl Fn(&str) -> bool,
+    get_aliases: impl Fn(&T) -> &Vec<String>,
+) -> Option<(&String, &T)> {
+    // Names are looked up before aliases, like an item added under the name an alias had
+    items.iter().find(|(name, _)| is_match(name)).or_else(|| {
+        items
+            .iter()
+            .find(|(_, item)| get_aliases(item).iter().any(|alias| is_match(alias)))
+    })
+}
+
+fn get_contents<T>(items: &BTreeMap<String, T>, get_content: impl Fn(&T) -> String) -> BTreeMap<String, String> {
+    items
+        .iter()
+        .map(|(name, item)| (name.clone(), get_content(item)))
+        .collect()
+}
+
+/// The files of one of the directories items used to be kept in, by name
+fn read_directory(data_dir: &Path, item_type: &str) -> Result<BTreeMap<String, String>, Box<dyn Error>> {
+    let mut items = BTreeMap::new();
+
+    let directory = data_dir.join(item_type);
+    if !directory.is_dir() {
+        return Ok(items);
+    }
+
+    for entry in fs::read_dir(directory)? {
+        let path = entry?.path();
+        if !path.is_file() {
+            continue;
+        }
+
+        // Dotfiles like .DS_Store were never items
+        let Some(name) = path.file_name().map(|name| name.to_string_lossy().to_string()) else {
+            continue;
+        };
+        if name.starts_with('.') {
+            continue;
+        }
+
+        match fs::read_to_string(&path) {
+            Ok(contents) => {
+                items.insert(name, contents);
+            }
+
+            Err(error) => send_to_error_log(error.to_string(), format!("Could not migrate {path:?}")),
+        }
+    }
+
+    Ok(items)
+}
+
+/// Chat, the admin and the cli are separate processes that all write to the store, the lock file
+/// keeps one from saving over changes another made since it loaded the store
+fn open_lock_file(data_dir: &Path) -> Result<File, Box<dyn Error>> {
+    Ok(File::options()
+        .create(true)
+        .truncate(false)
+        .write(true)
+        .open(data_dir.join(LOCK_FILE))?)
+}
+
+/// Loads the store, changes it and saves it again, other processes wait until it's saved
+pub fn update_store<T>(update: impl FnOnce(&mut Store) -> Result<T, Box<dyn Error>>) -> Result<T, Box<dyn Error>> {
+    update_store_in(&get_data_directory(None)?, update)
+}
+
+pub fn read_store() -> Result<Store, Box<dyn Error>> {
+    read_store_in(&get_data_directory(None)?)
+}
+
+pub fn update_store_in<T>(
+    data_dir: &Path,
+    update: impl FnOnce(&mut Store) -> Result<T, Box<dyn Error>>,
+) -> Result<T, Box<dyn Error>> {
+    let lock_file = open_lock_file(data_dir)?;
+    lock_file.lock()?;
+
+    let mut store = Store::load(data_dir)?;
+    let result = update(&mut store)?;
+    store.save(data_dir)?;
+
+    Ok(result)
+}
+
+pub fn read_store_in(data_dir: &Path) -> Result<Store, Box<dyn Error>> {
+    let lock_file = open_lock_file(data_dir)?;
+
+    // The first read migrates the data directories and saves the store
+    if data_dir.join(STORE_FILE).exists() {
+        lock_file.lock_shared()?;
+    } else {
+        lock_file.lock()?;
+    }
+
+    Store::load(data_dir)
+}
+
+fn check_name(name: &str) -> Result<(), Box<dyn Error>> {
+    if name.trim().is_empty() || name.contains(['/', '\\', '\n']) {
+        return Err("names can't be empty or have slashes or line breaks".into());
+    }
+
+    Ok(())
+}
+
+/// Checks what the types don't, like the minimum bits of a bits action being a number
+fn check_item(item_type: &str, name: &str, contents: &str) -> Result<(), Box<dyn Error>> {
+    check_name(name)?;
+
+    match item_type {
+        "bits_actions" => {
+            name.parse::<u64>()?;
+        }
+
+        "emote_providers" => {
+            let mut providers = contents.split(',').filter(|provider| !provider.is_empty());
+            if let Some(provider) = providers.find(|provider| !DEFAULT_PROVIDERS.contains(provider)) {
+                return Err(format!("{provider} is not an emote provider").into());
+            }
+        }
+
+        _ => {}
+    }
+
+    Ok(())
+}
+
+/// Each alias points at one item and isn't the name of another one
+fn check_aliases<T>(
+    items: &BTreeMap<String, T>,
+    get_aliases: impl Fn(&T) -> &Vec<String>,
+) -> Result<(), Box<dyn Error>> {
+    let mut aliases: Vec<&String> = vec![];
+
+    for item in items.values() {
+        for alias in get_aliases(item) {
+            check_name(alias)?;
+
+            if items.contains_key(alias) {
+                return Err(format!("{alias} is already the name of one").into());
+            }
+
+            if aliases.contains(&alias) {
+                return Err(format!("{alias} points at more than one").into());
+            }
+
+            aliases.push(alias);
+        }
+    }
+
+    Ok(())
+}
+
+#[test]
+fn test_store_validation() {
+    let mut store = Store {
+        version: STORE_VERSION,
+        ..Default::default()
+    };
+
+    let lurk = ChatCommand {
+        message: String::from("{user} is lurking"),
+        count: 3,
+        aliases: vec![String::from("afk")],
+        ..Default::default()
+    };
+    store.chat_commands.insert(String::from("lurk"), lurk);
+
+    let spam = ChatAnnouncement {
+        timing: 5,
+        message: String::from("Follow the channel"),
+    };
+    store.chat_announcements.insert(String::from("spam"), spam);
+
+    store
+        .bits_actions
+        .insert(String::from("100"), String::from("confetti chat"));
+    store
+        .emote_providers
+        .insert(String::from("s9tpepper_"), vec![String::from("7tv")]);
+    assert!(store.validate().is_ok());
+
+    let saved = toml::to_string(&store).unwrap();
+    assert_eq!(Store::parse(&saved).unwrap(), store);
+
+    let newer = saved.replace("version = 1", "version = 2");
+    assert!(Store::parse(&newer).is_err());
+    assert!(Store::parse("version = 1\n[quotes]\nfirst = \"hello\"").is_err());
+    assert!(Store::parse("version = 1\n[chat_commands.lurk]\nmessage = \"lurking\"\ncount = -1").is_err());
+    assert!(
+        Store::parse("version = 1\n[chat_commands.lurk]\nmessage = \"lurking\"\noptions = { whisper = 1 }").is_err()
+    );
+    assert!(Store::parse("version = 1\n[chat_announcements.spam]\nmessage = \"Follow the channel\"").is_err());
+
+    let mut invalid = store.clone();
+    invalid
+        .bits_actions
+        .insert(String::from("lots"), String::from("confetti chat"));
+    assert!(invalid.validate().is_err());
+
+    let mut invalid = store.clone();
+    invalid
+        .emote_providers
+        .insert(String::from("s9tpepper_"), vec![String::from("giphy")]);
+    assert!(invalid.validate().is_err());
+
+    let mut invalid = store.clone();
+    invalid.chat_rewards.insert(String::from(""), String::from("empty"));
+    assert!(invalid.validate().is_err());
+
+    // Aliases can't be the name of a command or point at two commands
+    let mut invalid = store.clone();
+    invalid
+        .chat_commands
+        .insert(String::from("afk"), ChatCommand::default());
+    assert!(invalid.validate().is_err());
+
+    let mut invalid = store.clone();
+    let discord = ChatCommand {
+        aliases: vec![String::from("afk")],
+        ..Default::default()
+    };
+    invalid.chat_commands.insert(String::from("discord"), discord);
+    assert!(invalid.validate().is_err());
+}
+
+#[test]
+fn test_store_find() {
+    let mut store = Store::default();
+    let lurk = ChatCommand {
+        message: String::from("{user} is lurking"),
+        aliases: vec![String::from("afk")],
+        ..Default::default()
+    };
+    store.chat_commands.insert(String::from("lurk"), lurk);
+
+    assert_eq!(store.find_chat_command(|name| name == "lurk").unwrap().0, "lurk");
+    assert_eq!(store.find_chat_command(|name| name == "afk").unwrap().0, "lurk");
+    assert!(store.find_chat_command(|name| name == "discord").is_none());
+    assert!(store.find_irc_action(|name| name == "lurk").is_none());
+}
+
+#[test]
+fn test_store_migration() {
+    let data_dir = std::env::temp_dir().join(format!("sergeant_store_migration_{}", std::process::id()));
+    let write = |item_type: &str, name: &str, contents: &str| {
+        fs::create_dir_all(data_dir.join(item_type)).unwrap();
+        fs::write(data_dir.join(item_type).join(name), contents).unwrap();
+    };
+
+    write("chat_commands", "discord", "Join the discord");
+    write("chat_announcements", "spam", "5\nFollow the channel");
+    write("chat_announcements", "timeless", "Follow the channel");
+    write("irc_actions", "boom", "any-cli-command chat");
+    write("chat_commands", ".DS_Store", "");
+    write("chat_rewards", "hydrate", "drink-water chat");
+    fs::write(data_dir.join("chat_rewards").join("binary"), [0xff, 0xfe, 0x00]).unwrap();
+
+    let store = Store::migrate(&data_dir).unwrap();
+    fs::remove_dir_all(&data_dir).unwrap();
+
+    assert_eq!(store.version, STORE_VERSION);
+    assert_eq!(
+        store.chat_commands.get("discord"),
+        Some(&ChatCommand {
+            message: String::from("Join the discord"),
+            ..Default::default()
+        })
+    );
+    assert_eq!(
+        store.chat_announcements.get("spam"),
+        Some(&ChatAnnouncement {
+            timing: 5,
+            message: String::from("Follow the channel"),
+        })
+    );
+    assert_eq!(store.chat_announcements.get("timeless"), None);
+    assert_eq!(
+        store.irc_actions.get("boom"),
+        Some(&IrcAction {
+            command: String::from("any-cli-command chat"),
+            ..Default::default()
+        })
+    );
+    assert_eq!(store.chat_commands.get(".DS_Store"), None);
+    assert_eq!(
+        store.chat_rewards.get("hydrate"),
+        Some(&String::from("drink-water chat"))
+    );
+    assert_eq!(store.chat_rewards.get("binary"), None);
+}
+
+#[test]
+fn test_store_lock() {
+    let data_dir = std::env::temp_dir().join(format!("sergeant_store_lock_{}", std::process::id()));
+    fs::create_dir_all(&data_dir).unwrap();
+
+    update_store_in(&data_dir, |store| {
+        store
+            .chat_commands
+            .insert(String::from("deaths"), ChatCommand::default());
+
+        Ok(())
+    })
+    .unwrap();
+
+    // Each update opens the lock file on its own, like separate processes do
+    let updates: Vec<_> = (0..8)
+        .map(|_| {
+            let data_dir = data_dir.clone();
+            std::thread::spawn(move || {
+                for _ in 0..5 {
+                    update_store_in(&data_dir, |store| {
+                        if let Some(command) = store.chat_commands.get_mut("deaths") {
+                            command.count += 1;
+                        }
+
+                        Ok(())
+                    })
+                    .unwrap();
+                }
+            })
+        })
+        .collect();
+
+    for update in updates {
+        update.join().unwrap();
+    }
+
+    let store = read_store_in(&data_dir).unwrap();
+    fs::remove_dir_all(&data_dir).unwrap();
+
+    assert_eq!(store.chat_commands.get("deaths").map(|command| command.count), Some(40));
+}
@@ -0,0 +1,320 @@
+use std::{
+    error::Error,
+    hash::{DefaultHasher, Hash, Hasher},
+    io::Cursor,
+    str::FromStr,
+    sync::OnceLock,
+};
+
+use base64::prelude::*;
+use image::{imageops::FilterType, ImageFormat, RgbaImage};
+use ratatui::style::Color;
+
+const ESCAPE: &str = "\x1b";
+const BELL: &str = "\x07";
+
+// Kitty only accepts 4096 bytes of base64 per escape sequence
+const KITTY_CHUNK_SIZE: usize = 4096;
+
+// Kitty draws images with unicode placeholders, the diacritics pick the row and column of the
+// image each placeholder shows. Emotes are one row and two columns, the same as EMOTE_SPACE
+const KITTY_PLACEHOLDER: char = '\u{10EEEE}';
+const KITTY_ROW_COLUMN: [char; 2] = ['\u{0305}', '\u{030D}'];
+
+// Emotes are scaled to fit two cells, this assumes the usual 10x20 pixel terminal cell
+const SIXEL_SIZE: u32 = 20;
+
+static IMAGE_PROTOCOL: OnceLock<ImageProtocol> = OnceLock::new();
+
+/// How emotes and badges are drawn in the terminal
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum ImageProtocol {
+    /// iTerm2 inline images, also supported by WezTerm
+    Iterm,
+    /// The kitty graphics protocol, also supported by Ghostty
+    Kitty,
+    /// Sixel graphics for foot, mlterm, xterm and friends
+    Sixel,
+    /// No images, emotes are shown as :emoteName:
+    Text,
+}
+
+impl FromStr for ImageProtocol {
+    type Err = String;
+
+    fn from_str(protocol: &str) -> Result<Self, Self::Err> {
+        match protocol.to_lowercase().as_str() {
+            "iterm" => Ok(ImageProtocol::Iterm),
+            "kitty" => Ok(ImageProtocol::Kitty),
+            "sixel" => Ok(ImageProtocol::Sixel),
+            "text" => Ok(ImageProtocol::Text),
+            _ => Err(format!("Unknown image protocol: {protocol}")),
+        }
+    }
+}
+
+impl ImageProtocol {
+    pub fn detect() -> Self {
+        ImageProtocol::detect_from(|name| std::env::var(name).ok())
+    }
+
+    /// Guesses the protocol from the terminal's environment variables
+    pub fn detect_from(get_env: impl Fn(&str) -> Option<String>) -> Self {
+        let term = get_env("TERM").unwrap_or_default().to_lowercase();
+        let term_program = get_env("TERM_PROGRAM").unwrap_or_default().to_lowercase();
+
+        // LC_TERMINAL survives tmux and ssh, TERM_PROGRAM doesn't
+        let lc_terminal = get_env("LC_TERMINAL").unwrap_or_default().to_lowercase();
+
+        if get_env("KITTY_WINDOW_ID").is_some() || term.contains("kitty") || term_program == "ghostty" {
+            return ImageProtocol::Kitty;
+        }
+
+        if term_program == "iterm.app" || term_program == "wezterm" || lc_terminal == "iterm2" {
+            return ImageProtocol::Iterm;
+        }
+
+        if term.starts_with("foot") || term.contains("mlterm") || term.contains("sixel") {
+            return ImageProtocol::Sixel;
+        }
+
+        ImageProtocol::Text
+    }
+
+    pub fn shows_images(&self) -> bool {
+        *self != ImageProtocol::Text
+    }
+
+    /// Encodes an image as the escape sequence that draws it in a single cell
+    pub fn encode(&self, image_bytes: &[u8], image_id: &str) -> Result<String, Box<dyn Error>> {
+        match self {
+            ImageProtocol::Iterm => Ok(encode_iterm(image_bytes)),
+            ImageProtocol::Kitty => encode_kitty(image_bytes, image_id),
+            ImageProtocol::Sixel => {
+                let image = image::load_from_memory(image_bytes)?;
+                let image = image.resize(SIXEL_SIZE, SIXEL_SIZE, FilterType::Triangle);
+
+                Ok(encode_sixel(&image.to_rgba8()))
+            }
+            ImageProtocol::Text => Ok(String::new()),
+        }
+    }
+
+    /// Kitty placeholders find their image through the foreground color of the cell
+    pub fn get_foreground(&self, image_id: &str) -> Option<Color> {
+        match self {
+            ImageProtocol::Kitty => {
+                let id = get_kitty_image_id(image_id);
+                Some(Color::Rgb((id >> 16) as u8, (id >> 8) as u8, id as u8))
+            }
+            _ => None,
+        }
+    }
+}
+
+/// Overrides the detected protocol, has to be called before the first emote is loaded
+pub fn set_image_protocol(protocol: ImageProtocol) {
+    let _ = IMAGE_PROTOCOL.set(protocol);
+}
+
+pub fn get_image_protocol() -> ImageProtocol {
+    *IMAGE_PROTOCOL.get_or_init(ImageProtocol::detect)
+}
+
+fn encode_iterm(image_bytes: &[u8]) -> String {
+    let base64_image = BASE64_STANDARD.encode(image_bytes);
+
+    format!("{ESCAPE}]1337;File=inline=1;height=22px;width=22px;doNotMoveCursor=1:{base64_image}{BELL}")
+}
+
+fn get_kitty_image_id(image_id: &str) -> u32 {
+    let mut hasher = DefaultHasher::new();
+    image_id.hash(&mut hasher);
+
+    // The id has to fit in a 24 bit color and 0 is not a valid id
+    ((hasher.finish() & 0xFFFFFF) as u32).max(1)
+}
+
+fn encode_kitty(image_bytes: &[u8], image_id: &str) -> Result<String, Box<dyn Error>> {
+    // Kitty understands PNG, emotes are often GIF or WebP
+    let image = image::load_from_memory(image_bytes)?;
+    let mut png: Vec<u8> = vec![];
+    image.write_to(&mut Cursor::new(&mut png), ImageFormat::Png)?;
+
+    Ok(get_kitty_escapes(&png, get_kitty_image_id(image_id)))
+}
+
+fn get_kitty_escapes(png: &[u8], id: u32) -> String {
+    let base64_image = BASE64_STANDARD.encode(png);
+    let chunks: Vec<&[u8]> = base64_image.as_bytes().chunks(KITTY_CHUNK_SIZE).collect();
+    let last_index = chunks.len().saturating_sub(1);
+
+    let mut escapes = String::new();
+    for (index, chunk) in chunks.iter().enumerate() {
+        let more = if index == last_index { 0 } else { 1 };
+        let chunk = String::from_utf8_lossy(chunk);
+
+        // Only the first chunk carries the image keys, q=2 keeps kitty from answering on stdin
+        if index == 0 {
+            escapes.push_str(&format!(
+                "{ESCAPE}_Ga=T,U=1,i={id},f=100,t=d,c=2,r=1,q=2,m={more};{chunk}{ESCAPE}\\"
+            ));
+        } else {
+            escapes.push_str(&format!("{ESCAPE}_Gm={more};{chunk}{ESCAPE}\\"));
+        }
+    }
+
+    for column in KITTY_ROW_COLUMN {
+        escapes.push(KITTY_PLACEHOLDER);
+        escapes.push(KITTY_ROW_COLUMN[0]);
+        escapes.push(column);
+    }
+
+    escapes
+}
+
+// Colors are reduced to a 6x6x6 cube, plenty for emotes this small
+fn get_sixel_color(r: u8, g: u8, b: u8) -> u8 {
+    let level = |value: u8| ((value as u16 * 5 + 127) / 255) as u8;
+
+    level(r) * 36 + level(g) * 6 + level(b)
+}
+
+fn push_sixel_run(sixel: &mut String, sixel_char: char, count: usize) {
+    if count > 3 {
+        sixel.push_str(&format!("!{count}{sixel_char}"));
+    } else {
+        (0..count).for_each(|_| sixel.push(sixel_char));
+    }
+}
+
+fn encode_sixel(image: &RgbaImage) -> String {
+    let (width, height) = image.dimensions();
+
+    // Mostly transparent pixels are left out so the background shows through
+    let pixels: Vec<Option<u8>> = image
+        .pixels()
+        .map(|pixel| {
+            let [r, g, b, a] = pixel.0;
+            (a >= 128).then(|| get_sixel_color(r, g, b))
+        })
+        .collect();
+
+    let mut colors: Vec<u8> = pixels.iter().flatten().copied().collect();
+    colors.sort();
+    colors.dedup();
+
+    let mut sixel = format!("{ESCAPE}P0;1;0q\"1;1;{width};{height}");
+    for color in colors.iter() {
+        let percent = |level: u8| level as u32 * 20;
+        sixel.push_str(&format!(
+            "#{color};2;{};{};{}",
+            percent(color / 36),
+            percent(color / 6 % 6),
+            percent(color % 6)
+        ));
+    }
+
+    // Each sixel is a column of 6 pixels, one pass per color over every band of 6 rows
+    for band_top in (0..height).step_by(6) {
+        for color in colors.iter() {
+            let mut band = String::new();
+            let mut has_pixels = false;
+            let mut run: Option<(char, usize)> = None;
+
+            for x in 0..width {
+                let mut bits: u8 = 0;
+                for row in 0..6 {
+                    let y = band_top + row;
+                    if y < height && pixels[(y * width + x) as usize] == Some(*color) {
+                        bits |= 1 << row;
+                    }
+                }
+
+                has_pixels |= bits != 0;
+                let sixel_char = (63 + bits) as char;
+
+                run = match run {
+                    Some((run_char, count)) if run_char == sixel_char => Some((run_char, count + 1)),
+                    Some((run_char, count)) => {
+                        push_sixel_run(&mut band, run_char, count);
+                        Some((sixel_char, 1))
+                    }
+                    None => Some((sixel_char, 1)),
+                };
+            }
+
+            // Empty sixels at the end of a band don't need to be sent
+            if let Some((run_char, count)) = run {
+                if run_char != '?' {
+                    push_sixel_run(&mut band, run_char, count);
+                }
+            }
+
+            if has_pixels {
+                sixel.push_str(&format!("#{color}{band}$"));
+            }
+        }
+
+        sixel.push('-');
+    }
+
+    sixel.push_str(&format!("{ESCAPE}\\"));
+
+    sixel
+}
+
+#[test]
+fn test_detect_image_protocol() {
+    let detect = |vars: &[(&str, &str)]| {
+        ImageProtocol::detect_from(|name| {
+            vars.iter()
+                .find(|(key, _)| *key == name)
+                .map(|(_, value)| value.to_string())
+        })
+    };
+
+    assert_eq!(detect(&[("TERM", "xterm-kitty")]), ImageProtocol::Kitty);
+    assert_eq!(detect(&[("KITTY_WINDOW_ID", "1")]), ImageProtocol::Kitty);
+    assert_eq!(detect(&[("TERM_PROGRAM", "iTerm.app")]), ImageProtocol::Iterm);
+    assert_eq!(
+        detect(&[("TERM", "tmux-256color"), ("LC_TERMINAL", "iTerm2")]),
+        ImageProtocol::Iterm
+    );
+    assert_eq!(detect(&[("TERM", "foot")]), ImageProtocol::Sixel);
+    assert_eq!(detect(&[("TERM", "xterm-256color")]), ImageProtocol::Text);
+
+    assert_eq!("Kitty".parse::<ImageProtocol>(), Ok(ImageProtocol::Kitty));
+    assert!("png".parse::<ImageProtocol>().is_err());
+}
+
+#[test]
+fn test_encode_sixel() {
+    // One red pixel above one transparent pixel
+    let mut image = RgbaImage::new(1, 2);
+    image.put_pixel(0, 0, image::Rgba([255, 0, 0, 255]));
+
+    assert_eq!(encode_sixel(&image), "\x1bP0;1;0q\"1;1;1;2#180;2;100;0;0#180@$-\x1b\\");
+
+    // Long runs are compressed
+    let image = RgbaImage::from_pixel(8, 1, image::Rgba([0, 0, 255, 255]));
+    assert_eq!(encode_sixel(&image), "\x1bP0;1;0q\"1;1;8;1#5;2;0;0;100#5!8@$-\x1b\\");
+}
+
+#[test]
+fn test_kitty_escapes() {
+    let png = vec![0; KITTY_CHUNK_SIZE];
+    let escapes = get_kitty_escapes(&png, 42);
+
+    // 4096 bytes of base64 per chunk
+    assert!(escapes.starts_with("\x1b_Ga=T,U=1,i=42,f=100,t=d,c=2,r=1,q=2,m=1;"));
+    assert_eq!(escapes.matches("\x1b_Gm=1;").count(), 0);
+    assert_eq!(escapes.matches("\x1b_Gm=0;").count(), 1);
+    assert!(escapes.ends_with("\u{10EEEE}\u{0305}\u{0305}\u{10EEEE}\u{0305}\u{030D}"));
+
+    assert_eq!(
+        ImageProtocol::Kitty.get_foreground("25"),
+        ImageProtocol::Kitty.get_foreground("25")
+    );
+    assert_eq!(ImageProtocol::Iterm.get_foreground("25"), None);
+}
@@ -1,13 +1,21 @@
-use crate::commands::get_action;
+use crate::commands::change_command_count;
+use crate::commands::cooldown::{use_cooldown, Cooldown};
+use crate::commands::counter::{send_counter, CounterChange};
+use crate::commands::matcher::{get_command_matcher, Arguments};
+use crate::commands::permissions::{Permissions, Role};
+use crate::commands::template::{format_duration, render_template, HelixLookup, TemplateContext, TemplateLookup};
 use crate::scrollview::scroll_view::ScrollView;
 use crate::scrollview::state::ScrollViewState;
+use crate::store::{read_store, ChatCommand, Store};
 use crate::twitch::irc::TwitchIrcClient;
 
 use color_eyre::config::HookBuilder;
 use color_eyre::eyre;
 
 use ratatui::prelude::*;
+use ratatui::widgets::{Block, Tabs};
 
+use std::collections::BTreeMap;
 use std::io::{self, stdout, Stdout};
 use std::process::{self, Command};
 use std::str::FromStr;
@@ -22,8 +30,9 @@ use ratatui::backend::CrosstermBackend;
 use color_eyre::{eyre::Result, eyre::WrapErr};
 
 use crate::tui;
+use crate::twitch::emote_loader::get_emote_loader;
 use crate::twitch::parse::Text;
-use crate::twitch::parse::{Emote, RedeemMessage};
+use crate::twitch::parse::{Emote, RedeemMessage, WhisperMessage};
 use crate::twitch::pubsub::{send_to_error_log, SubMessage};
 use crate::twitch::ChannelMessages;
 use crate::{
@@ -34,19 +43,48 @@ use crate::{
     utils::get_data_directory,
 };
 
+pub mod image_protocol;
 pub mod test_messages;
 
 /// A type alias for the terminal type used in this application
 pub type Tui = Terminal<CrosstermBackend<Stdout>>;
 
+const CHAT_LOG_MAX: usize = 100;
+const WHISPERS_MAX: usize = 50;
+const WHISPERS_PANE_HEIGHT: u16 = 8;
+
 #[derive(Debug, Default)]
 pub struct App {
     twitch_name: String,
+    channels: Vec<String>,
+    active_tab: usize,
     scroll_view_state: ScrollViewState,
     chat_log: Vec<ChannelMessages>,
+    /// Newest first, kept apart from chat and never saved or sent to the overlay
+    whispers: Vec<WhisperMessage>,
+    show_whispers: bool,
+    read_only: bool,
     exit: bool,
 }
 
+/// The tab a message is shown in, None for messages that are shown in every tab
+fn get_message_tab(channels: &[String], message: &ChannelMessages) -> Option<usize> {
+    match message {
+        ChannelMessages::TwitchMessage(TwitchMessage::ConnectionStatusMessage { .. }) => None,
+
+        ChannelMessages::TwitchMessage(message) => {
+            let tab = message
+                .channel()
+                .and_then(|channel| channels.iter().position(|item| item == channel));
+
+            Some(tab.unwrap_or(0))
+        }
+
+        // PubSub and EventSub only cover the streamer's own channel, which is the first tab
+        _ => Some(0),
+    }
+}
+
 #[derive(Debug, Clone, PartialEq)]
 pub enum Symbol {
     Text(Text),
@@ -69,17 +107,30 @@ impl Clone for MessageParts {
 }
 
 impl App {
-    pub fn new(twitch_name: &str) -> Self {
+    pub fn new(twitch_name: &str, channels: &[String]) -> Self {
         let scroll_view_state = ScrollViewState::new();
 
         App {
             twitch_name: twitch_name.to_string(),
+            channels: channels.to_vec(),
+            active_tab: 0,
             scroll_view_state,
             chat_log: vec![],
+            whispers: vec![],
+            show_whispers: true,
+            read_only: false,
             exit: false,
         }
     }
 
+    /// Chat for channels watched anonymously, the chat log and overlay are left alone
+    pub fn new_read_only(channels: &[String]) -> Self {
+        App {
+            read_only: true,
+            ..App::new("", channels)
+        }
+    }
+
     pub fn handle_events(&mut self, terminal: &mut Terminal<CrosstermBackend<Stdout>>) -> Result<()> {
         let available = event::poll(time::Duration::from_millis(16))?;
         if available {
@@ -122,6 +173,15 @@ impl App {
             KeyCode::Char('b') => self.scroll_view_state.scroll_page_up(),
             KeyCode::Char('g') => self.scroll_view_state.scroll_to_top(),
             KeyCode::Char('G') => self.scroll_view_state.scroll_to_bottom(),
+            KeyCode::Char('w') => self.show_whispers = !self.show_whispers,
+            KeyCode::Tab => self.select_tab(self.active_tab + 1),
+            KeyCode::BackTab => self.select_tab(self.active_tab + self.channels.len().saturating_sub(1)),
+            KeyCode::Char(number @ '1'..='9') => {
+                let tab = number.to_digit(10).unwrap_or(1) as usize - 1;
+                if tab < self.channels.len() {
+                    self.select_tab(tab);
+                }
+            }
             _ => {}
         }
 
@@ -137,7 +197,9 @@ impl App {
 
         enable_raw_mode()?;
 
-        let _ = self.restore_chat_log();
+        if !self.read_only {
+            let _ = self.restore_chat_log();
+        }
 
         // NOTE: Test messages can go here for now
         // let test_raid_message = get_raid_message();
@@ -151,6 +213,12 @@ impl App {
         while !self.exit {
             let _ = self.handle_events(&mut terminal);
 
+            // Emotes are loaded in the background, draw chat again once they are ready
+            if get_emote_loader().take_repaint() {
+                let _ = terminal.backend_mut().clear_region(backend::ClearType::All);
+                terminal.draw(|frame| self.render(frame))?;
+            }
+
             if let Ok(message) = rx.try_recv() {
                 let _ = terminal.backend_mut().clear_region(backend::ClearType::All);
 
@@ -166,6 +234,27 @@ impl App {
                                 });
                             }
 
+                            // NOTE: Timeouts, bans and full chat clears from IRC
+                            TwitchMessage::ClearChatMessage { message: clear_chat } => {
+                                self.chat_log.retain(|msg| match msg {
+                                    ChannelMessages::TwitchMessage(TwitchMessage::PrivMessage { message: msg }) => {
+                                        !clear_chat.targets(msg)
+                                    }
+                                    _ => true,
+                                });
+
+                                self.chat_log.insert(0, ChannelMessages::TwitchMessage(message.clone()));
+                                self.truncate();
+
+                                terminal.draw(|frame| self.render(frame))?;
+                            }
+
+                            TwitchMessage::WhisperMessage { message: whisper } => {
+                                self.add_whisper(whisper.clone());
+
+                                terminal.draw(|frame| self.render(frame))?;
+                            }
+
                             // NOTE: This message comes from EventSub channel
                             TwitchMessage::ClearMessageByUser { message } => {
                                 self.chat_log.retain(|msg| match msg {
@@ -241,6 +330,9 @@ impl App {
                     // noop here
                     ChannelMessages::Announcement(_) => {}
 
+                    // Counters are sent straight to the overlay
+                    ChannelMessages::Counter(_) => {}
+
                     ChannelMessages::Notifications(subscription_event) => {
                         if let Some(notice_type) = &subscription_event.notice_type {
                             #[allow(clippy::single_match)]
@@ -275,6 +367,16 @@ impl App {
                     }
                 }
 
+                // Read-only chat is for watching other channels, it doesn't touch your own log or overlay.
+                // Whispers are private and other joined channels aren't yours, they stay out of both too
+                let is_whisper = matches!(
+                    message,
+                    ChannelMessages::TwitchMessage(TwitchMessage::WhisperMessage { .. })
+                );
+                if self.read_only || is_whisper || !self.is_own_channel(&message) {
+                    continue;
+                }
+
                 // NOTE: Comment out for local dev
                 let _ = self.persist_chat_log();
 
@@ -322,8 +424,24 @@ impl App {
         Ok(())
     }
 
+    /// Whether the message belongs to the broadcaster's channel, PubSub and EventSub only cover that one
+    fn is_own_channel(&self, message: &ChannelMessages) -> bool {
+        match message {
+            ChannelMessages::TwitchMessage(message) => message
+                .channel()
+                .is_none_or(|channel| channel.trim_start_matches('#').eq_ignore_ascii_case(&self.twitch_name)),
+
+            _ => true,
+        }
+    }
+
     fn persist_chat_log(&self) -> Result<(), Box<dyn Error>> {
-        let json_string = serde_json::to_string(&self.chat_log).unwrap_or_default();
+        let chat_log: Vec<&ChannelMessages> = self
+            .chat_log
+            .iter()
+            .filter(|message| self.is_own_channel(message))
+            .collect();
+        let json_string = serde_json::to_string(&chat_log).unwrap_or_default();
 
         let target_dir = "chat_log";
         let mut chat_log_path = get_data_directory(Some(target_dir))?;
@@ -340,9 +458,65 @@ impl App {
         Ok(())
     }
 
+    fn add_whisper(&mut self, whisper: WhisperMessage) {
+        // IRC and EventSub can both deliver the same whisper
+        let repeated = self.whispers.first().is_some_and(|newest| {
+            !newest.outgoing
+                && !whisper.outgoing
+                && newest.from_login == whisper.from_login
+                && newest.message == whisper.message
+        });
+
+        if !repeated {
+            self.whispers.insert(0, whisper);
+            self.whispers.truncate(WHISPERS_MAX);
+        }
+    }
+
+    fn select_tab(&mut self, tab: usize) {
+        if self.channels.is_empty() {
+            return;
+        }
+
+        self.active_tab = tab % self.channels.len();
+        self.scroll_view_state.scroll_to_bottom();
+    }
+
     fn truncate(&mut self) {
-        if self.chat_log.len() > 100 {
-            self.chat_log.remove(self.chat_log.len() - 1);
+        for tab in 0..self.channels.len().max(1) {
+            let tab_messages = self
+                .chat_log
+                .iter()
+                .enumerate()
+                .filter(|(_, message)| get_message_tab(&self.channels, message).is_none_or(|item| item == tab))
+                .map(|(index, _)| index)
+                .collect::<Vec<usize>>();
+
+            if tab_messages.len() > CHAT_LOG_MAX {
+                if let Some(oldest) = tab_messages.last() {
+                    self.chat_log.remove(*oldest);
+                }
+            }
+        }
+    }
+
+    fn render_whispers(&mut self, area: Rect, buf: &mut Buffer) {
+        let block = Block::bordered()
+            .title(" Whispers ")
+            .border_style(Style::default().fg(Color::Rgb(145, 70, 255)));
+        let mut available_area = block.inner(area);
+        block.render(area, buf);
+
+        for whisper in self.whispers.iter_mut() {
+            if available_area.height == 0 {
+                break;
+            }
+
+            whisper.render(available_area, buf);
+
+            if let Some(whisper_area) = whisper.area {
+                available_area.height = available_area.height.saturating_sub(whisper_area.height);
+            }
         }
     }
 }
@@ -353,6 +527,33 @@ impl StatefulWidget for &mut App {
     fn render(self, area: Rect, buf: &mut Buffer, _state: &mut Self::State) {
         buf.reset();
 
+        // Only show the tabs when more than one channel was joined
+        let area = if self.channels.len() > 1 {
+            let [tabs_area, chat_area] = Layout::vertical([Constraint::Length(1), Constraint::Min(0)]).areas(area);
+
+            Tabs::new(self.channels.iter().map(String::as_str))
+                .select(self.active_tab)
+                .style(Style::default().fg(Color::Gray))
+                .highlight_style(Style::default().fg(Color::Rgb(145, 70, 255)).bold())
+                .render(tabs_area, buf);
+
+            chat_area
+        } else {
+            area
+        };
+
+        // Whispers get a pane under chat once the first one arrives, w hides it
+        let area = if self.show_whispers && !self.whispers.is_empty() {
+            let [chat_area, whispers_area] =
+                Layout::vertical([Constraint::Min(0), Constraint::Length(WHISPERS_PANE_HEIGHT)]).areas(area);
+
+            self.render_whispers(whispers_area, buf);
+
+            chat_area
+        } else {
+            area
+        };
+
         let content_size = layout::Size {
             // Subtract one to avoid getting horizontal scrollbar from tui-scrollview
             width: area.width.saturating_sub(1),
@@ -368,7 +569,13 @@ impl StatefulWidget for &mut App {
             self.scroll_view_state.scroll_to_bottom();
         }
 
+        let channels = &self.channels;
+        let active_tab = self.active_tab;
         self.chat_log.iter_mut().for_each(|message| {
+            if get_message_tab(channels, message).is_some_and(|tab| tab != active_tab) {
+                return;
+            }
+
             let message_area = match message {
                 ChannelMessages::TwitchMessage(message) => match message {
                     TwitchMessage::PrivMessage { message } => {
@@ -389,6 +596,84 @@ impl StatefulWidget for &mut App {
                         message.area
                     }
 
+                    TwitchMessage::SubNotice { message } => {
+                        message.render(available_area, scroll_view.buf_mut());
+
+                        message.area
+                    }
+
+                    TwitchMessage::ResubNotice { message } => {
+                        message.render(available_area, scroll_view.buf_mut());
+
+                        message.area
+                    }
+
+                    TwitchMessage::SubGiftNotice { message } => {
+                        message.render(available_area, scroll_view.buf_mut());
+
+                        message.area
+                    }
+
+                    TwitchMessage::SubMysteryGiftNotice { message } => {
+                        message.render(available_area, scroll_view.buf_mut());
+
+                        message.area
+                    }
+
+                    TwitchMessage::GiftPaidUpgradeNotice { message } => {
+                        message.render(available_area, scroll_view.buf_mut());
+
+                        message.area
+                    }
+
+                    TwitchMessage::PrimePaidUpgradeNotice { message } => {
+                        message.render(available_area, scroll_view.buf_mut());
+
+                        message.area
+                    }
+
+                    TwitchMessage::AnnouncementNotice { message } => {
+                        message.render(available_area, scroll_view.buf_mut());
+
+                        message.area
+                    }
+
+                    TwitchMessage::BitsBadgeTierNotice { message } => {
+                        message.render(available_area, scroll_view.buf_mut());
+
+                        message.area
+                    }
+
+                    TwitchMessage::ViewerMilestoneNotice { message } => {
+                        message.render(available_area, scroll_view.buf_mut());
+
+                        message.area
+                    }
+
+                    TwitchMessage::CharityDonationNotice { message } => {
+                        message.render(available_area, scroll_view.buf_mut());
+
+                        message.area
+                    }
+
+                    TwitchMessage::NoticeMessage { message } => {
+                        message.render(available_area, scroll_view.buf_mut());
+
+                        message.area
+                    }
+
+                    TwitchMessage::ClearChatMessage { message } => {
+                        message.render(available_area, scroll_view.buf_mut());
+
+                        message.area
+                    }
+
+                    TwitchMessage::ConnectionStatusMessage { message } => {
+                        message.render(available_area, scroll_view.buf_mut());
+
+                        message.area
+                    }
+
                     _ => Some(Rect::new(0, 0, 0, 0)),
                 },
 
@@ -416,7 +701,7 @@ impl StatefulWidget for &mut App {
             }
         });
 
-        scroll_view.render(buf.area, buf, &mut self.scroll_view_state);
+        scroll_view.render(area, buf, &mut self.scroll_view_state);
     }
 }
 
@@ -453,10 +738,6 @@ pub fn output(message: TwitchMessage, client: &mut TwitchIRC) {
     }
 }
 
-pub fn get_list_commands() -> Result<Vec<String>, Box<dyn Error>> {
-    get_list("chat_commands")
-}
-
 /// Restore the terminal to its original state
 pub fn restore() -> io::Result<()> {
     execute!(stdout(), LeaveAlternateScreen)?;
@@ -464,53 +745,84 @@ pub fn restore() -> io::Result<()> {
     Ok(())
 }
 
-fn get_list(directory: &str) -> Result<Vec<String>, Box<dyn Error>> {
-    let command_path = get_data_directory(Some(directory))?;
-    let mut commands = vec![];
-    let dir_entries = fs::read_dir(command_path)?;
-
-    for entry in dir_entries {
-        let entry = entry?;
-        let path = entry.path();
-        if path.is_file() {
-            let file_name = path.file_name();
-            if let Some(file_name) = file_name {
-                commands.push(file_name.to_string_lossy().to_string());
-            }
-        }
+pub fn check_for_irc_actions(message: &ChatMessage, client: &mut impl TwitchIrcClient) {
+    let matcher = get_command_matcher();
+    let Some((command, args)) = matcher.parse(&message.message) else {
+        return;
+    };
+
+    let Some(store) = load_store() else {
+        return;
+    };
+
+    let Some((name, action)) = store.find_irc_action(|name| matcher.is_match(command, name)) else {
+        return;
+    };
+
+    // Aliases share the cooldowns of the irc-action they point at
+    let key = format!("irc_actions/{name}");
+    let options = &action.options;
+    if is_permitted(&options.permissions, message, client)
+        && has_arguments(&options.arguments, name, &args, false, message, client)
+        && take_cooldown(&key, &options.cooldown, false, message, client)
+    {
+        let _ = run_action(&action.command, message, &args, client);
+    }
+}
+
+/// Runs every bits action whose minimum amount of bits is covered by the cheer
+pub fn check_for_bits_actions(message: &ChatMessage, client: &mut impl TwitchIrcClient) {
+    let Some(bits) = message.bits else {
+        return;
+    };
+
+    let Some(store) = load_store() else {
+        return;
+    };
+
+    if let Some(cmd_mapping) = get_bits_action(&store.bits_actions, bits) {
+        let _ = run_action(cmd_mapping, message, &[&bits.to_string()], client);
     }
+}
 
-    Ok(commands)
+/// The action of the highest tier the cheer reached, tiers are keyed by their minimum bits
+fn get_bits_action(bits_actions: &BTreeMap<String, String>, bits: u64) -> Option<&String> {
+    let mut tiers: Vec<(u64, &String)> = bits_actions
+        .iter()
+        .filter_map(|(min_bits, cmd_mapping)| Some((min_bits.parse::<u64>().ok()?, cmd_mapping)))
+        .collect();
+    tiers.sort_by_key(|(min_bits, _)| *min_bits);
+
+    tiers
+        .into_iter()
+        .rev()
+        .find(|(min_bits, _)| *min_bits <= bits)
+        .map(|(_, cmd_mapping)| cmd_mapping)
 }
 
-pub fn check_for_irc_actions(message: &str, display_name: &str, client: &mut impl TwitchIrcClient) {
-    let irc_actions_list = get_list("irc_actions");
-    if let Ok(list) = &irc_actions_list {
-        for item in list {
-            let command = format!("!{}", item);
-            if message == command {
-                let _ = execute_command(item, display_name, client);
-            }
+/// The store read once for a chat message, errors are logged and the message is skipped
+fn load_store() -> Option<Store> {
+    match read_store() {
+        Ok(store) => Some(store),
+        Err(error) => {
+            send_to_error_log(error.to_string(), "Could not read the store".to_string());
+
+            None
         }
     }
 }
 
-pub fn execute_command(
-    command: &str,
-    display_name: &str,
+/// Runs a "cmd[:sub_command] option" action mapping, the display_name of the chatter
+/// and any extra args are passed on to the CLI command. Options: "chat" sends the command
+/// output to chat, "reply" sends it as a threaded reply to the message that triggered it,
+/// "whisper" sends it to the chatter as a whisper
+fn run_action(
+    cmd_mapping: &str,
+    message: &ChatMessage,
+    extra_args: &[&str],
     client: &mut impl TwitchIrcClient,
 ) -> Result<(), Box<dyn Error>> {
-    let irc_action = get_action(command);
-
-    let found_command = if irc_action.is_ok() {
-        irc_action
-    } else {
-        return Ok(());
-    };
-
-    let Ok(cmd_mapping) = found_command else {
-        return Ok(());
-    };
+    let display_name = message.nickname.as_str();
 
     let Some((cmd_mapping, command_option)) = cmd_mapping.split_once(' ') else {
         return Ok(());
@@ -536,6 +848,8 @@ pub fn execute_command(
         send_to_error_log("irc_action".to_string(), format!("Added display_name: {display_name}"));
     }
 
+    args.extend(extra_args);
+
     command.args(args.clone());
     send_to_error_log("irc_action".to_string(), format!("Added args: {}", args.join(",")));
 
@@ -548,11 +862,19 @@ pub fn execute_command(
     let command_success = command_result.status.success();
     if command_success && command_option == "chat" {
         if let Ok(stdout) = String::from_utf8(command_result.stdout.clone()) {
-            client.send_privmsg(&stdout);
+            send_to_chat(&message.channel, &stdout, client);
+        }
+    }
+
+    if command_success && command_option == "reply" {
+        if let Ok(stdout) = String::from_utf8(command_result.stdout.clone()) {
+            reply_to_message(message, &stdout, client);
+        }
+    }
 
-            // Send message to display since this IRC client is the one posting the message
-            // it won't display if its not sent directly to the TUI client for rendering
-            client.display_msg(&stdout);
+    if command_success && command_option == "whisper" {
+        if let Ok(stdout) = String::from_utf8(command_result.stdout.clone()) {
+            whisper_to_chatter(message, &stdout, client);
         }
     }
 
@@ -571,49 +893,211 @@ pub fn execute_command(
     Ok(())
 }
 
-pub fn check_for_chat_commands(message: &str, client: &mut impl TwitchIrcClient) {
-    let commands_list = get_list_commands();
-    if message == "!commands" {
-        let available_commands = commands_list
-            .unwrap()
-            .iter()
-            .map(|item| format!("!{}", item))
+fn send_to_chat(channel: &str, message: &str, client: &mut impl TwitchIrcClient) {
+    client.send_privmsg(channel, message);
+
+    // Send message to display since this IRC client is the one posting the message
+    // it won't display if its not sent directly to the TUI client for rendering
+    client.display_msg(channel, message);
+}
+
+/// Answers a chat message as a threaded reply, messages without an id get a regular message instead
+fn reply_to_message(trigger: &ChatMessage, message: &str, client: &mut impl TwitchIrcClient) {
+    if trigger.id.is_empty() {
+        send_to_chat(&trigger.channel, message, client);
+
+        return;
+    }
+
+    client.send_reply(&trigger.channel, &trigger.id, message);
+    client.display_msg(&trigger.channel, message);
+}
+
+/// Answers a chat message in chat, or as a threaded reply for commands with the reply option
+fn answer(trigger: &ChatMessage, message: &str, reply: bool, client: &mut impl TwitchIrcClient) {
+    if reply {
+        reply_to_message(trigger, message, client);
+    } else {
+        send_to_chat(&trigger.channel, message, client);
+    }
+}
+
+/// Answers a chat message privately, messages without a user id get a threaded reply instead
+fn whisper_to_chatter(trigger: &ChatMessage, message: &str, client: &mut impl TwitchIrcClient) {
+    if trigger.user_id.is_empty() {
+        reply_to_message(trigger, message, client);
+    } else {
+        client.send_whisper(&trigger.user_id, &trigger.nickname, message);
+    }
+}
+
+/// Whether the chatter can use a command or action, followers are looked up with the Helix API
+fn is_permitted(permissions: &Permissions, trigger: &ChatMessage, client: &impl TwitchIrcClient) -> bool {
+    permissions.is_allowed(trigger, || {
+        let channel = trigger.channel.trim_start_matches('#');
+
+        HelixLookup::new(client.get_api_credentials())
+            .followage(channel, &trigger.user_id)
+            .is_some()
+    })
+}
+
+/// Records a use of a command or action, false while it's cooling down. Chatters get the
+/// cooldown's reply the first time they're turned down
+fn take_cooldown(
+    key: &str,
+    cooldown: &Cooldown,
+    reply: bool,
+    trigger: &ChatMessage,
+    client: &mut impl TwitchIrcClient,
+) -> bool {
+    let user = if trigger.user_id.is_empty() {
+        &trigger.nickname
+    } else {
+        &trigger.user_id
+    };
+
+    match use_cooldown(key, user, cooldown) {
+        Ok(()) => true,
+        Err(on_cooldown) => {
+            if on_cooldown.notify && !cooldown.reply.is_empty() {
+                let message = cooldown
+                    .reply
+                    .replace("{remaining}", &format_duration(on_cooldown.remaining));
+
+                answer(trigger, &message, reply, client);
+            }
+
+            false
+        }
+    }
+}
+
+/// Whether a command got as many arguments as it takes, chatters that got it wrong are sent its usage
+fn has_arguments(
+    arguments: &Arguments,
+    name: &str,
+    args: &[&str],
+    reply: bool,
+    trigger: &ChatMessage,
+    client: &mut impl TwitchIrcClient,
+) -> bool {
+    if arguments.accepts(args.len()) {
+        return true;
+    }
+
+    if let Some(usage) = arguments.get_usage_hint(&get_command_matcher().get_trigger(name)) {
+        answer(trigger, &usage, reply, client);
+    }
+
+    false
+}
+
+pub fn check_for_chat_commands(trigger: &ChatMessage, client: &mut impl TwitchIrcClient) {
+    let matcher = get_command_matcher();
+    let Some((command, args)) = matcher.parse(&trigger.message) else {
+        return;
+    };
+
+    let Some(store) = load_store() else {
+        return;
+    };
+
+    if matcher.is_match(command, "commands") {
+        let options = &store.command_list;
+        if !is_permitted(&options.permissions, trigger, client)
+            || !take_cooldown(
+                "chat_commands/commands",
+                &options.cooldown,
+                options.reply,
+                trigger,
+                client,
+            )
+        {
+            return;
+        }
+
+        // Only the names commands were added as are listed, not their aliases
+        let available_commands = store
+            .chat_commands
+            .keys()
+            .map(|item| matcher.get_trigger(item))
             .collect::<Vec<String>>()
             .join(", ");
 
         let message = format!("Available commands: {available_commands}");
-        client.send_privmsg(message.as_str());
-
-        // Send message to display since this IRC client is the one posting the message
-        // it won't display if its not sent directly to the TUI client for rendering
-        client.display_msg(&message);
+        answer(trigger, &message, options.reply, client);
 
         return;
     }
 
-    if let Ok(list) = &commands_list {
-        for item in list {
-            let command = format!("!{}", item);
-            if message == command {
-                let _ = output_chat_command(item, client);
-            }
+    if let Some((name, chat_command)) = store.find_chat_command(|name| matcher.is_match(command, name)) {
+        // Aliases share the cooldowns and {count} of the command they point at
+        let key = format!("chat_commands/{name}");
+        let options = &chat_command.options;
+        if is_permitted(&options.permissions, trigger, client)
+            && has_arguments(&options.arguments, name, &args, options.reply, trigger, client)
+            && take_cooldown(&key, &options.cooldown, options.reply, trigger, client)
+        {
+            output_chat_command(name, chat_command, trigger, &args, client);
         }
+
+        return;
     }
+
+    check_for_counter_change(command, &store, trigger, client);
 }
 
-fn output_chat_command(command: &str, client: &mut impl TwitchIrcClient) -> Result<(), Box<dyn Error>> {
-    let mut data_dir = get_data_directory(Some("chat_commands"))?;
-    data_dir.push(command);
+/// Changes a counter for !deaths+ !deaths- or !deaths=5, only moderators and the broadcaster can
+fn check_for_counter_change(command: &str, store: &Store, trigger: &ChatMessage, client: &mut impl TwitchIrcClient) {
+    let Some((name, change)) = CounterChange::parse(command) else {
+        return;
+    };
 
-    let message = fs::read_to_string(data_dir)?;
+    let matcher = get_command_matcher();
+    let Some((name, chat_command)) = store.find_chat_command(|item| matcher.is_match(name, item)) else {
+        return;
+    };
 
-    client.send_privmsg(&message);
+    if !chat_command.options.counter || Role::of(trigger) < Role::Moderator {
+        return;
+    }
 
-    // Send message to display since this IRC client is the one posting the message
-    // it won't display if its not sent directly to the TUI client for rendering
-    client.display_msg(&message);
+    let chat_command = match change_command_count(name, change) {
+        Ok(chat_command) => chat_command,
+        Err(error) => {
+            send_to_error_log(error.to_string(), format!("Could not change the counter {name}"));
 
-    Ok(())
+            return;
+        }
+    };
+
+    send_counter(name, chat_command.count);
+    output_chat_command(name, &chat_command, trigger, &[], client);
+}
+
+fn output_chat_command(
+    name: &str,
+    chat_command: &ChatCommand,
+    trigger: &ChatMessage,
+    args: &[&str],
+    client: &mut impl TwitchIrcClient,
+) {
+    let context = TemplateContext::from_message(name, trigger, args.to_vec());
+    let options = &chat_command.options;
+
+    let mut lookup = HelixLookup::new(client.get_api_credentials());
+    if options.counter {
+        lookup = lookup.with_count(chat_command.count);
+    }
+
+    let message = render_template(&chat_command.message, &context, &mut lookup);
+
+    if options.whisper {
+        whisper_to_chatter(trigger, &message, client);
+    } else {
+        answer(trigger, &message, options.reply, client);
+    }
 }
 
 fn print_message(message: &ChatMessage, client: &mut TwitchIRC) {
@@ -629,5 +1113,20 @@ fn print_message(message: &ChatMessage, client: &mut TwitchIRC) {
 
     println!("{final_message}");
 
-    check_for_chat_commands(&message.message, client);
+    check_for_chat_commands(message, client);
+}
+
+#[test]
+fn test_get_bits_action() {
+    let bits_actions = BTreeMap::from([
+        ("100".to_string(), "sound:small".to_string()),
+        ("1000".to_string(), "sound:large".to_string()),
+        ("500".to_string(), "sound:medium".to_string()),
+        ("lots".to_string(), "sound:broken".to_string()),
+    ]);
+
+    assert_eq!(get_bits_action(&bits_actions, 99), None);
+    assert_eq!(get_bits_action(&bits_actions, 100), Some(&"sound:small".to_string()));
+    assert_eq!(get_bits_action(&bits_actions, 999), Some(&"sound:medium".to_string()));
+    assert_eq!(get_bits_action(&bits_actions, 5000), Some(&"sound:large".to_string()));
 }
@@ -20,6 +20,8 @@ pub fn get_priv_message() -> TwitchMessage {
             returning_chatter: false,
             subscriber: false,
             moderator: false,
+            vip: false,
+            broadcaster: false,
             channel: "some_channel".to_string(),
             raw: "raw message".to_string(),
             area: None,
@@ -30,6 +32,10 @@ pub fn get_priv_message() -> TwitchMessage {
             b: 0,
             direction: 0,
             timestamp: None,
+            reply_parent: None,
+            bits: None,
+            user_id: "".to_string(),
+            login: "some_person".to_string(),
         },
     }
 }
@@ -39,6 +45,7 @@ pub fn get_raid_message() -> ChannelMessages {
         // display_name: "some_person".to_string(),
         // user_id: "1234".to_string(),
         // raid_notice: "1 raiders from some_person have joined!".to_string(),
+        channel: "#s9tpepper_".to_string(),
         display_name: "MatisseTec".to_string(),
         user_id: "468106723".to_string(),
         raid_notice: unescape(r"37\\sraiders\\sfrom\\sMatisseTec\\shave\\sjoined!"),
@@ -1,23 +1,17 @@
-use crate::twitch::irc::TwitchIrcClient;
-
 use std::{
     error::Error,
-    fs,
-    sync::mpsc::Sender,
+    sync::{mpsc::Sender, Arc, Mutex, PoisonError},
     thread::sleep,
-    time::{Duration, SystemTime},
+    time::{Duration, Instant, SystemTime},
 };
 
-use clap::builder::OsStr;
 use ratatui::layout::Rect;
 use serde::{Deserialize, Serialize};
 
-use crate::utils::get_data_directory;
+use crate::store::{read_store, Store};
 
 use super::{
-    irc::{TwitchIRC, MESSAGE_DELIMITER},
-    parse::{parse, TwitchMessage},
-    ChannelMessages,
+    irc::get_sent_message, parse::TwitchMessage, pubsub::send_to_error_log, ratelimit::SendQueue, ChannelMessages,
 };
 
 #[derive(Debug, Deserialize, Serialize)]
@@ -43,43 +37,30 @@ impl Clone for Announcement {
 }
 
 pub fn get_announcements() -> Result<Vec<Announcement>, Box<dyn Error>> {
-    let announcements_dir = get_data_directory(Some("chat_announcements"))?;
-
-    let mut announcements = vec![];
-    let dir_entries = fs::read_dir(announcements_dir)?;
-    for entry in dir_entries {
-        let entry = entry?;
-        let path = entry.path();
-        if path.is_file() {
-            let default_file_name = &OsStr::from("unknown_name");
-            let name = path.file_name().unwrap_or(default_file_name);
-
-            let file_contents = fs::read_to_string(&path)?;
-            if let Some((timing, message)) = file_contents.split_once('\n') {
-                let timing = Duration::from_secs(timing.parse::<u64>()? * 60);
-                let start = SystemTime::now();
-                let message = message.to_string();
-                let area = None;
-                let announcement = Announcement {
-                    name: String::from(name.to_str().unwrap_or("unknown_name")),
-                    timing,
-                    message,
-                    start,
-                    area,
-                };
-
-                announcements.push(announcement);
-            }
-        }
-    }
+    Ok(get_announcements_from(&read_store()?))
+}
 
-    Ok(announcements)
+/// The announcements in a store, each one starts waiting for its timing now
+pub fn get_announcements_from(store: &Store) -> Vec<Announcement> {
+    store
+        .chat_announcements
+        .iter()
+        .map(|(name, announcement)| Announcement {
+            name: name.clone(),
+            timing: Duration::from_secs(announcement.timing * 60),
+            message: announcement.message.clone(),
+            start: SystemTime::now(),
+            area: None,
+        })
+        .collect()
 }
 
+/// Queues the announcements that are due every 30s. send_queue is the chat connection's, which sends
+/// them so they count toward its rate limit
 pub fn start_announcements(
     twitch_name: &str,
-    oauth_token: &str,
-    client_id: &str,
+    bot_name: Option<String>,
+    send_queue: Arc<Mutex<SendQueue>>,
     tx: Sender<ChannelMessages>,
     skip_announcements: bool,
 ) -> Result<(), Box<dyn Error>> {
@@ -88,31 +69,9 @@ pub fn start_announcements(
     }
 
     let mut announcements = get_announcements()?;
-    let mut twitch_irc = TwitchIRC::new(twitch_name, oauth_token, client_id, tx.clone());
+    let channel = format!("#{}", twitch_name.to_lowercase());
 
     loop {
-        if let Ok(message) = twitch_irc.socket.read() {
-            let messages = message.to_text().unwrap().split(MESSAGE_DELIMITER);
-            let messages = messages.map(tungstenite::Message::from);
-
-            messages.for_each(|message| {
-                if let tungstenite::Message::Text(new_message) = message {
-                    match parse(&new_message, &mut twitch_irc) {
-                        Ok(TwitchMessage::PingMessage { message }) => {
-                            let pong_message = format!("PONG {message}");
-
-                            let _ = twitch_irc.socket.send(pong_message.into());
-                        }
-
-                        // NOTE: Dont care about other messages for announcements
-                        Ok(_) => {}
-
-                        Err(_) => {}
-                    }
-                }
-            })
-        }
-
         let new_announcements = get_announcements()?;
         if announcements.len() != new_announcements.len() {
             announcements = new_announcements;
@@ -124,7 +83,24 @@ pub fn start_announcements(
             if time_to_announce {
                 announcement.start = SystemTime::now();
 
-                twitch_irc.send_privmsg(&announcement.message);
+                let queued = send_queue.lock().unwrap_or_else(PoisonError::into_inner).push(
+                    &channel,
+                    &announcement.message,
+                    None,
+                    Instant::now(),
+                );
+
+                if let Err(dropped) = queued {
+                    send_to_error_log(dropped.message, "Send queue is full, announcement not sent".into());
+
+                    continue;
+                }
+
+                // Messages from a bot account come back through chat like anyone else's
+                if bot_name.is_none() {
+                    let message = get_sent_message(twitch_name, &channel, &announcement.message);
+                    let _ = tx.send(ChannelMessages::TwitchMessage(TwitchMessage::PrivMessage { message }));
+                }
             };
         }
 
@@ -142,15 +118,30 @@ fn check_announcement(announcement: &Announcement) -> bool {
 
 #[test]
 fn test_get_announcements() {
-    let announcements = get_announcements();
-    if let Ok(list) = announcements {
-        dbg!(&list);
-
-        assert!(list.len() == 1);
-        return;
-    }
-
-    panic!("Couldn't get announcements");
+    use crate::store::{read_store_in, update_store_in, ChatAnnouncement};
+
+    let data_dir = std::env::temp_dir().join(format!("sergeant_announcements_{}", std::process::id()));
+    std::fs::create_dir_all(&data_dir).unwrap();
+
+    let updated = update_store_in(&data_dir, |store| {
+        let announcement = ChatAnnouncement {
+            timing: 5,
+            message: String::from("Follow the channel"),
+        };
+        store.chat_announcements.insert(String::from("spam"), announcement);
+
+        Ok(())
+    });
+    let store = read_store_in(&data_dir);
+    std::fs::remove_dir_all(&data_dir).unwrap();
+
+    assert!(updated.is_ok());
+    let announcements = get_announcements_from(&store.unwrap());
+
+    assert_eq!(announcements.len(), 1);
+    assert_eq!(announcements[0].name, "spam");
+    assert_eq!(announcements[0].timing, Duration::from_secs(5 * 60));
+    assert_eq!(announcements[0].message, "Follow the channel");
 }
 
 #[test]
@@ -2,7 +2,7 @@ use std::error::Error;
 
 use serde::{Deserialize, Serialize};
 
-use crate::commands::{store_token, TokenStatus};
+use crate::commands::{store_bot_token, store_token, TokenStatus};
 
 use super::pubsub::{send_to_error_log, Credentials};
 
@@ -70,22 +70,67 @@ pub fn get_user(oauth_token: &str, client_id: &str) -> Result<User, Box<dyn Erro
     Ok(user)
 }
 
+/// Sends a whisper with the Helix API, the sender is the user the token belongs to.
+/// Twitch only lets accounts with a verified phone number whisper
+pub fn send_whisper(
+    oauth_token: &str,
+    client_id: &str,
+    from_user_id: &str,
+    to_user_id: &str,
+    message: &str,
+) -> Result<(), Box<dyn Error>> {
+    let whispers_url = "https://api.twitch.tv/helix/whispers";
+    let response = ureq::post(whispers_url)
+        .set(
+            "Authorization",
+            &format!("Bearer {}", oauth_token.replace("oauth:", "")),
+        )
+        .set("Client-Id", client_id)
+        .query_pairs(vec![("from_user_id", from_user_id), ("to_user_id", to_user_id)])
+        .send_json(ureq::json!({ "message": message }));
+
+    if let Err(error) = response {
+        return Err(format!("Failed to send whisper: {error}").into());
+    }
+
+    Ok(())
+}
+
 pub fn validate(oauth_token: &str) -> Result<(), Box<dyn Error>> {
     let url = "https://id.twitch.tv/oauth2/validate";
     let token = oauth_token.replace("oauth:", "");
     let response = ureq::get(url).set("Authorization", &format!("OAuth {}", token)).call();
 
-    let is_ok = response.is_ok();
-    let status = response.unwrap().status();
-
-    if is_ok && status == 200 {
-        Ok(())
-    } else {
-        Err(format!("Failed to validate token: {status}").into())
+    // Expired tokens get a 401, which ureq returns as an error instead of a response
+    match response {
+        Ok(response) if response.status() == 200 => Ok(()),
+        Ok(response) => Err(format!("Failed to validate token: {}", response.status()).into()),
+        Err(ureq::Error::Status(status, _)) => Err(format!("Failed to validate token: {status}").into()),
+        Err(error) => Err(error.into()),
     }
 }
 
 pub fn refresh_token(refresh_token: &str) -> Result<TokenStatus, Box<dyn Error>> {
+    let token_status = request_token_refresh(refresh_token)?;
+    if token_status.success {
+        // TODO: Fix this so it doesnt need a clone
+        store_token(token_status.clone())?;
+    }
+
+    Ok(token_status)
+}
+
+/// Same as refresh_token, for the token stored by login --bot
+pub fn refresh_bot_token(refresh_token: &str) -> Result<TokenStatus, Box<dyn Error>> {
+    let token_status = request_token_refresh(refresh_token)?;
+    if token_status.success {
+        store_bot_token(token_status.clone())?;
+    }
+
+    Ok(token_status)
+}
+
+fn request_token_refresh(refresh_token: &str) -> Result<TokenStatus, Box<dyn Error>> {
     let url = format!("https://twitchtokengenerator.com/api/refresh/{refresh_token}");
     let response = ureq::get(&url).call();
 
@@ -93,11 +138,5 @@ pub fn refresh_token(refresh_token: &str) -> Result<TokenStatus, Box<dyn Error>>
         return Err("Token refresh has failed.".into());
     }
 
-    let token_status = serde_json::from_str::<TokenStatus>(&response?.into_string()?)?;
-    if token_status.success {
-        // TODO: Fix this so it doesnt need a clone
-        store_token(token_status.clone())?;
-    }
-
-    Ok(token_status)
+    Ok(serde_json::from_str::<TokenStatus>(&response?.into_string()?)?)
 }
@@ -0,0 +1,499 @@
+use std::{
+    collections::HashMap,
+    error::Error,
+    fs::{self, File},
+    io::Read,
+    path::{Path, PathBuf},
+    process,
+    sync::{Mutex, OnceLock},
+    time::{Duration, SystemTime, UNIX_EPOCH},
+};
+
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+
+use crate::utils::get_data_directory;
+
+use super::pubsub::send_to_error_log;
+
+/// Least recently used images are evicted once the cache grows past this size
+pub const CACHE_MAX_SIZE: u64 = 100 * 1024 * 1024;
+
+/// Cached images older than this are revalidated with the server before they are used
+pub const CACHE_TTL: Duration = Duration::from_secs(7 * 24 * 60 * 60);
+
+const CACHE_DIRECTORY: &str = "asset_cache";
+const INDEX_FILE: &str = "index.json";
+const LOCK_FILE: &str = "index.json.lock";
+const OBJECTS_DIRECTORY: &str = "objects";
+
+static ASSET_CACHE: OnceLock<Mutex<AssetCache>> = OnceLock::new();
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+struct CacheEntry {
+    /// The sha256 of the image, several urls can point at the same image
+    hash: String,
+    size: u64,
+    fetched_at: u64,
+    last_used: u64,
+    etag: Option<String>,
+    last_modified: Option<String>,
+}
+
+#[derive(Default, Debug, Serialize, Deserialize)]
+struct CacheIndex {
+    entries: HashMap<String, CacheEntry>,
+}
+
+#[derive(Debug)]
+pub struct CachedAsset {
+    pub bytes: Vec<u8>,
+    pub is_stale: bool,
+    pub etag: Option<String>,
+    pub last_modified: Option<String>,
+}
+
+#[derive(Debug, PartialEq)]
+pub struct CacheStats {
+    pub entries: usize,
+    pub objects: usize,
+    pub size: u64,
+    pub max_size: u64,
+}
+
+/// Content addressed cache for emote and badge images, stored in the data directory
+#[derive(Debug)]
+pub struct AssetCache {
+    directory: PathBuf,
+    index: CacheIndex,
+    max_size: u64,
+    ttl: Duration,
+}
+
+impl AssetCache {
+    pub fn open(directory: PathBuf, max_size: u64, ttl: Duration) -> Result<Self, Box<dyn Error>> {
+        fs::create_dir_all(directory.join(OBJECTS_DIRECTORY))?;
+
+        let index = read_index(&directory);
+
+        Ok(AssetCache {
+            directory,
+            index,
+            max_size,
+            ttl,
+        })
+    }
+
+    fn get_object_path(&self, hash: &str) -> PathBuf {
+        self.directory.join(OBJECTS_DIRECTORY).join(hash)
+    }
+
+    /// Chat, the admin and the cli each have their own cache, the lock file keeps one from saving
+    /// over images another stored since it opened the index
+    fn lock_index(&self) -> Result<File, Box<dyn Error>> {
+        let lock_file = File::options()
+            .create(true)
+            .truncate(false)
+            .write(true)
+            .open(self.directory.join(LOCK_FILE))?;
+        lock_file.lock()?;
+
+        Ok(lock_file)
+    }
+
+    /// Adds what other processes saved to the index since it was read, then evicts and saves it
+    fn save_index(&mut self) -> Result<(), Box<dyn Error>> {
+        let _lock_file = self.lock_index()?;
+
+        for (url, saved) in read_index(&self.directory).entries {
+            let is_on_disk = self.get_object_path(&saved.hash).exists();
+
+            match self.index.entries.get_mut(&url) {
+                Some(entry) if saved.fetched_at > entry.fetched_at => {
+                    let last_used = entry.last_used.max(saved.last_used);
+                    *entry = CacheEntry { last_used, ..saved };
+                }
+
+                Some(entry) => entry.last_used = entry.last_used.max(saved.last_used),
+
+                // Entries whose image was evicted are left out
+                None if is_on_disk => {
+                    self.index.entries.insert(url, saved);
+                }
+
+                None => {}
+            }
+        }
+
+        self.evict()?;
+        self.write_index()
+    }
+
+    fn write_index(&self) -> Result<(), Box<dyn Error>> {
+        // Each process writes its own temporary file so a crash can't leave half an index behind
+        let temp_path = self.directory.join(format!("{INDEX_FILE}.{}.tmp", process::id()));
+        fs::write(&temp_path, serde_json::to_string(&self.index)?)?;
+        fs::rename(temp_path, self.directory.join(INDEX_FILE))?;
+
+        Ok(())
+    }
+
+    /// Returns the cached image for a url, stale images still need to be revalidated
+    pub fn lookup(&mut self, url: &str, now: u64) -> Option<CachedAsset> {
+        let entry = self.index.entries.get(url)?.clone();
+
+        let Ok(bytes) = fs::read(self.get_object_path(&entry.hash)) else {
+            // The image was removed from disk, forget about it
+            self.index.entries.remove(url);
+            return None;
+        };
+
+        // Recency is kept in memory and saved with the next change to the index
+        if let Some(entry) = self.index.entries.get_mut(url) {
+            entry.last_used = now;
+        }
+
+        Some(CachedAsset {
+            bytes,
+            is_stale: now.saturating_sub(entry.fetched_at) > self.ttl.as_secs(),
+            etag: entry.etag,
+            last_modified: entry.last_modified,
+        })
+    }
+
+    pub fn store(
+        &mut self,
+        url: &str,
+        bytes: &[u8],
+        etag: Option<String>,
+        last_modified: Option<String>,
+        now: u64,
+    ) -> Result<(), Box<dyn Error>> {
+        let hash = format!("{:x}", Sha256::digest(bytes));
+
+        let object_path = self.get_object_path(&hash);
+        if !object_path.exists() {
+            fs::write(object_path, bytes)?;
+        }
+
+        let previous = self.index.entries.insert(
+            url.to_string(),
+            CacheEntry {
+                hash,
+                size: bytes.len() as u64,
+                fetched_at: now,
+                last_used: now,
+                etag,
+                last_modified,
+            },
+        );
+
+        if let Some(previous) = previous {
+            self.remove_unused_object(&previous.hash)?;
+        }
+
+        self.save_index()
+    }
+
+    /// The server said the cached image is still current
+    pub fn revalidate(&mut self, url: &str, now: u64) -> Result<(), Box<dyn Error>> {
+        if let Some(entry) = self.index.entries.get_mut(url) {
+            entry.fetched_at = now;
+            entry.last_used = now;
+        }
+
+        self.save_index()
+    }
+
+    fn remove_unused_object(&self, hash: &str) -> Result<(), Box<dyn Error>> {
+        let is_used = self.index.entries.values().any(|entry| entry.hash == hash);
+        let object_path = self.get_object_path(hash);
+        if !is_used && object_path.exists() {
+            fs::remove_file(object_path)?;
+        }
+
+        Ok(())
+    }
+
+    // Each image is only stored once, no matter how many urls point at it
+    fn get_objects(&self) -> HashMap<&str, (u64, u64)> {
+        let mut objects: HashMap<&str, (u64, u64)> = HashMap::new();
+        for entry in self.index.entries.values() {
+            let object = objects.entry(entry.hash.as_str()).or_insert((entry.size, 0));
+            object.1 = object.1.max(entry.last_used);
+        }
+
+        objects
+    }
+
+    fn evict(&mut self) -> Result<(), Box<dyn Error>> {
+        let mut objects: Vec<(String, u64, u64)> = self
+            .get_objects()
+            .into_iter()
+            .map(|(hash, (size, last_used))| (hash.to_string(), size, last_used))
+            .collect();
+
+        let mut size: u64 = objects.iter().map(|(_, size, _)| size).sum();
+        if size <= self.max_size {
+            return Ok(());
+        }
+
+        objects.sort_by_key(|(_, _, last_used)| *last_used);
+
+        for (hash, object_size, _) in objects {
+            if size <= self.max_size {
+                break;
+            }
+
+            self.index.entries.retain(|_, entry| entry.hash != hash);
+            self.remove_unused_object(&hash)?;
+            size -= object_size;
+        }
+
+        Ok(())
+    }
+
+    pub fn stats(&self) -> CacheStats {
+        let objects = self.get_objects();
+
+        CacheStats {
+            entries: self.index.entries.len(),
+            objects: objects.len(),
+            size: objects.values().map(|(size, _)| size).sum(),
+            max_size: self.max_size,
+        }
+    }
+
+    pub fn clear(&mut self) -> Result<(), Box<dyn Error>> {
+        let _lock_file = self.lock_index()?;
+        self.index = CacheIndex::default();
+
+        let objects_directory = self.directory.join(OBJECTS_DIRECTORY);
+        if objects_directory.exists() {
+            fs::remove_dir_all(&objects_directory)?;
+        }
+        fs::create_dir_all(objects_directory)?;
+
+        self.write_index()
+    }
+}
+
+fn read_index(directory: &Path) -> CacheIndex {
+    match fs::read_to_string(directory.join(INDEX_FILE)) {
+        Ok(contents) => serde_json::from_str(&contents).unwrap_or_else(|error| {
+            send_to_error_log(error.to_string(), "Asset cache index is corrupt, starting over".into());
+            CacheIndex::default()
+        }),
+        Err(_) => CacheIndex::default(),
+    }
+}
+
+fn get_timestamp() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|duration| duration.as_secs())
+        .unwrap_or_default()
+}
+
+pub fn get_asset_cache() -> Result<&'static Mutex<AssetCache>, Box<dyn Error>> {
+    if let Some(asset_cache) = ASSET_CACHE.get() {
+        return Ok(asset_cache);
+    }
+
+    let asset_cache = AssetCache::open(get_data_directory(Some(CACHE_DIRECTORY))?, CACHE_MAX_SIZE, CACHE_TTL)?;
+
+    Ok(ASSET_CACHE.get_or_init(|| Mutex::new(asset_cache)))
+}
+
+/// Downloads an image through the asset cache, stale images are revalidated with
+/// If-None-Match/If-Modified-Since and still used when the server can't be reached
+pub fn fetch_asset(url: &str) -> Result<Vec<u8>, Box<dyn Error>> {
+    let asset_cache = get_asset_cache()?;
+    let now = get_timestamp();
+
+    // The lock isn't held during the download so other lookups don't have to wait
+    let cached = asset_cache.lock().unwrap().lookup(url, now);
+    if let Some(cached) = &cached {
+        if !cached.is_stale {
+            return Ok(cached.bytes.clone());
+        }
+    }
+
+    let mut request = ureq::get(url);
+    if let Some(cached) = &cached {
+        if let Some(etag) = &cached.etag {
+            request = request.set("If-None-Match", etag);
+        }
+
+        if let Some(last_modified) = &cached.last_modified {
+            request = request.set("If-Modified-Since", last_modified);
+        }
+    }
+
+    let response = match request.call() {
+        Ok(response) => response,
+        Err(error) => {
+            return match cached {
+                Some(cached) => Ok(cached.bytes),
+                None => Err(error.into()),
+            };
+        }
+    };
+
+    if response.status() == 304 {
+        if let Some(cached) = cached {
+            asset_cache.lock().unwrap().revalidate(url, now)?;
+
+            return Ok(cached.bytes);
+        }
+    }
+
+    let etag = response.header("etag").map(|etag| etag.to_string());
+    let last_modified = response.header("last-modified").map(|date| date.to_string());
+
+    let mut bytes: Vec<u8> = vec![];
+    response.into_reader().read_to_end(&mut bytes)?;
+
+    asset_cache
+        .lock()
+        .unwrap()
+        .store(url, &bytes, etag, last_modified, now)?;
+
+    Ok(bytes)
+}
+
+pub fn print_cache_stats() {
+    let Ok(asset_cache) = get_asset_cache() else {
+        println!("Could not open the asset cache.");
+        return;
+    };
+
+    let stats = asset_cache.lock().unwrap().stats();
+    let megabytes = |bytes: u64| bytes as f64 / (1024.0 * 1024.0);
+
+    println!("Cached urls: {}", stats.entries);
+    println!("Cached images: {}", stats.objects);
+    println!(
+        "Size: {:.1} MB of {:.1} MB",
+        megabytes(stats.size),
+        megabytes(stats.max_size)
+    );
+}
+
+pub fn clear_cache() -> Result<(), Box<dyn Error>> {
+    // Badges used to be saved one base64 file per badge, they are cached like emotes now
+    let legacy_badges = get_data_directory(None)?.join("badges");
+    if legacy_badges.exists() {
+        fs::remove_dir_all(legacy_badges)?;
+    }
+
+    get_asset_cache()?.lock().unwrap().clear()
+}
+
+#[cfg(test)]
+fn get_test_directory(name: &str) -> PathBuf {
+    let directory = std::env::temp_dir().join(format!("sergeant_asset_cache_{name}_{}", std::process::id()));
+    let _ = fs::remove_dir_all(&directory);
+
+    directory
+}
+
+#[test]
+fn test_asset_cache_store_and_lookup() {
+    let directory = get_test_directory("store");
+    let ttl = Duration::from_secs(60);
+    let mut cache = AssetCache::open(directory.clone(), 1024, ttl).unwrap();
+
+    cache
+        .store("https://example.com/kappa.png", b"kappa", None, None, 1000)
+        .unwrap();
+    cache
+        .store(
+            "https://example.com/kappa-copy.png",
+            b"kappa",
+            Some("\"abc\"".into()),
+            None,
+            1000,
+        )
+        .unwrap();
+
+    // Both urls share one image on disk
+    assert_eq!(
+        cache.stats(),
+        CacheStats {
+            entries: 2,
+            objects: 1,
+            size: 5,
+            max_size: 1024
+        }
+    );
+
+    let cached = cache.lookup("https://example.com/kappa-copy.png", 1030).unwrap();
+    assert_eq!(cached.bytes, b"kappa");
+    assert!(!cached.is_stale);
+    assert_eq!(cached.etag, Some("\"abc\"".to_string()));
+
+    assert!(cache.lookup("https://example.com/kappa.png", 1061).unwrap().is_stale);
+    cache.revalidate("https://example.com/kappa.png", 1061).unwrap();
+    assert!(!cache.lookup("https://example.com/kappa.png", 1062).unwrap().is_stale);
+
+    // The index survives a restart
+    let mut cache = AssetCache::open(directory.clone(), 1024, ttl).unwrap();
+    assert_eq!(
+        cache.lookup("https://example.com/kappa.png", 1062).unwrap().bytes,
+        b"kappa"
+    );
+
+    cache.clear().unwrap();
+    assert_eq!(cache.stats().entries, 0);
+    assert!(cache.lookup("https://example.com/kappa.png", 1062).is_none());
+
+    let _ = fs::remove_dir_all(directory);
+}
+
+#[test]
+fn test_asset_cache_evicts_least_recently_used() {
+    let directory = get_test_directory("evict");
+    let mut cache = AssetCache::open(directory.clone(), 10, Duration::from_secs(60)).unwrap();
+
+    cache
+        .store("https://example.com/a.png", b"aaaa", None, None, 1)
+        .unwrap();
+    cache
+        .store("https://example.com/b.png", b"bbbb", None, None, 2)
+        .unwrap();
+
+    // Using a makes b the least recently used image
+    assert!(cache.lookup("https://example.com/a.png", 3).is_some());
+    cache
+        .store("https://example.com/c.png", b"cccc", None, None, 4)
+        .unwrap();
+
+    assert!(cache.lookup("https://example.com/a.png", 5).is_some());
+    assert!(cache.lookup("https://example.com/b.png", 5).is_none());
+    assert!(cache.lookup("https://example.com/c.png", 5).is_some());
+    assert_eq!(cache.stats().size, 8);
+
+    let _ = fs::remove_dir_all(directory);
+}
+
+#[test]
+fn test_asset_cache_keeps_other_processes_images() {
+    let directory = get_test_directory("merge");
+    let ttl = Duration::from_secs(60);
+    let mut chat_cache = AssetCache::open(directory.clone(), 1024, ttl).unwrap();
+    let mut admin_cache = AssetCache::open(directory.clone(), 1024, ttl).unwrap();
+
+    chat_cache
+        .store("https://example.com/kappa.png", b"kappa", None, None, 1)
+        .unwrap();
+    admin_cache
+        .store("https://example.com/pogchamp.png", b"pogchamp", None, None, 2)
+        .unwrap();
+
+    let mut cache = AssetCache::open(directory.clone(), 1024, ttl).unwrap();
+    assert!(cache.lookup("https://example.com/kappa.png", 3).is_some());
+    assert!(cache.lookup("https://example.com/pogchamp.png", 3).is_some());
+
+    let _ = fs::remove_dir_all(directory);
+}
@@ -0,0 +1,196 @@
+use std::{
+    collections::{HashMap, VecDeque},
+    error::Error,
+    sync::{
+        mpsc::{channel, Receiver, Sender, TryRecvError},
+        Arc, Mutex, PoisonError,
+    },
+    thread::{self, sleep},
+    time::Duration,
+};
+
+use super::{
+    irc::{get_backoff, get_jitter_seed},
+    parse::{parse_user_state, UserState},
+    pubsub::send_to_error_log,
+    transport::{connect_transport, IrcRead, IrcTransport},
+};
+
+/// The bot account's USERSTATE in each channel it joined
+type UserStates = Arc<Mutex<HashMap<String, UserState>>>;
+
+// The bot only listens for PINGs and its USERSTATE, short reads keep its messages from waiting on the socket
+const BOT_READ_TIMEOUT: Duration = Duration::from_millis(50);
+
+/// The login of a second account that sends the bot's messages instead of the broadcaster
+#[derive(Clone, Debug, PartialEq)]
+pub struct BotCredentials {
+    pub name: String,
+    pub oauth_token: String,
+    pub client_id: String,
+}
+
+/// Sends chat messages as the bot account on its own connection, the broadcaster's connection
+/// keeps reading chat
+pub struct BotConnection {
+    pub credentials: BotCredentials,
+    lines_tx: Sender<String>,
+    user_states: UserStates,
+}
+
+impl BotConnection {
+    pub fn connect(irc_url: &str, credentials: &BotCredentials, channels: &[String]) -> Self {
+        let (lines_tx, lines_rx) = channel::<String>();
+
+        let irc_url = irc_url.to_string();
+        let bot_credentials = credentials.clone();
+        let channels = channels.to_vec();
+        let user_states = UserStates::default();
+        let bot_user_states = Arc::clone(&user_states);
+        thread::spawn(move || run_bot_connection(&irc_url, &bot_credentials, &channels, lines_rx, &bot_user_states));
+
+        BotConnection {
+            credentials: credentials.clone(),
+            lines_tx,
+            user_states,
+        }
+    }
+
+    /// Whether the bot account moderates a channel, or is its VIP, so it gets the higher rate limit
+    pub fn is_moderator(&self, channel: &str) -> bool {
+        self.user_states
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .get(channel)
+            .is_some_and(|user_state| user_state.is_moderator())
+    }
+
+    /// Queues a raw IRC line, like a PRIVMSG, to be sent by the bot account
+    pub fn send_line(&self, line: &str) -> Result<(), Box<dyn Error>> {
+        self.lines_tx.send(line.to_string())?;
+
+        Ok(())
+    }
+}
+
+fn connect_bot(
+    irc_url: &str,
+    credentials: &BotCredentials,
+    channels: &[String],
+) -> Result<Box<dyn IrcTransport>, Box<dyn Error>> {
+    let mut transport = connect_transport(irc_url, BOT_READ_TIMEOUT)?;
+
+    // Tags are needed to send replies, membership isn't since the bot doesn't show chat
+    transport.send_line("CAP REQ :twitch.tv/tags twitch.tv/commands")?;
+    transport.send_line(&format!("PASS {}", credentials.oauth_token))?;
+    transport.send_line(&format!("NICK {}", credentials.name))?;
+    transport.send_line(&format!("JOIN {}", channels.join(",")))?;
+
+    Ok(transport)
+}
+
+fn connect_bot_with_backoff(irc_url: &str, credentials: &BotCredentials, channels: &[String]) -> Box<dyn IrcTransport> {
+    let mut attempt: u32 = 0;
+
+    loop {
+        match connect_bot(irc_url, credentials, channels) {
+            Ok(transport) => return transport,
+            Err(error) => {
+                send_to_error_log(error.to_string(), "Error during connection of the bot account".into());
+
+                sleep(get_backoff(attempt, get_jitter_seed()));
+                attempt = attempt.saturating_add(1);
+            }
+        }
+    }
+}
+
+fn run_bot_connection(
+    irc_url: &str,
+    credentials: &BotCredentials,
+    channels: &[String],
+    lines_rx: Receiver<String>,
+    user_states: &UserStates,
+) {
+    let mut transport = connect_bot_with_backoff(irc_url, credentials, channels);
+    // Lines waiting to be sent, a failed send stays at the front until the connection is back
+    let mut queue: VecDeque<String> = VecDeque::new();
+
+    loop {
+        let mut disconnected = false;
+        loop {
+            match lines_rx.try_recv() {
+                Ok(line) => queue.push_back(line),
+
+                Err(TryRecvError::Empty) => break,
+
+                // The sender is dropped along with TwitchIRC
+                Err(TryRecvError::Disconnected) => {
+                    disconnected = true;
+
+                    break;
+                }
+            }
+        }
+
+        let mut reconnect = false;
+        while let Some(line) = queue.pop_front() {
+            if let Err(error) = transport.send_line(&line) {
+                send_to_error_log(error.to_string(), format!("Bot account could not send: {line}"));
+
+                queue.push_front(line);
+                reconnect = true;
+
+                break;
+            }
+        }
+
+        if disconnected {
+            transport.close();
+
+            return;
+        }
+
+        if !reconnect {
+            match transport.read_lines() {
+                Ok(IrcRead::Lines(lines)) => {
+                    for line in &lines {
+                        if let Some(server) = line.strip_prefix("PING ") {
+                            let pong = format!("PONG {server}");
+                            if let Err(error) = transport.send_line(&pong) {
+                                send_to_error_log(error.to_string(), "Bot account could not answer PING".into());
+
+                                queue.push_back(pong);
+                                reconnect = true;
+                            }
+                        }
+
+                        if let Some(user_state) = parse_user_state(line) {
+                            let mut user_states = user_states.lock().unwrap_or_else(PoisonError::into_inner);
+                            user_states.insert(user_state.channel.clone(), user_state);
+                        }
+
+                        if line.ends_with(" RECONNECT") {
+                            reconnect = true;
+                        }
+                    }
+                }
+                Ok(IrcRead::Timeout) => {}
+                Ok(IrcRead::Closed) => reconnect = true,
+                Err(error) => {
+                    send_to_error_log(error.to_string(), "Error while reading from the bot connection".into());
+
+                    reconnect = true;
+                }
+            }
+        }
+
+        if reconnect {
+            // Joining again sends a new USERSTATE for each channel
+            user_states.lock().unwrap_or_else(PoisonError::into_inner).clear();
+
+            transport.close();
+            transport = connect_bot_with_backoff(irc_url, credentials, channels);
+        }
+    }
+}
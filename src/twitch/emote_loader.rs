@@ -0,0 +1,181 @@
+use std::{
+    collections::{HashMap, HashSet},
+    error::Error,
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        mpsc::{channel, Receiver, Sender},
+        Arc, Mutex, OnceLock, RwLock,
+    },
+    thread,
+};
+
+use crate::tui::image_protocol::get_image_protocol;
+
+use super::{asset_cache::fetch_asset, pubsub::send_to_error_log};
+
+/// The number of threads downloading and encoding emotes at the same time
+const EMOTE_WORKERS: usize = 4;
+
+static EMOTE_LOADER: OnceLock<EmoteLoader> = OnceLock::new();
+
+/// Downloads an image and encodes it for the terminal, takes the emote id and the url
+pub type LoadEmote = fn(&str, &str) -> Result<String, Box<dyn Error>>;
+
+struct EmoteJob {
+    emote_id: String,
+    url: String,
+}
+
+#[derive(Default)]
+struct LoaderState {
+    encoded: RwLock<HashMap<String, String>>,
+    pending: Mutex<HashSet<String>>,
+    failed: Mutex<HashSet<String>>,
+    repaint: AtomicBool,
+}
+
+/// Loads emotes and badges on a pool of worker threads so drawing chat never waits on the network
+pub struct EmoteLoader {
+    jobs: Mutex<Sender<EmoteJob>>,
+    state: Arc<LoaderState>,
+}
+
+impl EmoteLoader {
+    pub fn new(workers: usize, load_emote: LoadEmote) -> Self {
+        let (jobs, receiver) = channel::<EmoteJob>();
+        let receiver = Arc::new(Mutex::new(receiver));
+        let state = Arc::new(LoaderState::default());
+
+        for _ in 0..workers {
+            let receiver = receiver.clone();
+            let state = state.clone();
+
+            thread::spawn(move || run_worker(receiver, state, load_emote));
+        }
+
+        EmoteLoader {
+            jobs: Mutex::new(jobs),
+            state,
+        }
+    }
+
+    /// The encoded image, None until a worker has finished loading it
+    pub fn get(&self, emote_id: &str) -> Option<String> {
+        self.state.encoded.read().unwrap().get(emote_id).cloned()
+    }
+
+    /// Queues an emote to be loaded, emotes that are loaded, loading or failed are skipped
+    pub fn request(&self, emote_id: &str, url: &str) {
+        // Workers store the result before they clear the pending id, so holding the pending lock
+        // while checking means a finished emote is never queued twice
+        let mut pending = self.state.pending.lock().unwrap();
+        if pending.contains(emote_id)
+            || self.state.encoded.read().unwrap().contains_key(emote_id)
+            || self.state.failed.lock().unwrap().contains(emote_id)
+        {
+            return;
+        }
+        pending.insert(emote_id.to_string());
+        drop(pending);
+
+        let job = EmoteJob {
+            emote_id: emote_id.to_string(),
+            url: url.to_string(),
+        };
+
+        if let Err(error) = self.jobs.lock().unwrap().send(job) {
+            send_to_error_log(format!("Could not queue emote {emote_id}"), error.to_string());
+            self.state.pending.lock().unwrap().remove(emote_id);
+        }
+    }
+
+    /// True once after any emote finished loading, chat has to be drawn again to show it
+    pub fn take_repaint(&self) -> bool {
+        self.state.repaint.swap(false, Ordering::AcqRel)
+    }
+}
+
+fn run_worker(receiver: Arc<Mutex<Receiver<EmoteJob>>>, state: Arc<LoaderState>, load_emote: LoadEmote) {
+    loop {
+        // The lock is only held while waiting for a job, not while loading it
+        let job = receiver.lock().unwrap().recv();
+        let Ok(job) = job else {
+            return;
+        };
+
+        match load_emote(&job.emote_id, &job.url) {
+            Ok(encoded) => {
+                state.encoded.write().unwrap().insert(job.emote_id.clone(), encoded);
+                state.repaint.store(true, Ordering::Release);
+            }
+
+            // Failed emotes stay as text, they aren't requested again until sergeant restarts
+            Err(error) => {
+                send_to_error_log(
+                    format!("Could not load emote {} from {}", job.emote_id, job.url),
+                    error.to_string(),
+                );
+                state.failed.lock().unwrap().insert(job.emote_id.clone());
+            }
+        }
+
+        state.pending.lock().unwrap().remove(&job.emote_id);
+    }
+}
+
+fn load_emote(emote_id: &str, url: &str) -> Result<String, Box<dyn Error>> {
+    let image_bytes = fetch_asset(url)?;
+
+    get_image_protocol().encode(&image_bytes, emote_id)
+}
+
+pub fn get_emote_loader() -> &'static EmoteLoader {
+    EMOTE_LOADER.get_or_init(|| EmoteLoader::new(EMOTE_WORKERS, load_emote))
+}
+
+#[test]
+fn test_emote_loader() {
+    use std::{
+        sync::atomic::AtomicUsize,
+        time::{Duration, Instant},
+    };
+
+    static LOADS: AtomicUsize = AtomicUsize::new(0);
+
+    fn test_load(emote_id: &str, url: &str) -> Result<String, Box<dyn Error>> {
+        LOADS.fetch_add(1, Ordering::SeqCst);
+        thread::sleep(Duration::from_millis(50));
+
+        match url {
+            "broken" => Err("404 Not Found".into()),
+            _ => Ok(format!("encoded {emote_id}")),
+        }
+    }
+
+    let loader = EmoteLoader::new(2, test_load);
+
+    // Requests for an emote that is already loading are dropped
+    loader.request("25", "https://example.com/25");
+    loader.request("25", "https://example.com/25");
+    loader.request("404", "broken");
+    assert_eq!(loader.get("25"), None);
+
+    let started = Instant::now();
+    while !loader.state.pending.lock().unwrap().is_empty() {
+        assert!(
+            started.elapsed() < Duration::from_secs(5),
+            "emotes never finished loading"
+        );
+        thread::sleep(Duration::from_millis(10));
+    }
+
+    assert_eq!(loader.get("25"), Some("encoded 25".to_string()));
+    assert_eq!(loader.get("404"), None);
+    assert!(loader.take_repaint());
+    assert!(!loader.take_repaint());
+
+    // Loaded and failed emotes aren't loaded again
+    loader.request("25", "https://example.com/25");
+    loader.request("404", "broken");
+    assert_eq!(LOADS.load(Ordering::SeqCst), 2);
+}
@@ -0,0 +1,488 @@
+use std::{collections::HashMap, error::Error};
+
+use serde::Deserialize;
+
+use crate::commands::get_emote_providers;
+
+use super::pubsub::send_to_error_log;
+
+pub const SEVEN_TV: &str = "7tv";
+pub const BTTV: &str = "bttv";
+pub const FFZ: &str = "ffz";
+
+/// The providers a channel uses when none have been configured for it
+pub const DEFAULT_PROVIDERS: [&str; 3] = [SEVEN_TV, BTTV, FFZ];
+
+const SEVEN_TV_API_URL: &str = "https://7tv.io/v3";
+const BTTV_API_URL: &str = "https://api.betterttv.net/3";
+const FFZ_API_URL: &str = "https://api.frankerfacez.com/v1";
+
+/// An emote from a third party provider, matched against the words in chat messages
+#[derive(Clone, Debug, PartialEq)]
+pub struct ProviderEmote {
+    pub provider: String,
+    pub id: String,
+    pub name: String,
+    pub url: String,
+}
+
+pub trait EmoteProvider {
+    fn name(&self) -> &str;
+    fn load_global(&self) -> Result<Vec<ProviderEmote>, Box<dyn Error>>;
+    fn load_channel(&self, channel_id: &str) -> Result<Vec<ProviderEmote>, Box<dyn Error>>;
+}
+
+#[derive(Deserialize)]
+struct SevenTvEmote {
+    id: String,
+    name: String,
+    #[serde(default)]
+    data: SevenTvEmoteData,
+}
+
+#[derive(Default, Deserialize)]
+struct SevenTvEmoteData {
+    #[serde(default)]
+    animated: bool,
+}
+
+#[derive(Deserialize)]
+struct SevenTvEmoteSet {
+    #[serde(default)]
+    emotes: Vec<SevenTvEmote>,
+}
+
+#[derive(Deserialize)]
+struct SevenTvUser {
+    emote_set: Option<SevenTvEmoteSet>,
+}
+
+pub struct SevenTv {
+    api_url: String,
+}
+
+impl SevenTv {
+    pub fn new(api_url: &str) -> Self {
+        SevenTv {
+            api_url: api_url.to_string(),
+        }
+    }
+
+    fn get_emotes(&self, emote_set: SevenTvEmoteSet) -> Vec<ProviderEmote> {
+        emote_set
+            .emotes
+            .into_iter()
+            .map(|emote| {
+                // Terminals can't show webp/avif, 7TV also serves gif and png versions
+                let extension = if emote.data.animated { "gif" } else { "png" };
+
+                ProviderEmote {
+                    provider: self.name().to_string(),
+                    url: format!("https://cdn.7tv.app/emote/{}/1x.{extension}", emote.id),
+                    id: emote.id,
+                    name: emote.name,
+                }
+            })
+            .collect()
+    }
+}
+
+impl Default for SevenTv {
+    fn default() -> Self {
+        SevenTv::new(SEVEN_TV_API_URL)
+    }
+}
+
+impl EmoteProvider for SevenTv {
+    fn name(&self) -> &str {
+        SEVEN_TV
+    }
+
+    fn load_global(&self) -> Result<Vec<ProviderEmote>, Box<dyn Error>> {
+        let response = ureq::get(&format!("{}/emote-sets/global", self.api_url)).call()?;
+        let emote_set: SevenTvEmoteSet = serde_json::from_reader(response.into_reader())?;
+
+        Ok(self.get_emotes(emote_set))
+    }
+
+    fn load_channel(&self, channel_id: &str) -> Result<Vec<ProviderEmote>, Box<dyn Error>> {
+        let response = ureq::get(&format!("{}/users/twitch/{channel_id}", self.api_url)).call()?;
+        let user: SevenTvUser = serde_json::from_reader(response.into_reader())?;
+
+        Ok(user.emote_set.map(|set| self.get_emotes(set)).unwrap_or_default())
+    }
+}
+
+#[derive(Deserialize)]
+struct BttvEmote {
+    id: String,
+    code: String,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct BttvUser {
+    #[serde(default)]
+    channel_emotes: Vec<BttvEmote>,
+    #[serde(default)]
+    shared_emotes: Vec<BttvEmote>,
+}
+
+pub struct Bttv {
+    api_url: String,
+}
+
+impl Bttv {
+    pub fn new(api_url: &str) -> Self {
+        Bttv {
+            api_url: api_url.to_string(),
+        }
+    }
+
+    fn get_emotes(&self, emotes: Vec<BttvEmote>) -> Vec<ProviderEmote> {
+        emotes
+            .into_iter()
+            .map(|emote| ProviderEmote {
+                provider: self.name().to_string(),
+                url: format!("https://cdn.betterttv.net/emote/{}/1x", emote.id),
+                id: emote.id,
+                name: emote.code,
+            })
+            .collect()
+    }
+}
+
+impl Default for Bttv {
+    fn default() -> Self {
+        Bttv::new(BTTV_API_URL)
+    }
+}
+
+impl EmoteProvider for Bttv {
+    fn name(&self) -> &str {
+        BTTV
+    }
+
+    fn load_global(&self) -> Result<Vec<ProviderEmote>, Box<dyn Error>> {
+        let response = ureq::get(&format!("{}/cached/emotes/global", self.api_url)).call()?;
+        let emotes: Vec<BttvEmote> = serde_json::from_reader(response.into_reader())?;
+
+        Ok(self.get_emotes(emotes))
+    }
+
+    fn load_channel(&self, channel_id: &str) -> Result<Vec<ProviderEmote>, Box<dyn Error>> {
+        let response = ureq::get(&format!("{}/cached/users/twitch/{channel_id}", self.api_url)).call()?;
+        let user: BttvUser = serde_json::from_reader(response.into_reader())?;
+
+        let mut emotes = user.channel_emotes;
+        emotes.extend(user.shared_emotes);
+
+        Ok(self.get_emotes(emotes))
+    }
+}
+
+#[derive(Deserialize)]
+struct FfzEmote {
+    id: u64,
+    name: String,
+}
+
+#[derive(Deserialize)]
+struct FfzSet {
+    #[serde(default)]
+    emoticons: Vec<FfzEmote>,
+}
+
+#[derive(Deserialize)]
+struct FfzGlobal {
+    default_sets: Vec<u64>,
+    sets: HashMap<String, FfzSet>,
+}
+
+#[derive(Deserialize)]
+struct FfzRoomSet {
+    set: u64,
+}
+
+#[derive(Deserialize)]
+struct FfzRoom {
+    room: FfzRoomSet,
+    sets: HashMap<String, FfzSet>,
+}
+
+pub struct Ffz {
+    api_url: String,
+}
+
+impl Ffz {
+    pub fn new(api_url: &str) -> Self {
+        Ffz {
+            api_url: api_url.to_string(),
+        }
+    }
+
+    fn get_emotes(&self, set_ids: &[u64], mut sets: HashMap<String, FfzSet>) -> Vec<ProviderEmote> {
+        set_ids
+            .iter()
+            .filter_map(|set_id| sets.remove(&set_id.to_string()))
+            .flat_map(|set| set.emoticons)
+            .map(|emote| ProviderEmote {
+                provider: self.name().to_string(),
+                id: emote.id.to_string(),
+                url: format!("https://cdn.frankerfacez.com/emote/{}/1", emote.id),
+                name: emote.name,
+            })
+            .collect()
+    }
+}
+
+impl Default for Ffz {
+    fn default() -> Self {
+        Ffz::new(FFZ_API_URL)
+    }
+}
+
+impl EmoteProvider for Ffz {
+    fn name(&self) -> &str {
+        FFZ
+    }
+
+    fn load_global(&self) -> Result<Vec<ProviderEmote>, Box<dyn Error>> {
+        let response = ureq::get(&format!("{}/set/global", self.api_url)).call()?;
+        let global: FfzGlobal = serde_json::from_reader(response.into_reader())?;
+
+        Ok(self.get_emotes(&global.default_sets, global.sets))
+    }
+
+    fn load_channel(&self, channel_id: &str) -> Result<Vec<ProviderEmote>, Box<dyn Error>> {
+        let response = ureq::get(&format!("{}/room/id/{channel_id}", self.api_url)).call()?;
+        let room: FfzRoom = serde_json::from_reader(response.into_reader())?;
+
+        Ok(self.get_emotes(&[room.room.set], room.sets))
+    }
+}
+
+/// Holds the emote sets of every provider, emotes are looked up by the word used in chat
+pub struct EmoteRegistry {
+    providers: Vec<Box<dyn EmoteProvider + Send>>,
+    global_emotes: HashMap<String, Vec<ProviderEmote>>,
+    channel_emotes: HashMap<String, HashMap<String, ProviderEmote>>,
+}
+
+impl Default for EmoteRegistry {
+    fn default() -> Self {
+        EmoteRegistry::new(vec![
+            Box::new(SevenTv::default()),
+            Box::new(Bttv::default()),
+            Box::new(Ffz::default()),
+        ])
+    }
+}
+
+impl EmoteRegistry {
+    pub fn new(providers: Vec<Box<dyn EmoteProvider + Send>>) -> Self {
+        EmoteRegistry {
+            providers,
+            global_emotes: HashMap::new(),
+            channel_emotes: HashMap::new(),
+        }
+    }
+
+    pub fn load_global(&mut self) {
+        for provider in self.providers.iter() {
+            match provider.load_global() {
+                Ok(emotes) => {
+                    self.global_emotes.insert(provider.name().to_string(), emotes);
+                }
+
+                Err(error) => send_to_error_log(
+                    error.to_string(),
+                    format!("Could not load {} global emotes", provider.name()),
+                ),
+            }
+        }
+    }
+
+    /// Loads the emote sets for a #channel, only the providers configured for the channel are used
+    pub fn load_channel(&mut self, channel: &str, channel_id: &str, enabled_providers: &[String]) {
+        let mut emotes: HashMap<String, ProviderEmote> = HashMap::new();
+
+        // Providers listed first win when two providers have an emote with the same name
+        let providers: Vec<&Box<dyn EmoteProvider + Send>> = enabled_providers
+            .iter()
+            .rev()
+            .filter_map(|provider_name| self.providers.iter().find(|provider| provider.name() == provider_name))
+            .collect();
+
+        for provider in &providers {
+            if let Some(global_emotes) = self.global_emotes.get(provider.name()) {
+                for emote in global_emotes {
+                    emotes.insert(emote.name.clone(), emote.clone());
+                }
+            }
+        }
+
+        // The channel's own emotes win over every provider's global emotes
+        for provider in &providers {
+            match provider.load_channel(channel_id) {
+                Ok(channel_emotes) => {
+                    for emote in channel_emotes {
+                        emotes.insert(emote.name.clone(), emote);
+                    }
+                }
+
+                Err(error) => send_to_error_log(
+                    error.to_string(),
+                    format!("Could not load {} emotes for {channel}", provider.name()),
+                ),
+            }
+        }
+
+        self.channel_emotes.insert(channel.to_string(), emotes);
+    }
+
+    /// Loads a channel's emote sets with the providers configured via `sergeant emote-providers`
+    pub fn load_configured_channel(&mut self, channel: &str, channel_id: &str) {
+        let enabled_providers = get_emote_providers(channel);
+        self.load_channel(channel, channel_id, &enabled_providers);
+    }
+
+    pub fn find(&self, channel: &str, word: &str) -> Option<&ProviderEmote> {
+        self.channel_emotes.get(channel)?.get(word)
+    }
+}
+
+#[cfg(test)]
+mod emote_providers_tests {
+    use std::{
+        io::{BufRead, BufReader, Write},
+        net::TcpListener,
+        thread,
+    };
+
+    use super::{Bttv, EmoteProvider, EmoteRegistry, Ffz, SevenTv, BTTV, FFZ, SEVEN_TV};
+
+    // Local stand-in for the provider APIs, answers every request with the body routed to its path
+    fn serve(routes: Vec<(&'static str, &'static str)>) -> String {
+        let listener = TcpListener::bind("127.0.0.1:0").expect("Could not bind the test server");
+        let address = listener.local_addr().expect("Expected a local address");
+
+        thread::spawn(move || {
+            for stream in listener.incoming() {
+                let Ok(mut stream) = stream else {
+                    continue;
+                };
+
+                let mut request_line = String::new();
+                let mut reader = BufReader::new(&stream);
+                let _ = reader.read_line(&mut request_line);
+                let path = request_line.split(' ').nth(1).unwrap_or_default();
+
+                let response = match routes.iter().find(|(route, _)| *route == path) {
+                    Some((_, body)) => format!(
+                        "HTTP/1.1 200 OK\r\nContent-Type: application/json\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{body}",
+                        body.len()
+                    ),
+                    None => "HTTP/1.1 404 Not Found\r\nContent-Length: 0\r\nConnection: close\r\n\r\n".to_string(),
+                };
+
+                let _ = stream.write_all(response.as_bytes());
+            }
+        });
+
+        format!("http://{address}")
+    }
+
+    #[test]
+    fn test_seven_tv_provider() {
+        let api_url = serve(vec![
+            (
+                "/emote-sets/global",
+                r#"{"id":"global","emotes":[{"id":"60ae958e229664e8667aea38","name":"peepoHappy","data":{"animated":false}}]}"#,
+            ),
+            (
+                "/users/twitch/12345678",
+                r#"{"emote_set":{"emotes":[{"id":"63071b80942ffb69e13d700f","name":"catJAM","data":{"animated":true}}]}}"#,
+            ),
+        ]);
+
+        let provider = SevenTv::new(&api_url);
+
+        let global_emotes = provider.load_global().expect("Expected global emotes");
+        assert_eq!(global_emotes[0].name, "peepoHappy");
+        assert_eq!(
+            global_emotes[0].url,
+            "https://cdn.7tv.app/emote/60ae958e229664e8667aea38/1x.png"
+        );
+
+        let channel_emotes = provider.load_channel("12345678").expect("Expected channel emotes");
+        assert_eq!(channel_emotes[0].name, "catJAM");
+        assert_eq!(
+            channel_emotes[0].url,
+            "https://cdn.7tv.app/emote/63071b80942ffb69e13d700f/1x.gif"
+        );
+    }
+
+    #[test]
+    fn test_emote_registry() {
+        let api_url = serve(vec![
+            (
+                "/cached/emotes/global",
+                r#"[{"id":"54fa8f1401e468494b85b537","code":":tf:","imageType":"png"},{"id":"5d38aaa592fc550c2d5996b8","code":"LUL","imageType":"png"}]"#,
+            ),
+            (
+                "/cached/users/twitch/12345678",
+                r#"{"channelEmotes":[{"id":"5f1b0186cf6d2144653d2970","code":"catJAM"}],"sharedEmotes":[{"id":"5e4e7a1f08b4447d56a92967","code":"Clap"}]}"#,
+            ),
+            (
+                "/set/global",
+                r#"{"default_sets":[3],"sets":{"3":{"emoticons":[{"id":25927,"name":"CatBag"}]},"4":{"emoticons":[{"id":1,"name":"Hidden"}]}}}"#,
+            ),
+            (
+                "/room/id/12345678",
+                r#"{"room":{"set":9},"sets":{"9":{"emoticons":[{"id":720507,"name":"catJAM"},{"id":2,"name":"LUL"}]}}}"#,
+            ),
+        ]);
+
+        let mut registry = EmoteRegistry::new(vec![Box::new(Bttv::new(&api_url)), Box::new(Ffz::new(&api_url))]);
+        registry.load_global();
+
+        let providers = vec![BTTV.to_string(), FFZ.to_string()];
+        registry.load_channel("#ronni", "12345678", &providers);
+
+        assert_eq!(
+            registry.find("#ronni", ":tf:").map(|emote| emote.provider.as_str()),
+            Some(BTTV)
+        );
+        assert_eq!(
+            registry.find("#ronni", "Clap").map(|emote| emote.provider.as_str()),
+            Some(BTTV)
+        );
+        assert_eq!(
+            registry.find("#ronni", "CatBag").map(|emote| emote.provider.as_str()),
+            Some(FFZ)
+        );
+        assert_eq!(registry.find("#ronni", "Hidden"), None);
+        assert_eq!(registry.find("#other", "Clap"), None);
+
+        // Both providers have a catJAM, the provider listed first is used
+        assert_eq!(
+            registry.find("#ronni", "catJAM").map(|emote| emote.provider.as_str()),
+            Some(BTTV)
+        );
+
+        // The channel's LUL wins over the global LUL of the provider listed first
+        assert_eq!(
+            registry.find("#ronni", "LUL").map(|emote| emote.provider.as_str()),
+            Some(FFZ)
+        );
+
+        // Providers that are not enabled for a channel are skipped
+        registry.load_channel("#dallas", "12345678", &[FFZ.to_string(), SEVEN_TV.to_string()]);
+        assert_eq!(
+            registry.find("#dallas", "catJAM").map(|emote| emote.provider.as_str()),
+            Some(FFZ)
+        );
+        assert_eq!(registry.find("#dallas", "Clap"), None);
+    }
+}
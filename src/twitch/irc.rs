@@ -1,249 +1,986 @@
 use std::{
+    collections::HashMap,
     error::Error,
-    net::TcpStream,
-    sync::{mpsc::Sender, Arc},
+    sync::{mpsc::Sender, Arc, Mutex, MutexGuard, OnceLock, PoisonError},
     thread::sleep,
-    time::Duration,
+    time::{Duration, Instant, SystemTime, UNIX_EPOCH},
 };
 
-use tungstenite::{stream::MaybeTlsStream, WebSocket};
-
 use crate::twitch::parse::parse;
 
 use super::{
-    api::{get_user, TwitchApiResponse},
-    parse::{BadgeItem, ChatMessage, TwitchMessage},
-    pubsub::send_to_error_log,
+    api::{get_user, send_whisper, TwitchApiResponse},
+    bot::{BotConnection, BotCredentials},
+    emote_providers::EmoteRegistry,
+    parse::{
+        BadgeItem, ChatMessage, Cheermote, ConnectionState, ConnectionStatus, GlobalUserState, NoticeMessage,
+        RoomState, TwitchMessage, UserState, WhisperMessage,
+    },
+    pubsub::{send_to_error_log, Credentials},
+    ratelimit::{SendQueue, MODERATOR_MESSAGE_LIMIT, USER_MESSAGE_LIMIT},
+    transport::{connect_transport, IrcRead, IrcTransport, READ_TIMEOUT, TWITCH_IRC_URL},
     ChannelMessages,
 };
 
 pub struct TwitchIRC {
     tx: Sender<ChannelMessages>,
-    pub socket: WebSocket<MaybeTlsStream<TcpStream>>,
+    pub transport: Box<dyn IrcTransport>,
     pub nickname: String,
+    pub channels: Vec<String>,
     pub oauth_token: String,
     pub client_id: String,
-    pub badges: Option<Vec<BadgeItem>>,
+    /// Channel badges by room id, loaded for every joined channel
+    pub badges: HashMap<String, Vec<BadgeItem>>,
+    /// Global plus channel cheermotes by room id
+    pub cheermotes: HashMap<String, Vec<Cheermote>>,
+    pub user_states: HashMap<String, UserState>,
+    pub global_user_state: Option<GlobalUserState>,
+    pub room_states: HashMap<String, RoomState>,
+    pub emote_registry: EmoteRegistry,
+    /// Logged in anonymously, nothing can be sent and bot features are off
+    pub read_only: bool,
+    /// Sends chat messages as a separate bot account when set
+    pub bot: Option<BotConnection>,
+    irc_url: String,
+    /// The user id whispers are sent from, looked up on the first whisper
+    whisper_user_id: Option<String>,
+    /// Owned by this connection, only shared with senders handed it through share_send_queue
+    send_queue: Arc<Mutex<SendQueue>>,
 }
 
 pub trait TwitchIrcClient {
-    fn display_msg(&self, message: &str);
+    fn display_msg(&self, channel: &str, message: &str);
     fn listen(&mut self);
-    fn send_privmsg(&mut self, message: &str);
-    fn get_badges(&self) -> Option<Vec<BadgeItem>>;
+    fn send_privmsg(&mut self, channel: &str, message: &str);
+    fn send_reply(&mut self, channel: &str, reply_to: &str, message: &str);
+    fn send_whisper(&mut self, to_user_id: &str, to_login: &str, message: &str);
+    fn get_badges(&self, room_id: &str) -> Option<Vec<BadgeItem>>;
+    fn get_cheermotes(&self, room_id: &str) -> Option<Vec<Cheermote>>;
+    fn get_emote_registry(&self) -> Option<&EmoteRegistry>;
+    fn is_read_only(&self) -> bool;
+    /// The login chat is read with, the broadcaster's
+    fn get_login(&self) -> String;
+    /// The login of the bot account replies are sent from, None without one
+    fn get_bot_login(&self) -> Option<String>;
+    /// The broadcaster's token for Helix API calls, None when chat is read-only
+    fn get_api_credentials(&self) -> Option<Credentials>;
 }
 
-const CONN_MAX_RETRIES: u8 = 3;
 pub const MESSAGE_DELIMITER: &str = "\r\n";
 
-fn connect(twitch_name: &str, oauth_token: &str, retry: u8) -> WebSocket<MaybeTlsStream<TcpStream>> {
-    if retry == CONN_MAX_RETRIES {
-        send_to_error_log(
-            "Unable to reconnect to Twitch IRC:".to_string(),
-            format!("Tried {retry} times"),
-        );
+// Twitch lets anyone read chat without a token with a justinfan nickname
+const ANONYMOUS_NICKNAME: &str = "justinfan";
+const BACKOFF_BASE_MS: u64 = 1000;
+const BACKOFF_MAX_MS: u64 = 60_000;
+
+static IRC_URL: OnceLock<String> = OnceLock::new();
+
+/// Overrides the Twitch IRC endpoint, has to be called before the first connection is made
+pub fn set_irc_url(irc_url: &str) {
+    let _ = IRC_URL.set(irc_url.to_string());
+}
+
+pub fn get_irc_url() -> &'static str {
+    IRC_URL.get().map(|irc_url| irc_url.as_str()).unwrap_or(TWITCH_IRC_URL)
+}
 
-        panic!("Could not connect to Twitch IRC servers, tried {retry} times.")
+/// Normalizes channel names to the #channel form used by IRC, defaults to the user's own channel
+pub fn get_channels(twitch_name: &str, channels: &[String]) -> Vec<String> {
+    let mut irc_channels: Vec<String> = vec![];
+
+    for channel in channels {
+        let channel = format!("#{}", channel.trim().trim_start_matches('#').to_lowercase());
+        if channel.len() > 1 && !irc_channels.contains(&channel) {
+            irc_channels.push(channel);
+        }
     }
 
-    let delay = 5 * retry;
-    let duration = Duration::new(delay.into(), 0);
-    sleep(duration);
+    if irc_channels.is_empty() {
+        irc_channels.push(format!("#{}", twitch_name.to_lowercase()));
+    }
 
-    match tungstenite::connect("wss://irc-ws.chat.twitch.tv:443") {
-        Ok((mut socket, _)) => {
-            if let Err(connection_error) = socket.send(format!("PASS {oauth_token}").into()) {
-                send_to_error_log(connection_error.to_string(), "Error while sending PASS message".into());
+    irc_channels
+}
 
-                let next_retry = retry + 1;
-                return connect(twitch_name, oauth_token, next_retry);
-            }
+fn connect(
+    irc_url: &str,
+    twitch_name: &str,
+    oauth_token: &str,
+    channels: &[String],
+) -> Result<Box<dyn IrcTransport>, Box<dyn Error>> {
+    let mut transport = connect_transport(irc_url, READ_TIMEOUT)?;
 
-            if let Err(nickname_error) = socket.send(format!("NICK {twitch_name}").into()) {
-                send_to_error_log(nickname_error.to_string(), "Error while sending NICK message".into());
+    transport.send_line("CAP REQ :twitch.tv/membership twitch.tv/tags twitch.tv/commands")?;
 
-                let next_retry = retry + 1;
-                return connect(twitch_name, oauth_token, next_retry);
-            }
+    // Anonymous logins don't send a password
+    if !oauth_token.is_empty() {
+        transport.send_line(&format!("PASS {oauth_token}"))?;
+    }
 
-            if let Err(join_channel_error) = socket.send(format!("JOIN #{twitch_name}").into()) {
-                send_to_error_log(
-                    join_channel_error.to_string(),
-                    "Error while sending JOIN message".into(),
-                );
+    transport.send_line(&format!("NICK {twitch_name}"))?;
+    transport.send_line(&format!("JOIN {}", channels.join(",")))?;
 
-                let next_retry = retry + 1;
-                return connect(twitch_name, oauth_token, next_retry);
-            }
+    Ok(transport)
+}
 
-            if let Err(capabilities_error) =
-                socket.send("CAP REQ :twitch.tv/membership twitch.tv/tags twitch.tv/commands".into())
-            {
-                send_to_error_log(
-                    capabilities_error.to_string(),
-                    "Error while requesting capabilities from Twitch".into(),
-                );
+/// Exponential backoff capped at BACKOFF_MAX_MS, half of the delay is random jitter
+pub fn get_backoff(attempt: u32, seed: u64) -> Duration {
+    let delay = BACKOFF_BASE_MS
+        .saturating_mul(2u64.saturating_pow(attempt))
+        .min(BACKOFF_MAX_MS);
+    let half = delay / 2;
+
+    Duration::from_millis(half + seed % (half + 1))
+}
 
-                let next_retry = retry + 1;
-                return connect(twitch_name, oauth_token, next_retry);
+/// A justinfan nickname with a random number, like justinfan48213
+pub fn get_anonymous_nickname(seed: u64) -> String {
+    format!("{ANONYMOUS_NICKNAME}{}", 10_000 + seed % 90_000)
+}
+
+pub fn get_jitter_seed() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|time| time.subsec_nanos().into())
+        .unwrap_or_default()
+}
+
+/// Broadcasters, moderators and VIPs get the higher rate limit, the bot account's own USERSTATE
+/// counts when it sends the messages
+fn is_moderator(user_states: &HashMap<String, UserState>, bot: Option<&BotConnection>, channel: &str) -> bool {
+    match bot {
+        Some(bot) => bot.is_moderator(channel),
+        None => user_states
+            .get(channel)
+            .is_some_and(|user_state| user_state.is_moderator()),
+    }
+}
+
+fn send_status(tx: &Sender<ChannelMessages>, state: ConnectionState, message: String) {
+    let status = ConnectionStatus {
+        state,
+        message,
+        area: None,
+    };
+
+    let _ = tx.send(ChannelMessages::TwitchMessage(TwitchMessage::ConnectionStatusMessage {
+        message: status,
+    }));
+}
+
+/// A message sent from this client, shown in chat since Twitch doesn't send it back to the connection
+/// that sent it
+pub fn get_sent_message(nickname: &str, channel: &str, message: &str) -> ChatMessage {
+    let message = message.strip_suffix('\n').unwrap_or(message);
+
+    ChatMessage {
+        is_bot: true,
+        animation_id: String::from(""),
+        can_animate: false,
+        r: 0,
+        g: 0,
+        b: 0,
+        direction: 0,
+        id: String::from(""),
+        badges: vec![],
+        emotes: vec![],
+        nickname: nickname.to_string(),
+        first_msg: false,
+        returning_chatter: false,
+        subscriber: false,
+        moderator: false,
+        vip: false,
+        broadcaster: false,
+        message: message.to_string(),
+        color: "#808080".to_string(),
+        channel: channel.to_string(),
+        raw: "".to_string(),
+        area: None,
+        timestamp: None,
+        reply_parent: None,
+        bits: None,
+        user_id: String::from(""),
+        login: nickname.to_lowercase(),
+    }
+}
+
+/// Keeps trying to connect until it succeeds, reporting each attempt to the TUI
+fn connect_with_backoff(
+    irc_url: &str,
+    twitch_name: &str,
+    oauth_token: &str,
+    channels: &[String],
+    tx: &Sender<ChannelMessages>,
+) -> Box<dyn IrcTransport> {
+    let mut attempt: u32 = 0;
+
+    loop {
+        match connect(irc_url, twitch_name, oauth_token, channels) {
+            Ok(transport) => {
+                send_status(tx, ConnectionState::Connected, "Connected to Twitch IRC".to_string());
+
+                return transport;
             }
 
-            socket
-        }
+            Err(conn_error) => {
+                send_to_error_log(conn_error.to_string(), "Error during connection to Twitch IRC".into());
+
+                let backoff = get_backoff(attempt, get_jitter_seed());
+                attempt = attempt.saturating_add(1);
 
-        Err(conn_error) => {
-            send_to_error_log(conn_error.to_string(), "Error during connection to socket".into());
+                send_status(
+                    tx,
+                    ConnectionState::Connecting,
+                    format!(
+                        "Could not connect to Twitch IRC, retrying in {}s (attempt {attempt})",
+                        backoff.as_secs()
+                    ),
+                );
 
-            let next_retry = retry + 1;
-            connect(twitch_name, oauth_token, next_retry)
+                sleep(backoff);
+            }
         }
     }
 }
 
 impl TwitchIRC {
-    pub fn new(twitch_name: &str, oauth_token: &str, client_id: &str, tx: Sender<ChannelMessages>) -> Self {
-        let socket = connect(twitch_name, oauth_token, 0);
+    pub fn new(
+        twitch_name: &str,
+        oauth_token: &str,
+        client_id: &str,
+        channels: &[String],
+        tx: Sender<ChannelMessages>,
+    ) -> Self {
+        TwitchIRC::with_url(get_irc_url(), twitch_name, oauth_token, client_id, channels, tx)
+    }
+
+    /// Joins channels as an anonymous justinfan user, no credentials needed
+    pub fn read_only(channels: &[String], tx: Sender<ChannelMessages>) -> Self {
+        let nickname = get_anonymous_nickname(get_jitter_seed());
+
+        let mut twitch_irc = TwitchIRC::with_url(get_irc_url(), &nickname, "", "", channels, tx);
+        twitch_irc.read_only = true;
+
+        twitch_irc
+    }
+
+    /// Connects to a different IRC endpoint than Twitch's, like a local server in tests
+    pub fn with_url(
+        irc_url: &str,
+        twitch_name: &str,
+        oauth_token: &str,
+        client_id: &str,
+        channels: &[String],
+        tx: Sender<ChannelMessages>,
+    ) -> Self {
+        let channels = get_channels(twitch_name, channels);
+        let transport = connect_with_backoff(irc_url, twitch_name, oauth_token, &channels, &tx);
 
         TwitchIRC {
-            socket,
+            transport,
             tx,
+            channels,
             nickname: twitch_name.to_string(),
             oauth_token: oauth_token.to_string(),
             client_id: client_id.to_string(),
-            badges: None,
+            badges: HashMap::new(),
+            cheermotes: HashMap::new(),
+            user_states: HashMap::new(),
+            global_user_state: None,
+            room_states: HashMap::new(),
+            emote_registry: EmoteRegistry::default(),
+            read_only: false,
+            bot: None,
+            irc_url: irc_url.to_string(),
+            whisper_user_id: None,
+            send_queue: Arc::default(),
+        }
+    }
+
+    /// Sends chat messages as the bot account from now on, the broadcaster's login keeps reading chat
+    pub fn connect_bot(&mut self, credentials: &BotCredentials) {
+        self.bot = Some(BotConnection::connect(&self.irc_url, credentials, &self.channels));
+        self.whisper_user_id = None;
+
+        // The bot account has a rate limit of its own
+        self.send_queue = Arc::default();
+    }
+
+    /// Sends through a queue that another sender also pushes to, like announcements, so their
+    /// messages count toward the same rate limit. Only this connection sends what's queued
+    pub fn share_send_queue(&mut self, send_queue: Arc<Mutex<SendQueue>>) {
+        self.send_queue = send_queue;
+    }
+
+    /// The login, token and client id whispers are sent with, the bot account's when there is one
+    fn get_whisper_credentials(&self) -> (String, String, String) {
+        match &self.bot {
+            Some(bot) => (
+                bot.credentials.name.clone(),
+                bot.credentials.oauth_token.clone(),
+                bot.credentials.client_id.clone(),
+            ),
+            None => (self.nickname.clone(), self.oauth_token.clone(), self.client_id.clone()),
+        }
+    }
+
+    fn whisper(&mut self, to_user_id: &str, message: &str) -> Result<String, Box<dyn Error>> {
+        let (login, oauth_token, client_id) = self.get_whisper_credentials();
+
+        let from_user_id = match &self.whisper_user_id {
+            Some(user_id) => user_id.clone(),
+            None => {
+                let user_id = get_user(&oauth_token, &client_id)?.id;
+                self.whisper_user_id = Some(user_id.clone());
+
+                user_id
+            }
+        };
+
+        send_whisper(&oauth_token, &client_id, &from_user_id, to_user_id, message)?;
+
+        Ok(login)
+    }
+
+    /// Broadcasters, moderators and VIPs get the higher rate limit in their channel
+    fn is_moderator(&self, channel: &str) -> bool {
+        is_moderator(&self.user_states, self.bot.as_ref(), channel)
+    }
+
+    fn display_notice(&self, channel: &str, msg_id: &str, message: String) {
+        let notice = NoticeMessage {
+            channel: channel.to_string(),
+            msg_id: msg_id.to_string(),
+            message,
+            ..Default::default()
+        };
+
+        let _ = self
+            .tx
+            .send(ChannelMessages::TwitchMessage(TwitchMessage::NoticeMessage {
+                message: notice,
+            }));
+    }
+
+    fn queue_privmsg(&mut self, channel: &str, reply_to: Option<&str>, message: &str) {
+        if self.read_only {
+            send_to_error_log(message.to_string(), "Chat is read-only, message not sent".into());
+
+            return;
+        }
+
+        let pushed = self.lock_send_queue().push(channel, message, reply_to, Instant::now());
+        if let Err(dropped) = pushed {
+            self.display_notice(
+                channel,
+                "message_dropped",
+                format!("Send queue is full, dropped message: {}", dropped.message),
+            );
+
+            return;
+        }
+
+        let Some(queued) = self.flush_send_queue() else {
+            return;
+        };
+
+        let limit = if self.is_moderator(channel) {
+            MODERATOR_MESSAGE_LIMIT
+        } else {
+            USER_MESSAGE_LIMIT
+        };
+
+        self.display_notice(
+            channel,
+            "message_delayed",
+            format!("Rate limit of {limit} messages per 30s reached, {queued} message(s) queued"),
+        );
+    }
+
+    fn lock_send_queue(&self) -> MutexGuard<'_, SendQueue> {
+        self.send_queue.lock().unwrap_or_else(PoisonError::into_inner)
+    }
+
+    /// Sends every queued message that the rate limit allows, drops the ones that waited too long.
+    /// Returns how many messages the rate limit held back, None when nothing was held back
+    pub fn flush_send_queue(&mut self) -> Option<usize> {
+        let now = Instant::now();
+        let send_queue = Arc::clone(&self.send_queue);
+        let mut send_queue = send_queue.lock().unwrap_or_else(PoisonError::into_inner);
+
+        for stale in send_queue.drop_stale(now) {
+            self.display_notice(
+                &stale.channel,
+                "message_dropped",
+                format!("Rate limited, dropped message: {}", stale.message),
+            );
+        }
+
+        let user_states = &self.user_states;
+        let bot = self.bot.as_ref();
+
+        while let Some(queued) = send_queue.pop_ready(now, |channel| is_moderator(user_states, bot, channel)) {
+            let message = match &queued.reply_to {
+                Some(reply_to) => format!(
+                    "@reply-parent-msg-id={reply_to} PRIVMSG {} :{}",
+                    queued.channel, queued.message
+                ),
+                None => format!("PRIVMSG {} :{}", queued.channel, queued.message),
+            };
+
+            let sent = match &self.bot {
+                Some(bot) => bot.send_line(&message),
+                None => self.transport.send_line(&message),
+            };
+
+            if let Err(send_error) = sent {
+                send_to_error_log(send_error.to_string(), "Error while sending PRIVMSG".into());
+            }
+        }
+
+        // pop_ready only stops early when the rate limit refused the next message
+        let held_back = send_queue.len();
+        (held_back > 0).then_some(held_back)
+    }
+
+    fn reconnect(&mut self, reason: &str) {
+        send_status(
+            &self.tx,
+            ConnectionState::Disconnected,
+            format!("{reason}, reconnecting to Twitch IRC..."),
+        );
+
+        self.transport.close();
+        self.transport = connect_with_backoff(
+            &self.irc_url,
+            &self.nickname,
+            &self.oauth_token,
+            &self.channels,
+            &self.tx,
+        );
+
+        // Twitch sends fresh state after the JOIN, the first ROOMSTATE reloads badges and cheermotes
+        self.user_states.clear();
+        self.room_states.clear();
+    }
+
+    /// Loads the badges and cheermotes of a joined channel
+    fn load_room_assets(&mut self, room_id: &str) {
+        // Badges and cheermotes come from Helix, which needs a token
+        if self.read_only {
+            return;
+        }
+
+        if let Err(error) = self.load_channel_badges(room_id) {
+            send_to_error_log(error.to_string(), format!("Could not load badges for room {room_id}"));
+        }
+
+        if let Err(error) = self.load_cheermotes(room_id) {
+            send_to_error_log(
+                error.to_string(),
+                format!("Could not load cheermotes for room {room_id}"),
+            );
         }
     }
 
-    fn load_channel_badges(&mut self) -> Result<(), Box<dyn Error>> {
+    fn load_cheermotes(&mut self, room_id: &str) -> Result<(), Box<dyn Error>> {
+        // Global cheermotes plus the channel's custom ones
+        let cheermotes =
+            ureq::get(format!("https://api.twitch.tv/helix/bits/cheermotes?broadcaster_id={room_id}").as_str())
+                .set("Client-ID", &self.client_id)
+                .set(
+                    "Authorization",
+                    &format!("Bearer {}", self.oauth_token.replace("oauth:", "")),
+                )
+                .call();
+
+        if let Ok(response) = cheermotes {
+            let response: TwitchApiResponse<Vec<Cheermote>> = serde_json::from_reader(response.into_reader())?;
+
+            self.cheermotes.insert(room_id.to_string(), response.data);
+        }
+
+        Ok(())
+    }
+
+    fn load_channel_badges(&mut self, room_id: &str) -> Result<(), Box<dyn Error>> {
         // Get channel badges
-        let user = get_user(&self.oauth_token, &self.client_id)?;
-        let channel_badges = ureq::get(
-            format!(
-                "https://api.twitch.tv/helix/chat/badges?broadcaster_id={}",
-                user.id.as_str()
-            )
-            .as_str(),
-        )
-        .set("Client-ID", &self.client_id)
-        .set(
-            "Authorization",
-            &format!("Bearer {}", self.oauth_token.replace("oauth:", "")),
-        )
-        .call();
+        let channel_badges =
+            ureq::get(format!("https://api.twitch.tv/helix/chat/badges?broadcaster_id={room_id}").as_str())
+                .set("Client-ID", &self.client_id)
+                .set(
+                    "Authorization",
+                    &format!("Bearer {}", self.oauth_token.replace("oauth:", "")),
+                )
+                .call();
 
         if let Ok(response) = channel_badges {
             let response: TwitchApiResponse<Vec<BadgeItem>> = serde_json::from_reader(response.into_reader())?;
 
-            self.badges = Some(response.data);
+            self.badges.insert(room_id.to_string(), response.data);
         }
 
         Ok(())
     }
+
+    /// Sends queued messages and handles whatever Twitch sent since the last read, waits at most
+    /// READ_TIMEOUT for something to arrive
+    fn read_messages(&mut self) {
+        let _ = self.flush_send_queue();
+
+        // NOTE: Twitch could send multiple messages at once, the transport splits them into lines
+        let messages = match self.transport.read_lines() {
+            Ok(IrcRead::Lines(messages)) => messages,
+            Ok(IrcRead::Timeout) => return,
+            Ok(IrcRead::Closed) => {
+                send_to_error_log("Connection closed".to_string(), "Connection closed".to_string());
+                self.reconnect("Connection closed");
+
+                return;
+            }
+            Err(read_error) => {
+                send_to_error_log(read_error.to_string(), "Error while reading from Twitch IRC".into());
+                self.reconnect("Lost connection");
+
+                return;
+            }
+        };
+
+        let mut reconnect_reason: Option<&str> = None;
+        messages.iter().for_each(|new_message| match parse(new_message, self) {
+            Ok(
+                message @ TwitchMessage::RedeemMessage { .. }
+                | message @ TwitchMessage::ClearMessage { .. }
+                | message @ TwitchMessage::ClearChatMessage { .. }
+                | message @ TwitchMessage::PrivMessage { .. }
+                | message @ TwitchMessage::NoticeMessage { .. }
+                | message @ TwitchMessage::RaidMessage { .. }
+                | message @ TwitchMessage::SubNotice { .. }
+                | message @ TwitchMessage::ResubNotice { .. }
+                | message @ TwitchMessage::SubGiftNotice { .. }
+                | message @ TwitchMessage::SubMysteryGiftNotice { .. }
+                | message @ TwitchMessage::GiftPaidUpgradeNotice { .. }
+                | message @ TwitchMessage::PrimePaidUpgradeNotice { .. }
+                | message @ TwitchMessage::AnnouncementNotice { .. }
+                | message @ TwitchMessage::BitsBadgeTierNotice { .. }
+                | message @ TwitchMessage::ViewerMilestoneNotice { .. }
+                | message @ TwitchMessage::CharityDonationNotice { .. }
+                | message @ TwitchMessage::WhisperMessage { .. },
+            ) => {
+                let _ = self.tx.send(ChannelMessages::TwitchMessage(message));
+            }
+
+            Ok(TwitchMessage::ClearMessageByUser { .. }) => {
+                // NOTE: This message is being used by EventSub
+            }
+
+            Ok(TwitchMessage::UserStateMessage { message }) => {
+                self.user_states.insert(message.channel.clone(), message);
+            }
+
+            Ok(TwitchMessage::GlobalUserStateMessage { message }) => {
+                self.global_user_state = Some(message);
+            }
+
+            Ok(TwitchMessage::RoomStateMessage { message }) => {
+                match self.room_states.get_mut(&message.channel) {
+                    Some(room_state) => room_state.merge(message),
+                    None => {
+                        // The first ROOMSTATE after joining has the channel id the emote providers need
+                        self.emote_registry
+                            .load_configured_channel(&message.channel, &message.room_id);
+                        self.load_room_assets(&message.room_id);
+
+                        self.room_states.insert(message.channel.clone(), message);
+                    }
+                }
+            }
+
+            Ok(TwitchMessage::ReconnectMessage { message }) => {
+                send_to_error_log(message, "Twitch requested a reconnect".to_string());
+
+                reconnect_reason = Some("Twitch requested a reconnect");
+            }
+
+            Ok(
+                TwitchMessage::HostTargetMessage { .. }
+                | TwitchMessage::JoinMessage { .. }
+                | TwitchMessage::PartMessage { .. }
+                | TwitchMessage::ConnectionStatusMessage { .. },
+            ) => {}
+
+            Ok(TwitchMessage::PingMessage { message }) => {
+                let pong_message = format!("PONG {message}");
+
+                let _ = self.transport.send_line(&pong_message);
+            }
+
+            Ok(TwitchMessage::UnknownMessage { message }) => send_to_error_log(message, "Unknown message".to_string()),
+
+            Err(error) => send_to_error_log(
+                error.to_string(),
+                format!("Error while parsing message: {}", new_message),
+            ),
+        });
+
+        if let Some(reason) = reconnect_reason {
+            self.reconnect(reason);
+        }
+    }
 }
 
 impl TwitchIrcClient for TwitchIRC {
-    fn get_badges(&self) -> Option<Vec<BadgeItem>> {
-        self.badges.clone()
+    fn get_badges(&self, room_id: &str) -> Option<Vec<BadgeItem>> {
+        self.badges.get(room_id).cloned()
+    }
+
+    fn get_cheermotes(&self, room_id: &str) -> Option<Vec<Cheermote>> {
+        self.cheermotes.get(room_id).cloned()
+    }
+
+    fn get_emote_registry(&self) -> Option<&EmoteRegistry> {
+        Some(&self.emote_registry)
     }
 
     // Displays messages sent by the bot
-    fn display_msg(&self, message: &str) {
-        let msg = if message.ends_with("\n") {
-            let stripped = message.strip_suffix("\n");
-            stripped.unwrap_or(message)
-        } else {
-            message
-        };
+    fn display_msg(&self, channel: &str, message: &str) {
+        // Messages from a bot account come back through chat like anyone else's
+        if self.bot.is_some() {
+            return;
+        }
 
-        let _ = self.tx.send(ChannelMessages::TwitchMessage(TwitchMessage::PrivMessage {
-            message: ChatMessage {
-                is_bot: true,
-                animation_id: String::from(""),
-                can_animate: false,
-                r: 0,
-                g: 0,
-                b: 0,
-                direction: 0,
-                id: String::from(""),
-                badges: vec![],
-                emotes: vec![],
-                nickname: self.nickname.to_string(),
-                first_msg: false,
-                returning_chatter: false,
-                subscriber: false,
-                moderator: false,
-                message: msg.to_string(),
-                color: "#808080".to_string(),
-                channel: format!("{}{}", "#", self.nickname),
-                raw: "".to_string(),
-                area: None,
-                timestamp: None,
-            },
-        }));
-    }
-
-    fn send_privmsg(&mut self, message: &str) {
-        let message = format!("PRIVMSG #{} :{message}", self.nickname);
-        let _ = self.socket.send(message.into());
+        let message = get_sent_message(&self.nickname, channel, message);
+        let _ = self
+            .tx
+            .send(ChannelMessages::TwitchMessage(TwitchMessage::PrivMessage { message }));
     }
 
-    fn listen(&mut self) {
-        let _ = self.load_channel_badges();
+    fn send_privmsg(&mut self, channel: &str, message: &str) {
+        self.queue_privmsg(channel, None, message);
+    }
 
-        loop {
-            if let Ok(message) = self.socket.read() {
-                // NOTE: Twitch could send multiple messages at once, so we need to split them
-                // The messages are separated by '\r\n'
-                let Ok(messages_text) = message.to_text() else {
-                    continue;
+    fn send_reply(&mut self, channel: &str, reply_to: &str, message: &str) {
+        self.queue_privmsg(channel, Some(reply_to), message);
+    }
+
+    fn send_whisper(&mut self, to_user_id: &str, to_login: &str, message: &str) {
+        if self.read_only {
+            send_to_error_log(message.to_string(), "Chat is read-only, whisper not sent".into());
+
+            return;
+        }
+
+        let message = message.trim_end();
+        match self.whisper(to_user_id, message) {
+            // Sent whispers don't come back from Twitch, so they're shown here
+            Ok(from_login) => {
+                let whisper = WhisperMessage {
+                    display_name: from_login.clone(),
+                    from_login,
+                    to_login: to_login.to_string(),
+                    message: message.to_string(),
+                    color: "#808080".to_string(),
+                    outgoing: true,
+                    ..Default::default()
                 };
 
-                let messages = messages_text.split(MESSAGE_DELIMITER).map(tungstenite::Message::from);
+                let _ = self
+                    .tx
+                    .send(ChannelMessages::TwitchMessage(TwitchMessage::WhisperMessage {
+                        message: whisper,
+                    }));
+            }
 
-                messages.for_each(|message| match message {
-                    tungstenite::Message::Text(new_message) => match parse(&new_message, self) {
-                        Ok(
-                            message @ TwitchMessage::RedeemMessage { .. }
-                            | message @ TwitchMessage::ClearMessage { .. }
-                            | message @ TwitchMessage::PrivMessage { .. }
-                            | message @ TwitchMessage::RaidMessage { .. },
-                        ) => {
-                            let _ = self.tx.send(ChannelMessages::TwitchMessage(message));
-                        }
+            Err(error) => send_to_error_log(error.to_string(), format!("Could not whisper {to_login}: {message}")),
+        }
+    }
 
-                        Ok(TwitchMessage::ClearMessageByUser { .. }) => {
-                            // NOTE: This message is being used by EventSub
-                        }
+    fn is_read_only(&self) -> bool {
+        self.read_only
+    }
 
-                        Ok(TwitchMessage::PingMessage { message }) => {
-                            let pong_message = format!("PONG {message}");
+    fn get_login(&self) -> String {
+        self.nickname.clone()
+    }
 
-                            let _ = self.socket.send(pong_message.into());
-                        }
+    fn get_bot_login(&self) -> Option<String> {
+        self.bot.as_ref().map(|bot| bot.credentials.name.clone())
+    }
 
-                        Ok(TwitchMessage::UnknownMessage { message }) => {
-                            send_to_error_log(message, "Unknown message".to_string())
-                        }
+    fn get_api_credentials(&self) -> Option<Credentials> {
+        if self.read_only {
+            return None;
+        }
 
-                        Err(error) => send_to_error_log(
-                            error.to_string(),
-                            format!("Error while parsing message: {}", new_message),
-                        ),
-                    },
+        Some(Credentials {
+            oauth_token: Arc::new(self.oauth_token.clone()),
+            client_id: Arc::new(self.client_id.clone()),
+        })
+    }
 
-                    tungstenite::Message::Close(_) => {
-                        send_to_error_log("Connection closed".to_string(), "Connection closed".to_string());
+    fn listen(&mut self) {
+        self.emote_registry.load_global();
 
-                        let name: Arc<String> = Arc::new(self.nickname.clone());
-                        let token: Arc<String> = Arc::new(self.oauth_token.clone());
+        loop {
+            self.read_messages();
+        }
+    }
+}
 
-                        connect(&name, &token, 0);
-                    }
+#[test]
+fn test_get_backoff() {
+    assert_eq!(get_backoff(0, 0), Duration::from_millis(500));
+    assert_eq!(get_backoff(0, 500), Duration::from_millis(1000));
+    assert_eq!(get_backoff(3, 0), Duration::from_millis(4000));
+
+    // Never goes over the cap, even for large attempts or seeds
+    assert!(get_backoff(30, u64::MAX) <= Duration::from_millis(BACKOFF_MAX_MS));
+    assert!(get_backoff(u32::MAX, 0) >= Duration::from_millis(BACKOFF_MAX_MS / 2));
+}
+
+#[test]
+fn test_get_channels() {
+    assert_eq!(get_channels("S9tpepper_", &[]), vec!["#s9tpepper_"]);
+
+    let channels = vec![
+        "#Dallas".to_string(),
+        "ronni".to_string(),
+        "".to_string(),
+        "dallas".to_string(),
+    ];
+    assert_eq!(get_channels("s9tpepper_", &channels), vec!["#dallas", "#ronni"]);
+}
 
-                    /*   */
-                    unknown => send_to_error_log(unknown.to_string(), "Unhandled error from socket.read()".to_string()),
-                });
+#[cfg(test)]
+fn read_until(
+    twitch_irc: &mut TwitchIRC,
+    rx: &std::sync::mpsc::Receiver<ChannelMessages>,
+    matches: impl Fn(&TwitchMessage) -> bool,
+) -> TwitchMessage {
+    for _ in 0..10 {
+        twitch_irc.read_messages();
+
+        while let Ok(message) = rx.try_recv() {
+            if let ChannelMessages::TwitchMessage(message) = message {
+                if matches(&message) {
+                    return message;
+                }
             }
         }
     }
+
+    panic!("TwitchIRC never received the expected message");
+}
+
+#[test]
+fn test_mock_server_handshake() {
+    use super::mock_irc::MockIrcServer;
+
+    let server = MockIrcServer::start();
+    let (tx, rx) = std::sync::mpsc::channel();
+    let channels = vec!["s9tpepper_".to_string(), "dallas".to_string()];
+    let mut twitch_irc = TwitchIRC::with_url(server.url(), "s9tpepper_", "oauth:abc123", "client", &channels, tx);
+
+    server.wait_for("JOIN");
+    assert_eq!(
+        server.received(),
+        vec![
+            "CAP REQ :twitch.tv/membership twitch.tv/tags twitch.tv/commands",
+            "PASS oauth:abc123",
+            "NICK s9tpepper_",
+            "JOIN #s9tpepper_,#dallas",
+        ]
+    );
+
+    let connected = read_until(&mut twitch_irc, &rx, |message| {
+        matches!(message, TwitchMessage::ConnectionStatusMessage { .. })
+    });
+    let TwitchMessage::ConnectionStatusMessage { message: status } = connected else {
+        unreachable!();
+    };
+    assert_eq!(status.state, ConnectionState::Connected);
+}
+
+#[test]
+fn test_mock_server_messages() {
+    use super::mock_irc::MockIrcServer;
+
+    // The same over a WebSocket and plain IRC
+    for server in [MockIrcServer::start(), MockIrcServer::start_tcp()] {
+        let (tx, rx) = std::sync::mpsc::channel();
+        let mut twitch_irc = TwitchIRC::with_url(server.url(), "s9tpepper_", "oauth:abc123", "client", &[], tx);
+
+        server.send("@badges=;color=#FF0000;display-name=Dallas;emotes=;id=b34ccfc7-4977-403a-8a94-33c6bac34fb8;mod=0;room-id=961536166;subscriber=0;tmi-sent-ts=1712345678901;turbo=0;user-id=1337;user-type= :dallas!dallas@dallas.tmi.twitch.tv PRIVMSG #s9tpepper_ :hello from the mock server");
+        let message = read_until(&mut twitch_irc, &rx, |message| {
+            matches!(message, TwitchMessage::PrivMessage { .. })
+        });
+        let TwitchMessage::PrivMessage { message } = message else {
+            unreachable!();
+        };
+        assert_eq!(message.nickname, "Dallas");
+        assert_eq!(message.user_id, "1337");
+        assert_eq!(message.channel, "#s9tpepper_");
+        assert_eq!(message.message, "hello from the mock server");
+
+        // Whispers are passed on to the TUI
+        server.send("@badges=;color=#FF0000;display-name=Dallas;emotes=;message-id=1;thread-id=1337_961536166;turbo=0;user-id=1337;user-type= :dallas!dallas@dallas.tmi.twitch.tv WHISPER s9tpepper_ :psst");
+        let whisper = read_until(&mut twitch_irc, &rx, |message| {
+            matches!(message, TwitchMessage::WhisperMessage { .. })
+        });
+        let TwitchMessage::WhisperMessage { message: whisper } = whisper else {
+            unreachable!();
+        };
+        assert_eq!(whisper.from_login, "dallas");
+        assert_eq!(whisper.message, "psst");
+
+        // Pings are answered right away
+        server.send("PING :tmi.twitch.tv");
+        twitch_irc.read_messages();
+        server.wait_for("PONG tmi.twitch.tv");
+
+        twitch_irc.send_privmsg("#s9tpepper_", "hello back");
+        server.wait_for("PRIVMSG #s9tpepper_ :hello back");
+
+        twitch_irc.send_reply("#s9tpepper_", "b34ccfc7-4977-403a-8a94-33c6bac34fb8", "a reply");
+        server.wait_for("@reply-parent-msg-id=b34ccfc7-4977-403a-8a94-33c6bac34fb8 PRIVMSG #s9tpepper_ :a reply");
+    }
+}
+
+#[test]
+fn test_mock_server_reconnect() {
+    use super::mock_irc::MockIrcServer;
+
+    // The same over a WebSocket and plain IRC
+    for server in [MockIrcServer::start(), MockIrcServer::start_tcp()] {
+        let (tx, rx) = std::sync::mpsc::channel();
+        let mut twitch_irc = TwitchIRC::with_url(server.url(), "s9tpepper_", "oauth:abc123", "client", &[], tx);
+        assert_eq!(server.connections(), 1);
+
+        // Twitch asks clients to reconnect before it restarts a server
+        server.send(":tmi.twitch.tv RECONNECT");
+        read_until(&mut twitch_irc, &rx, |message| {
+            matches!(
+                message,
+                TwitchMessage::ConnectionStatusMessage { message } if message.state == ConnectionState::Disconnected
+            )
+        });
+        assert_eq!(server.connections(), 2);
+
+        // A dropped connection is reconnected too
+        server.disconnect();
+        read_until(&mut twitch_irc, &rx, |message| {
+            matches!(
+                message,
+                TwitchMessage::ConnectionStatusMessage { message } if message.state == ConnectionState::Disconnected
+            )
+        });
+        assert_eq!(server.connections(), 3);
+    }
+}
+
+#[test]
+fn test_mock_server_read_only() {
+    use super::mock_irc::MockIrcServer;
+
+    assert_eq!(get_anonymous_nickname(42), "justinfan10042");
+
+    let server = MockIrcServer::start();
+    let (tx, _rx) = std::sync::mpsc::channel();
+    let nickname = get_anonymous_nickname(42);
+    let channels = vec!["dallas".to_string()];
+    let mut twitch_irc = TwitchIRC::with_url(server.url(), &nickname, "", "", &channels, tx);
+    twitch_irc.read_only = true;
+
+    server.wait_for("JOIN");
+    assert_eq!(
+        server.received(),
+        vec![
+            "CAP REQ :twitch.tv/membership twitch.tv/tags twitch.tv/commands",
+            "NICK justinfan10042",
+            "JOIN #dallas",
+        ]
+    );
+
+    // Nothing is sent while read-only, Twitch would drop it anyway
+    twitch_irc.send_privmsg("#dallas", "hello");
+    server.send("PING :tmi.twitch.tv");
+    for _ in 0..5 {
+        twitch_irc.read_messages();
+    }
+    server.wait_for("PONG");
+    assert!(!server.received().iter().any(|line| line.starts_with("PRIVMSG")));
+}
+
+#[test]
+fn test_mock_server_rate_limit() {
+    use super::mock_irc::MockIrcServer;
+
+    let server = MockIrcServer::start();
+    let (tx, rx) = std::sync::mpsc::channel();
+    let mut twitch_irc = TwitchIRC::with_url(server.url(), "s9tpepper_", "oauth:abc123", "client", &[], tx);
+
+    let is_delayed = |message: &ChannelMessages| {
+        matches!(
+            message,
+            ChannelMessages::TwitchMessage(TwitchMessage::NoticeMessage { message }) if message.msg_id == "message_delayed"
+        )
+    };
+
+    // Announcements push to the shared queue, the connection sends them
+    let send_queue = Arc::new(Mutex::new(SendQueue::default()));
+    twitch_irc.share_send_queue(send_queue.clone());
+    let _ = send_queue
+        .lock()
+        .unwrap()
+        .push("#s9tpepper_", "announcement", None, Instant::now());
+    let _ = twitch_irc.flush_send_queue();
+    server.wait_for("PRIVMSG #s9tpepper_ :announcement");
+
+    for index in 1..USER_MESSAGE_LIMIT {
+        twitch_irc.send_privmsg("#s9tpepper_", &format!("message {index}"));
+    }
+    assert!(!rx.try_iter().any(|message| is_delayed(&message)));
+
+    twitch_irc.send_privmsg("#s9tpepper_", "one too many");
+    assert!(rx.try_iter().any(|message| is_delayed(&message)));
+}
+
+#[test]
+fn test_mock_server_bot_account() {
+    use super::mock_irc::MockIrcServer;
+
+    let server = MockIrcServer::start();
+    let bot_server = MockIrcServer::start();
+    let (tx, rx) = std::sync::mpsc::channel();
+    let mut twitch_irc = TwitchIRC::with_url(server.url(), "s9tpepper_", "oauth:abc123", "client", &[], tx);
+
+    let credentials = BotCredentials {
+        name: "sergeant_bot".to_string(),
+        oauth_token: "oauth:bot456".to_string(),
+        client_id: "bot_client".to_string(),
+    };
+    twitch_irc.bot = Some(BotConnection::connect(
+        bot_server.url(),
+        &credentials,
+        &twitch_irc.channels,
+    ));
+
+    bot_server.wait_for("JOIN #s9tpepper_");
+    assert_eq!(bot_server.wait_for("PASS"), "PASS oauth:bot456");
+    assert_eq!(bot_server.wait_for("NICK"), "NICK sergeant_bot");
+
+    // Replies go out as the bot, the broadcaster's connection only reads
+    twitch_irc.send_privmsg("#s9tpepper_", "hello from the bot");
+    bot_server.wait_for("PRIVMSG #s9tpepper_ :hello from the bot");
+    assert!(!server.received().iter().any(|line| line.starts_with("PRIVMSG")));
+
+    // The bot's messages come back through chat, so they aren't displayed twice
+    twitch_irc.display_msg("#s9tpepper_", "hello from the bot");
+    assert!(rx.try_iter().all(|message| !matches!(
+        message,
+        ChannelMessages::TwitchMessage(TwitchMessage::PrivMessage { .. })
+    )));
+
+    bot_server.send("PING :tmi.twitch.tv");
+    bot_server.wait_for("PONG :tmi.twitch.tv");
 }
@@ -0,0 +1,247 @@
+// A stand-in for Twitch's IRC server so TwitchIRC can be tested without network access. It speaks
+// IRC over a WebSocket or plain TCP, answers the CAP/PASS/NICK/JOIN handshake like Twitch does, records every line the client sends
+// and lets tests script the lines Twitch would send back
+
+use std::{
+    net::TcpListener,
+    sync::{
+        atomic::{AtomicUsize, Ordering},
+        mpsc::{channel, Receiver, Sender},
+        Arc, Mutex,
+    },
+    thread,
+    time::{Duration, Instant},
+};
+
+use super::{
+    irc::MESSAGE_DELIMITER,
+    transport::{IrcRead, IrcTransport, LineTransport, WebSocketTransport},
+};
+
+const WAIT_TIMEOUT: Duration = Duration::from_secs(5);
+const POLL_INTERVAL: Duration = Duration::from_millis(10);
+
+enum ServerEvent {
+    Send(String),
+    Disconnect,
+}
+
+pub struct MockIrcServer {
+    url: String,
+    received: Arc<Mutex<Vec<String>>>,
+    connections: Arc<AtomicUsize>,
+    current: Arc<Mutex<Option<Sender<ServerEvent>>>>,
+}
+
+impl MockIrcServer {
+    /// An IRC WebSocket server, like irc-ws.chat.twitch.tv
+    pub fn start() -> Self {
+        MockIrcServer::listen("ws", true)
+    }
+
+    /// A plain IRC server, like irc.chat.twitch.tv without the TLS
+    pub fn start_tcp() -> Self {
+        MockIrcServer::listen("irc", false)
+    }
+
+    /// Listens on a free local port, every connection replaces the previous one like a reconnect
+    fn listen(scheme: &str, websocket: bool) -> Self {
+        let listener = TcpListener::bind("127.0.0.1:0").expect("Could not bind the mock IRC server");
+        let url = format!("{scheme}://{}", listener.local_addr().unwrap());
+
+        let server = MockIrcServer {
+            url,
+            received: Arc::new(Mutex::new(vec![])),
+            connections: Arc::new(AtomicUsize::new(0)),
+            current: Arc::new(Mutex::new(None)),
+        };
+
+        let received = server.received.clone();
+        let connections = server.connections.clone();
+        let current = server.current.clone();
+
+        thread::spawn(move || {
+            for stream in listener.incoming().flatten() {
+                let (events_tx, events_rx) = channel::<ServerEvent>();
+                *current.lock().unwrap() = Some(events_tx);
+                connections.fetch_add(1, Ordering::SeqCst);
+
+                let received = received.clone();
+                thread::spawn(move || {
+                    let transport: Box<dyn IrcTransport> = if websocket {
+                        let Ok(socket) = tungstenite::accept(stream) else {
+                            return;
+                        };
+
+                        // Reads time out so scripted lines go out while the client is quiet
+                        let _ = socket.get_ref().set_read_timeout(Some(POLL_INTERVAL));
+
+                        Box::new(WebSocketTransport::new(socket))
+                    } else {
+                        let _ = stream.set_read_timeout(Some(POLL_INTERVAL));
+
+                        Box::new(LineTransport::new(stream))
+                    };
+
+                    handle_connection(transport, events_rx, received);
+                });
+            }
+        });
+
+        server
+    }
+
+    pub fn url(&self) -> &str {
+        &self.url
+    }
+
+    /// The number of connections made so far, goes up by one for every reconnect
+    pub fn connections(&self) -> usize {
+        self.connections.load(Ordering::SeqCst)
+    }
+
+    /// Every line the clients sent, in order
+    pub fn received(&self) -> Vec<String> {
+        self.received.lock().unwrap().clone()
+    }
+
+    /// Sends a raw IRC line to the current connection, as if Twitch sent it
+    pub fn send(&self, line: &str) {
+        self.send_event(ServerEvent::Send(line.to_string()));
+    }
+
+    /// Closes the current connection from the server side
+    pub fn disconnect(&self) {
+        self.send_event(ServerEvent::Disconnect);
+    }
+
+    /// Waits for the client to send a line starting with prefix, panics if it never does
+    pub fn wait_for(&self, prefix: &str) -> String {
+        let started = Instant::now();
+
+        loop {
+            let line = self.received().into_iter().find(|line| line.starts_with(prefix));
+            if let Some(line) = line {
+                return line;
+            }
+
+            assert!(
+                started.elapsed() < WAIT_TIMEOUT,
+                "The mock IRC server never received {prefix:?}, got {:?}",
+                self.received()
+            );
+
+            thread::sleep(POLL_INTERVAL);
+        }
+    }
+
+    fn send_event(&self, event: ServerEvent) {
+        // A plain TCP client can finish connecting before the server has accepted it
+        let started = Instant::now();
+        while self.current.lock().unwrap().is_none() {
+            assert!(
+                started.elapsed() < WAIT_TIMEOUT,
+                "No client connected to the mock IRC server"
+            );
+
+            thread::sleep(POLL_INTERVAL);
+        }
+
+        let current = self.current.lock().unwrap();
+        let events_tx = current.as_ref().expect("No client connected to the mock IRC server");
+
+        events_tx.send(event).expect("The mock IRC connection is gone");
+    }
+}
+
+/// The lines Twitch answers a client line with during the handshake
+fn get_handshake_replies(line: &str, nickname: &mut String) -> Vec<String> {
+    let (command, parameters) = line.split_once(' ').unwrap_or((line, ""));
+
+    match command {
+        "CAP" => {
+            let capabilities = parameters.split_once(':').map(|(_, caps)| caps).unwrap_or_default();
+
+            vec![format!(":tmi.twitch.tv CAP * ACK :{capabilities}")]
+        }
+
+        "NICK" => {
+            *nickname = parameters.to_string();
+
+            vec![
+                format!(":tmi.twitch.tv 001 {nickname} :Welcome, GLHF!"),
+                format!(":tmi.twitch.tv 376 {nickname} :>"),
+            ]
+        }
+
+        "JOIN" => parameters
+            .split(',')
+            .map(|channel| format!(":{nickname}!{nickname}@{nickname}.tmi.twitch.tv JOIN {channel}"))
+            .collect(),
+
+        _ => vec![],
+    }
+}
+
+fn handle_connection(
+    mut transport: Box<dyn IrcTransport>,
+    events_rx: Receiver<ServerEvent>,
+    received: Arc<Mutex<Vec<String>>>,
+) {
+    let mut nickname = String::new();
+    let mut joined = false;
+
+    loop {
+        // Like Twitch, nothing is sent before the client has joined its channels
+        if joined {
+            while let Ok(event) = events_rx.try_recv() {
+                match event {
+                    ServerEvent::Send(line) => {
+                        let _ = transport.send_line(&line);
+                    }
+
+                    ServerEvent::Disconnect => {
+                        transport.close();
+
+                        return;
+                    }
+                }
+            }
+        }
+
+        let lines = match transport.read_lines() {
+            Ok(IrcRead::Lines(lines)) => lines,
+            Ok(IrcRead::Timeout) => continue,
+            Ok(IrcRead::Closed) | Err(_) => return,
+        };
+
+        for line in lines {
+            received.lock().unwrap().push(line.clone());
+            joined |= line.starts_with("JOIN ");
+
+            let replies = get_handshake_replies(&line, &mut nickname);
+            if !replies.is_empty() {
+                let _ = transport.send_line(&replies.join(MESSAGE_DELIMITER));
+            }
+        }
+    }
+}
+
+#[test]
+fn test_get_handshake_replies() {
+    let mut nickname = String::new();
+
+    assert_eq!(
+        get_handshake_replies("CAP REQ :twitch.tv/tags twitch.tv/commands", &mut nickname),
+        vec![":tmi.twitch.tv CAP * ACK :twitch.tv/tags twitch.tv/commands"]
+    );
+    assert!(get_handshake_replies("PASS oauth:abc", &mut nickname).is_empty());
+    assert_eq!(get_handshake_replies("NICK dallas", &mut nickname).len(), 2);
+    assert_eq!(
+        get_handshake_replies("JOIN #dallas,#ronni", &mut nickname),
+        vec![
+            ":dallas!dallas@dallas.tmi.twitch.tv JOIN #dallas",
+            ":dallas!dallas@dallas.tmi.twitch.tv JOIN #ronni"
+        ]
+    );
+}
@@ -1,16 +1,23 @@
 use serde::{Deserialize, Serialize};
 
-use crate::eventsub::structs::SubscriptionEvent;
+use crate::{commands::counter::Counter, eventsub::structs::SubscriptionEvent};
 
 use self::{announcements::Announcement, parse::TwitchMessage, pubsub::MessageData};
 
 pub mod announcements;
 pub mod api;
+pub mod asset_cache;
+pub mod bot;
+pub mod emote_loader;
+pub mod emote_providers;
 pub mod irc;
 pub mod message;
 pub mod messages;
 pub mod parse;
 pub mod pubsub;
+pub mod ratelimit;
+pub mod transport;
+pub mod usernotice;
 
 #[derive(Clone, Debug, Deserialize, Serialize)]
 pub enum ChannelMessages {
@@ -18,7 +25,11 @@ pub enum ChannelMessages {
     Announcement(Announcement),
     TwitchMessage(TwitchMessage),
     Notifications(Box<SubscriptionEvent>),
+    Counter(Counter),
 }
 
 #[cfg(test)]
 pub mod fixtures;
+
+#[cfg(test)]
+pub mod mock_irc;
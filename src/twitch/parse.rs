@@ -1,13 +1,5 @@
-use std::{
-    collections::HashMap,
-    error::Error,
-    fs,
-    path::PathBuf,
-    sync::{OnceLock, RwLock},
-    time::SystemTime,
-};
+use std::{collections::HashMap, error::Error, sync::OnceLock, time::SystemTime};
 
-use base64::prelude::*;
 use ratatui::{
     buffer::Buffer,
     layout::Rect,
@@ -17,19 +9,31 @@ use ratatui::{
 };
 use serde::{Deserialize, Serialize};
 use time::OffsetDateTime;
+use unicode_segmentation::UnicodeSegmentation;
+use unicode_width::UnicodeWidthStr;
 
 use crate::{
-    tui::{check_for_chat_commands, check_for_irc_actions, MessageParts, Symbol},
-    utils::{get_data_directory, unescape},
+    tui::{
+        check_for_bits_actions, check_for_chat_commands, check_for_irc_actions, image_protocol::get_image_protocol,
+        MessageParts, Symbol,
+    },
+    utils::unescape,
 };
 
-use super::{api::TwitchApiResponse, irc::TwitchIrcClient};
+use super::{
+    api::TwitchApiResponse,
+    emote_loader::get_emote_loader,
+    emote_providers::EmoteRegistry,
+    irc::TwitchIrcClient,
+    usernotice::{
+        AnnouncementNotice, BitsBadgeTierNotice, CharityDonationNotice, GiftPaidUpgradeNotice, NoticeTags,
+        PrimePaidUpgradeNotice, SubGiftNotice, SubMysteryGiftNotice, SubNotice, UserNotice, ViewerMilestoneNotice,
+    },
+};
 
-const ESCAPE: &str = "\x1b";
-const BELL: &str = "\x07";
 const EMOTE_SPACE: u8 = 2;
 
-static EMOTE_CACHE: OnceLock<RwLock<HashMap<String, String>>> = OnceLock::new();
+static GLOBAL_BADGES: OnceLock<Vec<BadgeItem>> = OnceLock::new();
 
 #[derive(Debug, PartialEq, Clone)]
 pub struct Text {
@@ -37,6 +41,13 @@ pub struct Text {
     color: Option<(u8, u8, u8)>,
 }
 
+impl Text {
+    /// The number of terminal cells the grapheme takes up, wide characters like CJK and emoji take two
+    pub fn width(&self) -> usize {
+        self.char.width().max(1)
+    }
+}
+
 #[derive(Debug, PartialEq, Serialize, Deserialize)]
 pub struct Emote {
     emote_id: String,
@@ -48,48 +59,23 @@ pub struct Emote {
 }
 
 impl Emote {
-    pub fn load(&mut self) -> Result<(), Box<dyn Error>> {
-        if self.encoded.is_some() {
-            return Ok(());
+    /// Uses the encoded image once it's loaded, otherwise asks the emote loader to fetch it in the
+    /// background. Until then the emote is drawn as text
+    pub fn load(&mut self) {
+        if self.encoded.is_some() || !get_image_protocol().shows_images() {
+            return;
         }
 
-        let cache = EMOTE_CACHE.get_or_init(|| RwLock::new(HashMap::new()));
-        let cache_read = cache.read().unwrap();
-        if let Some(encoding) = cache_read.get(&self.emote_id) {
-            self.encoded = Some(encoding.to_string());
+        let loader = get_emote_loader();
+        self.encoded = loader.get(&self.emote_id);
 
-            return Ok(());
+        if self.encoded.is_none() {
+            loader.request(&self.emote_id, &self.url);
         }
-        drop(cache_read);
-
-        let response = ureq::get(&self.url).call()?;
-        let length: usize = response.header("content-length").unwrap().parse()?;
-        let mut file_bytes: Vec<u8> = vec![0; length];
-        response.into_reader().read_exact(&mut file_bytes)?;
-
-        // let things = &file_bytes[..4];
-        // panic!("{:?}", things);
-
-        // let img_data = image::load_from_memory(&file_bytes)?;
-        // let mut buffer: Vec<u8> = Vec::new();
-        // // img_data.write_to(&mut Cursor::new(&mut buffer), image::ImageFormat::Png)?;
-        // img_data.write_to(&mut Cursor::new(&mut buffer), image::ImageFormat::Gif)?;
-        // let base64_emote = BASE64_STANDARD.encode(&buffer);
-        let base64_emote = BASE64_STANDARD.encode(&file_bytes);
-
-        let encoded_image = format!(
-            // "{}1337;File=inline=1;height=22px;width=22px;preserveAspectRatio=1;doNotMoveCursor=1:{}{}",
-            "{}1337;File=inline=1;height=22px;width=22px;doNotMoveCursor=1:{}{}",
-            get_emote_prefix(),
-            base64_emote.as_str(),
-            get_emote_suffix()
-        );
-
-        self.encoded = Some(encoded_image.clone());
-        let mut cache_write = cache.write().unwrap();
-        cache_write.insert(self.emote_id.clone(), encoded_image);
+    }
 
-        Ok(())
+    fn is_loaded(&self) -> bool {
+        self.encoded.is_some()
     }
 }
 
@@ -122,6 +108,10 @@ pub struct ChatMessage {
     pub returning_chatter: bool,
     pub subscriber: bool,
     pub moderator: bool,
+    #[serde(default)]
+    pub vip: bool,
+    #[serde(default)]
+    pub broadcaster: bool,
     pub message: String,
     pub color: String,
     pub channel: String,
@@ -133,48 +123,124 @@ pub struct ChatMessage {
     // Set to option so it won't break the chat log deserialization
     pub timestamp: Option<String>,
 
+    #[serde(default)]
+    pub reply_parent: Option<Box<ReplyParent>>,
+
+    /// The amount of bits cheered with the message
+    #[serde(default)]
+    pub bits: Option<u64>,
+
+    /// The chatter's Twitch user id, whispers are sent to it
+    #[serde(default)]
+    pub user_id: String,
+
+    /// The chatter's login, the nickname is their display name which can differ from it
+    #[serde(default)]
+    pub login: String,
+
     #[serde(skip)]
     pub area: Option<Rect>,
 }
 
+/// The reply-parent-* tags, only sent when a PRIVMSG is a reply to another message
+#[derive(Default, Clone, Debug, Serialize, Deserialize)]
+pub struct ReplyParent {
+    pub msg_id: String,
+    pub user_id: String,
+    pub user_login: String,
+    pub display_name: String,
+    pub msg_body: String,
+    pub thread_parent_msg_id: Option<String>,
+    pub thread_parent_user_login: Option<String>,
+}
+
+const REPLY_PREVIEW_LENGTH: usize = 50;
+
+impl ReplyParent {
+    /// The "replying to" line shown above a reply, long messages are shortened
+    pub fn get_context(&self) -> String {
+        let mut body: String = self.msg_body.chars().take(REPLY_PREVIEW_LENGTH).collect();
+        if self.msg_body.chars().count() > REPLY_PREVIEW_LENGTH {
+            body.push('…');
+        }
+
+        format!("↪ replying to @{}: {body}", self.display_name)
+    }
+}
+
 fn default_is_bot() -> bool {
     false
 }
 
 // Place all characters and emote base64s in a vector
 pub fn get_message_symbols(message: &str, emotes: &mut [Emote], color: Option<(u8, u8, u8)>) -> Vec<Symbol> {
-    // Load the base64 encoded emotes
-    emotes.iter_mut().for_each(|e| {
-        e.load().unwrap();
-    });
+    // Start loading the emotes, the ones that aren't ready yet are drawn as text
+    emotes.iter_mut().for_each(|e| e.load());
+
+    // Without image support emotes are shown by name, like :Kappa:
+    segment_message(message, emotes, color)
+        .into_iter()
+        .flat_map(|symbol| match symbol {
+            Symbol::Emote(emote) if !emote.is_loaded() => format!(":{}:", emote.name)
+                .graphemes(true)
+                .map(|grapheme| {
+                    Symbol::Text(Text {
+                        char: grapheme.to_string(),
+                        color,
+                    })
+                })
+                .collect(),
+            symbol => vec![symbol],
+        })
+        .collect()
+}
 
-    // Place all characters and emote base64s in a vector
+/// Splits a message into grapheme clusters and emotes. Twitch emote ranges count unicode
+/// code points, so each range is mapped onto the graphemes it starts and ends in
+fn segment_message(message: &str, emotes: &[Emote], color: Option<(u8, u8, u8)>) -> Vec<Symbol> {
     let mut symbols: Vec<Symbol> = vec![];
-    let msg_length = message.len();
+    let mut code_point = 0;
+    let mut emote_end: Option<usize> = None;
 
-    let mut cursor = 0;
-    'outer: for i in 0..msg_length {
-        if i < cursor {
-            continue;
+    for grapheme in message.graphemes(true) {
+        let grapheme_start = code_point;
+        code_point += grapheme.chars().count();
+
+        // Skip the rest of the graphemes covered by the last emote
+        if let Some(end) = emote_end {
+            if grapheme_start <= end {
+                continue;
+            }
+
+            emote_end = None;
         }
 
-        for emote in emotes.iter_mut() {
-            if emote.start == i {
-                let emote_length = emote.end - emote.start;
-                symbols.push(Symbol::Emote(emote.clone()));
-                cursor += emote_length + 1;
-                continue 'outer;
+        let emote = emotes
+            .iter()
+            .find(|emote| emote.start >= grapheme_start && emote.start < code_point);
+
+        if let Some(emote) = emote {
+            let mut emote = emote.clone();
+
+            // Twitch emotes don't come with a name, it's the text the emote replaces
+            if emote.name.is_empty() {
+                emote.name = message
+                    .chars()
+                    .skip(emote.start)
+                    .take((emote.end + 1).saturating_sub(emote.start))
+                    .collect();
             }
+
+            emote_end = Some(emote.end);
+            symbols.push(Symbol::Emote(emote));
+
+            continue;
         }
 
-        let temp = message.chars().nth(i).unwrap_or(' ').to_string();
-        let c: &str = temp.as_str();
         symbols.push(Symbol::Text(Text {
-            char: c.to_string(),
+            char: grapheme.to_string(),
             color,
         }));
-
-        cursor += 1;
     }
 
     symbols
@@ -300,14 +366,16 @@ pub fn get_lines(symbols: &[Symbol], area: &Rect) -> Vec<Vec<MessageParts>> {
 
     message_parts.iter().enumerate().for_each(|(ndx, part)| {
         let section_length = match part {
-            MessageParts::Text(word) => word.len(),
+            MessageParts::Text(word) => get_symbols_width(word),
             MessageParts::Emote(_) => EMOTE_SPACE as usize,
         };
 
         let next_line_length = line_length + section_length;
         if next_line_length > (area.width - 1) as usize {
-            lines.push(line.clone());
-            line.clear();
+            if !line.is_empty() {
+                lines.push(line.clone());
+                line.clear();
+            }
 
             line_length = section_length;
         } else {
@@ -318,9 +386,9 @@ pub fn get_lines(symbols: &[Symbol], area: &Rect) -> Vec<Vec<MessageParts>> {
         // if it is, split the section into multiple lines
         if let MessageParts::Text(word) = part {
             if section_length >= area.width.into() {
-                let chunks = word.chunks((area.width - 2).into());
+                let chunks = get_width_chunks(word, (area.width - 2).into());
                 let last_index = chunks.len() - 1;
-                chunks.enumerate().for_each(|(index, chunk)| {
+                chunks.iter().enumerate().for_each(|(index, chunk)| {
                     let mut symbols: Vec<Symbol> = vec![];
                     chunk.iter().for_each(|c| {
                         symbols.push(c.clone());
@@ -335,6 +403,8 @@ pub fn get_lines(symbols: &[Symbol], area: &Rect) -> Vec<Vec<MessageParts>> {
                         lines.push(line.clone());
                         line.clear();
                     } else {
+                        // The rest of the line starts after the last chunk
+                        line_length = get_symbols_width(&symbols);
                         line.push(MessageParts::Text(symbols));
                     }
                 });
@@ -370,6 +440,41 @@ pub fn get_lines(symbols: &[Symbol], area: &Rect) -> Vec<Vec<MessageParts>> {
     lines
 }
 
+fn get_symbols_width(symbols: &[Symbol]) -> usize {
+    symbols
+        .iter()
+        .map(|symbol| match symbol {
+            Symbol::Text(text) => text.width(),
+            Symbol::Emote(_) => EMOTE_SPACE as usize,
+        })
+        .sum()
+}
+
+// Splits a word that is too wide for a line into chunks that fit, without splitting wide characters
+fn get_width_chunks(word: &[Symbol], max_width: usize) -> Vec<Vec<Symbol>> {
+    let mut chunks: Vec<Vec<Symbol>> = vec![];
+    let mut chunk: Vec<Symbol> = vec![];
+    let mut chunk_width = 0;
+
+    for symbol in word {
+        let symbol_width = get_symbols_width(std::slice::from_ref(symbol));
+        if chunk_width + symbol_width > max_width && !chunk.is_empty() {
+            chunks.push(chunk.clone());
+            chunk.clear();
+            chunk_width = 0;
+        }
+
+        chunk.push(symbol.clone());
+        chunk_width += symbol_width;
+    }
+
+    if !chunk.is_empty() {
+        chunks.push(chunk);
+    }
+
+    chunks
+}
+
 pub fn get_screen_lines(lines: &mut [Vec<MessageParts>], area: &Rect) -> Vec<Vec<MessageParts>> {
     if lines.len() > area.height.into() {
         let line_limit = area.height.saturating_sub(1);
@@ -403,15 +508,24 @@ pub fn write_to_buffer(lines: &mut [Vec<MessageParts>], buf: &mut Buffer, cursor
                                 return;
                             }
 
+                            let width = character.width() as u16;
                             buf.cell_mut((cursor.x, cursor.y)).map(|cell| {
                                 let (r, g, b) = character.color.unwrap_or((255, 255, 255));
                                 let rgb = Color::Rgb(r, g, b);
 
-                                cursor.x += 1;
                                 cell.reset();
 
                                 cell.set_symbol(&character.char).set_fg(rgb)
                             });
+
+                            // Wide characters cover the cells after them
+                            for offset in 1..width {
+                                if let Some(cell) = buf.cell_mut((cursor.x + offset, cursor.y)) {
+                                    cell.reset();
+                                }
+                            }
+
+                            cursor.x += width;
                         }
                         Symbol::Emote(_) => {}
                     });
@@ -423,7 +537,14 @@ pub fn write_to_buffer(lines: &mut [Vec<MessageParts>], buf: &mut Buffer, cursor
                         // let _ = terminal.backend_mut().clear_region(backend::ClearType::CurrentLine);
                         let encoded = emote.encoded.clone().unwrap_or_default();
 
-                        buf.cell_mut((cursor.x, cursor.y)).map(|cell| cell.set_symbol(&encoded));
+                        buf.cell_mut((cursor.x, cursor.y)).map(|cell| {
+                            let cell = cell.set_symbol(&encoded);
+
+                            match get_image_protocol().get_foreground(&emote.emote_id) {
+                                Some(foreground) => cell.set_fg(foreground),
+                                None => cell,
+                            }
+                        });
                         buf.cell_mut((cursor.x + 1, cursor.y)).map(|cell| cell.set_skip(true));
 
                         cursor.x += EMOTE_SPACE as u16;
@@ -517,6 +638,129 @@ impl Widget for &mut RedeemMessage {
     }
 }
 
+impl Widget for &mut NoticeMessage {
+    fn render(self, area: Rect, buf: &mut Buffer) {
+        let mut cursor = RenderCursor {
+            x: area.left(),
+            y: area.bottom(),
+        };
+
+        // Render server notices in gray
+        let symbols: Vec<Symbol> = get_message_symbols(&self.message, &mut [], Some((128, 128, 128)));
+        let mut lines: Vec<Vec<MessageParts>> = get_lines(&symbols, &area);
+
+        cursor.x = area.left();
+        cursor.y = cursor.y.saturating_sub(lines.len() as u16);
+
+        let mut screen_lines = get_screen_lines(&mut lines, &area);
+
+        write_to_buffer(&mut screen_lines, buf, &mut cursor);
+
+        self.area = Some(Rect {
+            x: 0,
+            y: cursor.y,
+            width: area.width,
+            height: lines.len() as u16,
+        });
+    }
+}
+
+impl Widget for &mut WhisperMessage {
+    fn render(self, area: Rect, buf: &mut Buffer) {
+        let mut cursor = RenderCursor {
+            x: area.left(),
+            y: area.bottom(),
+        };
+
+        // Sent whispers are gray and name who they went to
+        let (name, color) = if self.outgoing {
+            (format!("→ {}: ", self.to_login), (128, 128, 128))
+        } else {
+            (format!("{}: ", self.display_name), get_nickname_color(&self.color))
+        };
+        let message_color = if self.outgoing { Some(color) } else { None };
+
+        let mut symbols: Vec<Symbol> = get_message_symbols(&name, &mut [], Some(color));
+        symbols.extend(get_message_symbols(&self.message, &mut [], message_color));
+        let mut lines: Vec<Vec<MessageParts>> = get_lines(&symbols, &area);
+
+        // The whispers pane is small, long whispers are cut off at the top of the pane
+        let mut screen_lines = get_screen_lines(&mut lines, &area);
+
+        cursor.x = area.left();
+        cursor.y = cursor.y.saturating_sub(screen_lines.len() as u16);
+
+        write_to_buffer(&mut screen_lines, buf, &mut cursor);
+
+        self.area = Some(Rect {
+            x: 0,
+            y: cursor.y,
+            width: area.width,
+            height: screen_lines.len() as u16,
+        });
+    }
+}
+
+impl Widget for &mut ConnectionStatus {
+    fn render(self, area: Rect, buf: &mut Buffer) {
+        let mut cursor = RenderCursor {
+            x: area.left(),
+            y: area.bottom(),
+        };
+
+        let color = match self.state {
+            ConnectionState::Connected => (0, 200, 0),
+            ConnectionState::Connecting => (255, 179, 38),
+            ConnectionState::Disconnected => (220, 20, 60),
+        };
+
+        let symbols: Vec<Symbol> = get_message_symbols(&self.message, &mut [], Some(color));
+        let mut lines: Vec<Vec<MessageParts>> = get_lines(&symbols, &area);
+
+        cursor.x = area.left();
+        cursor.y = cursor.y.saturating_sub(lines.len() as u16);
+
+        let mut screen_lines = get_screen_lines(&mut lines, &area);
+
+        write_to_buffer(&mut screen_lines, buf, &mut cursor);
+
+        self.area = Some(Rect {
+            x: 0,
+            y: cursor.y,
+            width: area.width,
+            height: lines.len() as u16,
+        });
+    }
+}
+
+impl Widget for &mut ClearChat {
+    fn render(self, area: Rect, buf: &mut Buffer) {
+        let mut cursor = RenderCursor {
+            x: area.left(),
+            y: area.bottom(),
+        };
+
+        // Render timeouts and bans in gray, same as server notices
+        let description = self.description();
+        let symbols: Vec<Symbol> = get_message_symbols(&description, &mut [], Some((128, 128, 128)));
+        let mut lines: Vec<Vec<MessageParts>> = get_lines(&symbols, &area);
+
+        cursor.x = area.left();
+        cursor.y = cursor.y.saturating_sub(lines.len() as u16);
+
+        let mut screen_lines = get_screen_lines(&mut lines, &area);
+
+        write_to_buffer(&mut screen_lines, buf, &mut cursor);
+
+        self.area = Some(Rect {
+            x: 0,
+            y: cursor.y,
+            width: area.width,
+            height: lines.len() as u16,
+        });
+    }
+}
+
 impl ChatMessage {
     fn get_symbols(&mut self) -> Vec<Symbol> {
         let mut symbols: Vec<Symbol> = get_message_symbols(&self.message, &mut self.emotes, None);
@@ -551,8 +795,9 @@ impl ChatMessage {
             )
         });
 
-        // add badges to front of message
-        self.badges.iter().for_each(|badge| {
+        // add badges to front of message, badges that are still loading are left out
+        self.badges.iter_mut().for_each(|badge| badge.load());
+        self.badges.iter().filter(|badge| badge.is_loaded()).for_each(|badge| {
             symbols.insert(0, Symbol::Emote(badge.clone()));
         });
 
@@ -584,6 +829,15 @@ impl Widget for &mut ChatMessage {
         line_area.width = if needs_borders { area.width - 4 } else { area.width };
         let mut screen_lines: Vec<Vec<MessageParts>> = get_lines(&symbols, &line_area);
 
+        // Show what the message is replying to on its own line above the message
+        if let Some(reply_parent) = &self.reply_parent {
+            let reply_symbols = get_message_symbols(&reply_parent.get_context(), &mut [], Some((128, 128, 128)));
+            let mut reply_lines = get_lines(&reply_symbols, &line_area);
+
+            reply_lines.append(&mut screen_lines);
+            screen_lines = reply_lines;
+        }
+
         let y_pos = cursor.y.saturating_sub(screen_lines.len() as u16);
         cursor.x = if needs_borders { area.left() + 1 } else { area.left() };
         cursor.y = if needs_borders { y_pos.saturating_sub(1) } else { y_pos };
@@ -678,13 +932,244 @@ impl Widget for &mut ChatMessage {
 pub enum TwitchMessage {
     ClearMessage { message: ClearMessage },
     ClearMessageByUser { message: ClearMessageByUser },
+    ClearChatMessage { message: ClearChat },
     RedeemMessage { message: RedeemMessage },
     RaidMessage { message: RaidMessage },
+    SubNotice { message: Box<SubNotice> },
+    ResubNotice { message: Box<SubNotice> },
+    SubGiftNotice { message: Box<SubGiftNotice> },
+    SubMysteryGiftNotice { message: Box<SubMysteryGiftNotice> },
+    GiftPaidUpgradeNotice { message: Box<GiftPaidUpgradeNotice> },
+    PrimePaidUpgradeNotice { message: Box<PrimePaidUpgradeNotice> },
+    AnnouncementNotice { message: Box<AnnouncementNotice> },
+    BitsBadgeTierNotice { message: Box<BitsBadgeTierNotice> },
+    ViewerMilestoneNotice { message: Box<ViewerMilestoneNotice> },
+    CharityDonationNotice { message: Box<CharityDonationNotice> },
     PrivMessage { message: ChatMessage },
+    NoticeMessage { message: NoticeMessage },
+    UserStateMessage { message: UserState },
+    GlobalUserStateMessage { message: GlobalUserState },
+    RoomStateMessage { message: RoomState },
+    WhisperMessage { message: WhisperMessage },
+    HostTargetMessage { message: HostTarget },
+    JoinMessage { message: Membership },
+    PartMessage { message: Membership },
+    ReconnectMessage { message: String },
+    ConnectionStatusMessage { message: ConnectionStatus },
     PingMessage { message: String },
     UnknownMessage { message: String },
 }
 
+impl TwitchMessage {
+    /// The #channel a message was sent to, None for messages that aren't tied to a channel
+    pub fn channel(&self) -> Option<&str> {
+        let channel = match self {
+            TwitchMessage::PrivMessage { message } => &message.channel,
+            TwitchMessage::RaidMessage { message } => &message.channel,
+            TwitchMessage::NoticeMessage { message } => &message.channel,
+            TwitchMessage::ClearChatMessage { message } => &message.channel,
+            TwitchMessage::UserStateMessage { message } => &message.channel,
+            TwitchMessage::RoomStateMessage { message } => &message.channel,
+            TwitchMessage::HostTargetMessage { message } => &message.channel,
+            TwitchMessage::JoinMessage { message } | TwitchMessage::PartMessage { message } => &message.channel,
+            TwitchMessage::SubNotice { message } | TwitchMessage::ResubNotice { message } => &message.notice.channel,
+            TwitchMessage::SubGiftNotice { message } => &message.notice.channel,
+            TwitchMessage::SubMysteryGiftNotice { message } => &message.notice.channel,
+            TwitchMessage::GiftPaidUpgradeNotice { message } => &message.notice.channel,
+            TwitchMessage::PrimePaidUpgradeNotice { message } => &message.notice.channel,
+            TwitchMessage::AnnouncementNotice { message } => &message.notice.channel,
+            TwitchMessage::BitsBadgeTierNotice { message } => &message.notice.channel,
+            TwitchMessage::ViewerMilestoneNotice { message } => &message.notice.channel,
+            TwitchMessage::CharityDonationNotice { message } => &message.notice.channel,
+            _ => "",
+        };
+
+        if channel.is_empty() {
+            None
+        } else {
+            Some(channel)
+        }
+    }
+}
+
+#[derive(Default, Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub enum ConnectionState {
+    #[default]
+    Connecting,
+    Connected,
+    Disconnected,
+}
+
+/// Not sent by Twitch, reports the state of the IRC connection to the TUI
+#[derive(Default, Clone, Debug, Serialize, Deserialize)]
+pub struct ConnectionStatus {
+    pub state: ConnectionState,
+    pub message: String,
+    #[serde(skip)]
+    pub area: Option<Rect>,
+}
+
+/// NOTICE, sent by the server for things like failed commands or chat mode changes
+#[derive(Default, Clone, Debug, Serialize, Deserialize)]
+pub struct NoticeMessage {
+    pub channel: String,
+    pub msg_id: String,
+    pub target_user_id: Option<String>,
+    pub message: String,
+    #[serde(skip)]
+    pub area: Option<Rect>,
+}
+
+/// USERSTATE, sent after joining a channel and after the bot sends a PRIVMSG
+#[derive(Default, Clone, Debug, Serialize, Deserialize)]
+pub struct UserState {
+    pub channel: String,
+    pub badge_info: Vec<Badge>,
+    pub badges: Vec<Badge>,
+    pub color: Option<String>,
+    pub display_name: String,
+    pub emote_sets: Vec<String>,
+    pub id: Option<String>,
+    pub moderator: bool,
+    pub subscriber: bool,
+    pub turbo: bool,
+    pub user_type: String,
+}
+
+impl UserState {
+    /// Broadcasters and VIPs get the same rate limits as moderators
+    pub fn is_moderator(&self) -> bool {
+        self.moderator
+            || self
+                .badges
+                .iter()
+                .any(|badge| matches!(badge.set_id.as_str(), "broadcaster" | "moderator" | "vip"))
+    }
+}
+
+/// GLOBALUSERSTATE, sent once after a successful login
+#[derive(Default, Clone, Debug, Serialize, Deserialize)]
+pub struct GlobalUserState {
+    pub badge_info: Vec<Badge>,
+    pub badges: Vec<Badge>,
+    pub color: Option<String>,
+    pub display_name: String,
+    pub emote_sets: Vec<String>,
+    pub turbo: bool,
+    pub user_id: String,
+    pub user_type: String,
+}
+
+/// ROOMSTATE, sent in full when joining a channel and partially
+/// (only the changed setting) whenever a chat mode changes
+#[derive(Default, Clone, Debug, Serialize, Deserialize)]
+pub struct RoomState {
+    pub channel: String,
+    pub room_id: String,
+    pub emote_only: Option<bool>,
+    /// -1 when disabled, otherwise the minimum follow time in minutes
+    pub followers_only: Option<i64>,
+    pub r9k: Option<bool>,
+    /// Seconds a chatter has to wait between messages
+    pub slow: Option<u64>,
+    pub subs_only: Option<bool>,
+}
+
+impl RoomState {
+    /// Apply a partial ROOMSTATE update on top of the current state
+    pub fn merge(&mut self, update: RoomState) {
+        if !update.room_id.is_empty() {
+            self.room_id = update.room_id;
+        }
+
+        self.emote_only = update.emote_only.or(self.emote_only);
+        self.followers_only = update.followers_only.or(self.followers_only);
+        self.r9k = update.r9k.or(self.r9k);
+        self.slow = update.slow.or(self.slow);
+        self.subs_only = update.subs_only.or(self.subs_only);
+    }
+}
+
+/// CLEARCHAT, sent when the whole chat is cleared or when a single user
+/// is timed out (ban_duration is set) or banned (ban_duration is None)
+#[derive(Default, Clone, Debug, Serialize, Deserialize)]
+pub struct ClearChat {
+    pub channel: String,
+    pub room_id: String,
+    pub target_user_id: Option<String>,
+    pub target_user_login: Option<String>,
+    pub ban_duration: Option<u64>,
+    pub timestamp: String,
+    #[serde(skip)]
+    pub area: Option<Rect>,
+}
+
+impl ClearChat {
+    /// Whether this clears the whole channel rather than a single user
+    pub fn is_full_clear(&self) -> bool {
+        self.target_user_login.is_none() && self.target_user_id.is_none()
+    }
+
+    /// Whether this message was sent by the user that was timed out or banned
+    pub fn targets(&self, message: &ChatMessage) -> bool {
+        if message.channel != self.channel {
+            return false;
+        }
+
+        // Display names can differ from the login, like localized names, so the user id is compared.
+        // Messages without one fall back to their login
+        match (&self.target_user_id, &self.target_user_login) {
+            (Some(user_id), _) if !message.user_id.is_empty() => message.user_id == *user_id,
+            (_, Some(login)) => message.login.eq_ignore_ascii_case(login),
+            _ => self.is_full_clear(),
+        }
+    }
+
+    pub fn description(&self) -> String {
+        match (&self.target_user_login, self.ban_duration) {
+            (Some(login), Some(duration)) => format!("{login} has been timed out for {duration} seconds."),
+            (Some(login), None) => format!("{login} has been banned."),
+            (None, _) => "Chat was cleared by a moderator.".to_string(),
+        }
+    }
+}
+
+#[derive(Default, Clone, Debug, Serialize, Deserialize)]
+pub struct WhisperMessage {
+    pub from_login: String,
+    pub to_login: String,
+    pub display_name: String,
+    pub user_id: String,
+    pub message_id: String,
+    pub thread_id: String,
+    pub color: String,
+    pub badges: Vec<Badge>,
+    pub emotes: Vec<Emote>,
+    pub turbo: bool,
+    pub user_type: String,
+    pub message: String,
+    /// Sent by sergeant instead of received
+    #[serde(default)]
+    pub outgoing: bool,
+    #[serde(skip)]
+    pub area: Option<Rect>,
+}
+
+/// HOSTTARGET, target is None when the channel stops hosting
+#[derive(Default, Clone, Debug, Serialize, Deserialize)]
+pub struct HostTarget {
+    pub channel: String,
+    pub target: Option<String>,
+    pub viewers: Option<u64>,
+}
+
+/// JOIN and PART from the twitch.tv/membership capability
+#[derive(Default, Clone, Debug, Serialize, Deserialize)]
+pub struct Membership {
+    pub channel: String,
+    pub login: String,
+}
+
 #[derive(Clone, Debug, Serialize, Deserialize)]
 pub struct ClearMessageByUser {
     pub display_name: String,
@@ -698,6 +1183,8 @@ pub struct ClearMessage {
 
 #[derive(Default, Clone, Debug, Serialize, Deserialize)]
 pub struct RaidMessage {
+    #[serde(default)]
+    pub channel: String,
     pub r: u8,
     pub g: u8,
     pub b: u8,
@@ -738,16 +1225,54 @@ pub struct BadgeItem {
     versions: Vec<BadgeVersion>,
 }
 
-#[allow(dead_code)]
-#[derive(Serialize, Deserialize, Debug)]
-struct IrcMessage<'a> {
-    parameters: &'a str,
-    channel: &'a str,
-    tags: Vec<(&'a str, &'a str)>,
-    r#type: &'a str,
-    sender: &'a str,
-    raw: &'a str,
-    // message: Option<&'a str>,
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct CheermoteImages {
+    animated: HashMap<String, String>,
+    #[serde(rename = "static")]
+    still: HashMap<String, String>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct CheermoteThemes {
+    dark: CheermoteImages,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct CheermoteTier {
+    min_bits: u64,
+    id: String,
+    images: CheermoteThemes,
+}
+
+/// A cheermote from https://api.twitch.tv/helix/bits/cheermotes, e.g. the Cheer in Cheer100
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct Cheermote {
+    prefix: String,
+    tiers: Vec<CheermoteTier>,
+}
+
+impl Cheermote {
+    /// Returns the highest tier the amount of bits reaches
+    fn get_tier(&self, bits: u64) -> Option<&CheermoteTier> {
+        self.tiers
+            .iter()
+            .filter(|tier| tier.min_bits <= bits)
+            .max_by_key(|tier| tier.min_bits)
+    }
+}
+
+#[allow(dead_code)]
+#[derive(Serialize, Deserialize, Debug)]
+struct IrcMessage<'a> {
+    parameters: &'a str,
+    channel: &'a str,
+    target: &'a str,
+    tags: Vec<(&'a str, &'a str)>,
+    r#type: &'a str,
+    sender: &'a str,
+    login: &'a str,
+    raw: &'a str,
+    // message: Option<&'a str>,
 }
 
 // TODO: Come back to this later for fixing
@@ -764,31 +1289,6 @@ struct IrcMessage<'a> {
 // @badge-info=;badges=;color=#FF4500;display-name=vei_bean;emotes=;flags=;id=4c33fcb0-9337-4e68-b7d0-3a3049ad7cfd;login=vei_bean;mod=0;msg-id=raid;msg-param-displayName=vei_bean;msg-param-login=vei_bean;msg-param-profileImageURL=https://static-cdn.jtvnw.net/jtv_user_pictures/618358c1-993a-4a2d-b0b9-a51d1827c659-profile_image-%s.png;msg-param-viewerCount=1;room-id=961536166;subscriber=0;system-msg=1\sraiders\sfrom\svei_bean\shave\sjoined!;tmi-sent-ts=1708304703515;user-id=624578741;user-type=;vip=0 :tmi.twitch.tv USERNOTICE #s9tpepper_
 //
 
-fn get_encoded_image(url: &str) -> Result<String, Box<dyn Error>> {
-    let response = ureq::get(url).call()?;
-    let length: usize = response.header("content-length").unwrap().parse()?;
-    let mut file_bytes: Vec<u8> = vec![0; length];
-
-    response.into_reader().read_exact(&mut file_bytes)?;
-
-    // let img_data = image::load_from_memory(&file_bytes)?;
-    //
-    // let mut buffer: Vec<u8> = Vec::new();
-    // // img_data.write_to(&mut Cursor::new(&mut buffer), image::ImageFormat::Png)?;
-    // img_data.write_to(&mut Cursor::new(&mut buffer), image::ImageFormat::Gif)?;
-    let base64_emote = BASE64_STANDARD.encode(file_bytes);
-
-    Ok(base64_emote)
-}
-
-fn generate_badge_file(badge_path: PathBuf, version: &BadgeVersion) -> Result<(), Box<dyn Error>> {
-    if let Ok(encoded_image) = get_encoded_image(&version.image_url_1x) {
-        fs::write(badge_path, encoded_image)?;
-    }
-
-    Ok(())
-}
-
 type AsyncResult<T> = Result<T, Box<dyn Error>>;
 pub fn get_badges(token: &str, client_id: &str) -> AsyncResult<Vec<BadgeItem>> {
     // Global badges: https://api.twitch.tv/helix/chat/badges/global
@@ -804,30 +1304,82 @@ pub fn get_badges(token: &str, client_id: &str) -> AsyncResult<Vec<BadgeItem>> {
         .set("Client-Id", client_id)
         .call()?;
 
-    let mut response: TwitchApiResponse<Vec<BadgeItem>> = serde_json::from_reader(response.into_reader())?;
+    let response: TwitchApiResponse<Vec<BadgeItem>> = serde_json::from_reader(response.into_reader())?;
 
-    let data_dir = get_data_directory(Some("badges"))?;
+    // The images are downloaded through the asset cache the first time a badge is shown
+    let _ = GLOBAL_BADGES.set(response.data.clone());
 
-    for badge_item in response.data.iter_mut() {
-        for version in badge_item.versions.iter_mut() {
-            let file_name = format!("{}_{}.txt", badge_item.set_id, version.id);
-            let badge_path = data_dir.join(file_name);
-            if !badge_path.exists() {
-                generate_badge_file(badge_path, version)?;
-            }
+    Ok(response.data)
+}
+
+pub fn parse(message: &str, client: &mut impl TwitchIrcClient) -> Result<TwitchMessage, Box<dyn Error>> {
+    let irc_message = parse_irc_message(message)?;
+
+    match irc_message.r#type {
+        "PRIVMSG" => {
+            let priv_msg = parse_privmsg(irc_message, client);
+
+            Ok(priv_msg)
+        }
+
+        "USERNOTICE" => Ok(parse_usernotice(irc_message)),
+
+        "PING" => {
+            let message: String = irc_message.parameters.to_string();
+            Ok(TwitchMessage::PingMessage { message })
         }
+
+        "CLEARMSG" => Ok(parse_clearmsg(irc_message)),
+
+        "CLEARCHAT" => Ok(parse_clearchat(irc_message)),
+
+        "NOTICE" => Ok(parse_notice(irc_message)),
+
+        "USERSTATE" => Ok(parse_userstate(irc_message)),
+
+        "GLOBALUSERSTATE" => Ok(parse_globaluserstate(irc_message)),
+
+        "ROOMSTATE" => Ok(parse_roomstate(irc_message)),
+
+        "WHISPER" => Ok(parse_whisper(irc_message)),
+
+        "HOSTTARGET" => Ok(parse_hosttarget(irc_message)),
+
+        "JOIN" => Ok(TwitchMessage::JoinMessage {
+            message: parse_membership(irc_message),
+        }),
+
+        "PART" => Ok(TwitchMessage::PartMessage {
+            message: parse_membership(irc_message),
+        }),
+
+        "RECONNECT" => Ok(TwitchMessage::ReconnectMessage {
+            message: irc_message.raw.to_string(),
+        }),
+
+        _ => Err("Unknown message type".into()),
     }
+}
 
-    Ok(response.data)
+/// The USERSTATE in a line read by the bot account, which tells whether it moderates the channel
+pub fn parse_user_state(line: &str) -> Option<UserState> {
+    let irc_message = parse_irc_message(line).ok()?;
+    if irc_message.r#type != "USERSTATE" {
+        return None;
+    }
+
+    match parse_userstate(irc_message) {
+        TwitchMessage::UserStateMessage { message } => Some(message),
+        _ => None,
+    }
 }
 
-pub fn parse(mut message: &str, client: &mut impl TwitchIrcClient) -> Result<TwitchMessage, Box<dyn Error>> {
+fn parse_irc_message(mut message: &str) -> Result<IrcMessage<'_>, Box<dyn Error>> {
     let raw = message;
 
     let mut tags = vec![];
     let mut sender: &str = "";
-    let channel: &str;
-    let parameters: &str;
+    let mut login: &str = "";
 
     // Check if the message contains tags
     if message.starts_with('@') {
@@ -853,6 +1405,7 @@ pub fn parse(mut message: &str, client: &mut impl TwitchIrcClient) -> Result<Twi
         };
 
         sender = left.trim_start_matches(':');
+        login = sender.split('!').next().unwrap_or(sender);
 
         if let Some((_, display_name)) = tags.iter().find(|(tag, _)| *tag == "display-name") {
             sender = display_name;
@@ -861,43 +1414,31 @@ pub fn parse(mut message: &str, client: &mut impl TwitchIrcClient) -> Result<Twi
         message = msg;
     }
 
-    let (r#type, rest) = message.split_once(' ').unwrap_or(("", ""));
-    if rest.starts_with('#') {
-        let (c, p) = rest.split_once(' ').unwrap_or(("", ""));
-        channel = c;
-        parameters = p.strip_prefix(':').unwrap_or(p);
-    } else {
-        channel = "";
-        parameters = rest.strip_prefix(':').unwrap_or(rest);
-    }
+    let (r#type, rest) = message.split_once(' ').unwrap_or((message, ""));
+
+    // The trailing parameter starts with a ':' and can contain spaces
+    let (middle, trailing) = match rest.strip_prefix(':') {
+        Some(trailing) => ("", Some(trailing)),
+        None => match rest.split_once(" :") {
+            Some((middle, trailing)) => (middle, Some(trailing)),
+            None => (rest, None),
+        },
+    };
 
-    let irc_message = IrcMessage {
+    let (target, extra) = middle.split_once(' ').unwrap_or((middle, ""));
+    let channel = if target.starts_with('#') { target } else { "" };
+    let parameters = trailing.unwrap_or(extra);
+
+    Ok(IrcMessage {
         tags,
         sender,
+        login,
         r#type,
         channel,
+        target,
         parameters,
         raw,
-    };
-
-    match r#type {
-        "PRIVMSG" => {
-            let priv_msg = parse_privmsg(irc_message, client);
-
-            Ok(priv_msg)
-        }
-
-        "USERNOTICE" => Ok(parse_usernotice(irc_message)),
-
-        "PING" => {
-            let message: String = irc_message.parameters.to_string();
-            Ok(TwitchMessage::PingMessage { message })
-        }
-
-        "CLEARMSG" => Ok(parse_clearmsg(irc_message)),
-
-        _ => Err("Unknown message type".into()),
-    }
+    })
 }
 
 /// A message tag as defined by [IRCv3.2](http://ircv3.net/specs/core/message-tags-3.2.html).
@@ -909,6 +1450,7 @@ pub struct Tag<'a>(pub &'a str, pub &'a str);
 
 // This Badge struct is used to figure out what BadgeItem to use
 // when parsing through a user's badges
+#[derive(Default, Clone, Debug, PartialEq, Serialize, Deserialize)]
 pub struct Badge {
     pub set_id: String,
     pub version_id: String,
@@ -933,6 +1475,22 @@ fn get_bool(value: &str) -> bool {
     value != "0"
 }
 
+fn get_optional(value: &str) -> Option<String> {
+    if value.is_empty() {
+        None
+    } else {
+        Some(value.to_string())
+    }
+}
+
+fn get_emote_sets(value: &str) -> Vec<String> {
+    value
+        .split(',')
+        .filter(|set| !set.is_empty())
+        .map(|set| set.to_string())
+        .collect()
+}
+
 // 303147449:0-13
 // id: text-position-for-emote
 // https://static-cdn.jtvnw.net/emoticons/v2/303147449/default/dark/1.0
@@ -977,75 +1535,121 @@ fn process_emotes(value: &str, emotes: &mut Vec<Emote>) {
     }
 }
 
-fn get_emote_prefix() -> String {
-    // if is_tmux() {
-    //     return format!("{0}Ptmux;{0}{0}]", ESCAPE);
-    // }
+// Cheermotes are words like Cheer100 where the prefix is one of the channel's cheermotes,
+// the prefix is rendered as the tier image and the amount stays as text
+fn process_cheermotes(message: &str, cheermotes: &[Cheermote], emotes: &mut Vec<Emote>) {
+    let mut position = 0;
 
-    format!("{ESCAPE}]")
-}
+    for word in message.split(' ') {
+        let word_length = word.chars().count();
+
+        for cheermote in cheermotes {
+            let prefix_length = cheermote.prefix.chars().count();
+            let Some((split_index, _)) = word.char_indices().nth(prefix_length) else {
+                continue;
+            };
+
+            let (prefix, amount) = word.split_at(split_index);
+            if !prefix.eq_ignore_ascii_case(&cheermote.prefix) || !amount.chars().all(|c| c.is_ascii_digit()) {
+                continue;
+            }
+
+            let Ok(bits) = amount.parse::<u64>() else {
+                continue;
+            };
+
+            let Some(tier) = cheermote.get_tier(bits) else {
+                continue;
+            };
+
+            let images = &tier.images.dark;
+            let Some(url) = images.animated.get("1").or(images.still.get("1")) else {
+                continue;
+            };
 
-fn get_emote_suffix() -> String {
-    // if is_tmux() {
-    //     return format!("{}{}\\", BELL, ESCAPE);
-    // }
+            emotes.push(Emote {
+                emote_id: format!("cheermote-{}-{}", cheermote.prefix.to_lowercase(), tier.id),
+                start: position,
+                end: position + prefix_length - 1,
+                url: url.to_string(),
+                name: cheermote.prefix.to_string(),
+                encoded: None,
+            });
 
-    BELL.to_string()
+            break;
+        }
+
+        position += word_length + 1;
+    }
 }
 
-fn get_iterm_encoded_image(base64: String) -> String {
-    let base64_str = base64.as_str();
+// 7TV, BTTV and FFZ emotes are plain words in the message, words already covered
+// by a Twitch emote or cheermote are left alone
+fn process_provider_emotes(message: &str, channel: &str, emote_registry: &EmoteRegistry, emotes: &mut Vec<Emote>) {
+    let mut position = 0;
+
+    for word in message.split(' ') {
+        let word_length = word.chars().count();
+        let word_end = position + word_length;
+
+        let is_covered = emotes
+            .iter()
+            .any(|emote| emote.start < word_end && emote.end >= position);
+
+        if word_length > 0 && !is_covered {
+            if let Some(provider_emote) = emote_registry.find(channel, word) {
+                emotes.push(Emote {
+                    emote_id: format!("{}-{}", provider_emote.provider, provider_emote.id),
+                    start: position,
+                    end: word_end - 1,
+                    url: provider_emote.url.clone(),
+                    name: provider_emote.name.clone(),
+                    encoded: None,
+                });
+            }
+        }
 
-    // format!("{ESCAPE}]1337;File=inline=1;height=22px;preserveAspectRatio=1:{base64_str}{BELL}")
-    format!("{ESCAPE}]1337;File=inline=1;preserveAspectRatio=1:{base64_str}{BELL}")
+        position = word_end + 1;
+    }
 }
 
-fn get_badges_symbols(badges: &[Badge], channel_badges: &Option<Vec<BadgeItem>>) -> Result<Vec<Emote>, Box<dyn Error>> {
+fn get_badges_symbols(badges: &[Badge], channel_badges: &Option<Vec<BadgeItem>>) -> Vec<Emote> {
     let mut badges_symbols: Vec<Emote> = vec![];
-    let data_dir = get_data_directory(Some("badges"))?;
+    if !get_image_protocol().shows_images() {
+        return badges_symbols;
+    }
+
     for badge in badges.iter() {
-        let badge_path = data_dir.join(format!("{}_{}.txt", badge.set_id, badge.version_id));
-
-        // Check if the badge exists, then its a global badge
-        if badge_path.exists() {
-            let base64 = fs::read_to_string(badge_path)?;
-            let encoded = get_iterm_encoded_image(base64);
-
-            badges_symbols.push(Emote {
-                emote_id: badge.set_id.clone(),
-                start: 0,
-                end: 0,
-                url: "".to_string(),
-                name: "".to_string(),
-                encoded: Some(encoded),
-            });
-        } else {
-            // This might be a channel badge
-            if let Some(channel_badges) = channel_badges {
-                for badge_item in channel_badges.iter() {
-                    if badge_item.set_id == badge.set_id {
-                        for version in badge_item.versions.iter() {
-                            if version.id == badge.version_id {
-                                let mut emote = Emote {
-                                    emote_id: badge.set_id.clone(),
-                                    start: 0,
-                                    end: 0,
-                                    url: version.image_url_1x.clone(),
-                                    name: "".to_string(),
-                                    encoded: None,
-                                };
-                                emote.load()?;
-
-                                badges_symbols.push(emote);
-                            }
-                        }
-                    }
-                }
-            }
-        }
+        // Channel badges like subscriber badges replace the global ones
+        let version = [
+            channel_badges.as_deref(),
+            GLOBAL_BADGES.get().map(|badges| badges.as_slice()),
+        ]
+        .into_iter()
+        .flatten()
+        .flat_map(|badge_items| badge_items.iter())
+        .filter(|badge_item| badge_item.set_id == badge.set_id)
+        .flat_map(|badge_item| badge_item.versions.iter())
+        .find(|version| version.id == badge.version_id);
+
+        let Some(version) = version else {
+            continue;
+        };
+
+        let mut emote = Emote {
+            emote_id: format!("{}_{}", badge.set_id, badge.version_id),
+            start: 0,
+            end: 0,
+            url: version.image_url_1x.clone(),
+            name: "".to_string(),
+            encoded: None,
+        };
+        emote.load();
+
+        badges_symbols.push(emote);
     }
 
-    Ok(badges_symbols)
+    badges_symbols
 }
 
 fn parse_clearmsg(irc_message: IrcMessage) -> TwitchMessage {
@@ -1068,6 +1672,161 @@ fn parse_clearmsg(irc_message: IrcMessage) -> TwitchMessage {
     }
 }
 
+fn parse_clearchat(irc_message: IrcMessage) -> TwitchMessage {
+    let mut clear_chat = ClearChat {
+        channel: irc_message.channel.to_string(),
+        target_user_login: get_optional(irc_message.parameters),
+        ..Default::default()
+    };
+
+    for (tag, value) in irc_message.tags {
+        match tag {
+            "room-id" => clear_chat.room_id = value.to_string(),
+            "target-user-id" => clear_chat.target_user_id = get_optional(value),
+            "ban-duration" => clear_chat.ban_duration = value.parse().ok(),
+            "tmi-sent-ts" => clear_chat.timestamp = value.to_string(),
+            _ => {}
+        }
+    }
+
+    TwitchMessage::ClearChatMessage { message: clear_chat }
+}
+
+fn parse_notice(irc_message: IrcMessage) -> TwitchMessage {
+    let mut notice = NoticeMessage {
+        channel: irc_message.channel.to_string(),
+        message: irc_message.parameters.to_string(),
+        ..Default::default()
+    };
+
+    for (tag, value) in irc_message.tags {
+        match tag {
+            "msg-id" => notice.msg_id = value.to_string(),
+            "target-user-id" => notice.target_user_id = get_optional(value),
+            _ => {}
+        }
+    }
+
+    TwitchMessage::NoticeMessage { message: notice }
+}
+
+fn parse_userstate(irc_message: IrcMessage) -> TwitchMessage {
+    let mut user_state = UserState {
+        channel: irc_message.channel.to_string(),
+        ..Default::default()
+    };
+
+    for (tag, value) in irc_message.tags {
+        match tag {
+            "badge-info" => set_badges(value, &mut user_state.badge_info),
+            "badges" => set_badges(value, &mut user_state.badges),
+            "color" => user_state.color = get_optional(value),
+            "display-name" => user_state.display_name = value.to_string(),
+            "emote-sets" => user_state.emote_sets = get_emote_sets(value),
+            "id" => user_state.id = get_optional(value),
+            "mod" => user_state.moderator = get_bool(value),
+            "subscriber" => user_state.subscriber = get_bool(value),
+            "turbo" => user_state.turbo = get_bool(value),
+            "user-type" => user_state.user_type = value.to_string(),
+            _ => {}
+        }
+    }
+
+    TwitchMessage::UserStateMessage { message: user_state }
+}
+
+fn parse_globaluserstate(irc_message: IrcMessage) -> TwitchMessage {
+    let mut global_user_state = GlobalUserState::default();
+
+    for (tag, value) in irc_message.tags {
+        match tag {
+            "badge-info" => set_badges(value, &mut global_user_state.badge_info),
+            "badges" => set_badges(value, &mut global_user_state.badges),
+            "color" => global_user_state.color = get_optional(value),
+            "display-name" => global_user_state.display_name = value.to_string(),
+            "emote-sets" => global_user_state.emote_sets = get_emote_sets(value),
+            "turbo" => global_user_state.turbo = get_bool(value),
+            "user-id" => global_user_state.user_id = value.to_string(),
+            "user-type" => global_user_state.user_type = value.to_string(),
+            _ => {}
+        }
+    }
+
+    TwitchMessage::GlobalUserStateMessage {
+        message: global_user_state,
+    }
+}
+
+fn parse_roomstate(irc_message: IrcMessage) -> TwitchMessage {
+    let mut room_state = RoomState {
+        channel: irc_message.channel.to_string(),
+        ..Default::default()
+    };
+
+    for (tag, value) in irc_message.tags {
+        match tag {
+            "room-id" => room_state.room_id = value.to_string(),
+            "emote-only" => room_state.emote_only = Some(get_bool(value)),
+            "followers-only" => room_state.followers_only = value.parse().ok(),
+            "r9k" => room_state.r9k = Some(get_bool(value)),
+            "slow" => room_state.slow = value.parse().ok(),
+            "subs-only" => room_state.subs_only = Some(get_bool(value)),
+            _ => {}
+        }
+    }
+
+    TwitchMessage::RoomStateMessage { message: room_state }
+}
+
+fn parse_whisper(irc_message: IrcMessage) -> TwitchMessage {
+    let mut whisper = WhisperMessage {
+        from_login: irc_message.login.to_string(),
+        to_login: irc_message.target.to_string(),
+        display_name: irc_message.sender.to_string(),
+        message: irc_message.parameters.to_string(),
+        color: "#FF9912".to_string(),
+        ..Default::default()
+    };
+
+    for (tag, value) in irc_message.tags {
+        match tag {
+            "badges" => set_badges(value, &mut whisper.badges),
+            "color" if !value.is_empty() => whisper.color = value.to_string(),
+            "emotes" => process_emotes(value, &mut whisper.emotes),
+            "message-id" => whisper.message_id = value.to_string(),
+            "thread-id" => whisper.thread_id = value.to_string(),
+            "turbo" => whisper.turbo = get_bool(value),
+            "user-id" => whisper.user_id = value.to_string(),
+            "user-type" => whisper.user_type = value.to_string(),
+            _ => {}
+        }
+    }
+
+    TwitchMessage::WhisperMessage { message: whisper }
+}
+
+fn parse_hosttarget(irc_message: IrcMessage) -> TwitchMessage {
+    // :tmi.twitch.tv HOSTTARGET #hosting_channel :<channel|-> [<number-of-viewers>]
+    let mut parameters = irc_message.parameters.split(' ');
+    let target = parameters.next().filter(|target| *target != "-" && !target.is_empty());
+    let viewers = parameters.next().and_then(|viewers| viewers.parse().ok());
+
+    TwitchMessage::HostTargetMessage {
+        message: HostTarget {
+            channel: irc_message.channel.to_string(),
+            target: target.map(|target| target.to_string()),
+            viewers,
+        },
+    }
+}
+
+fn parse_membership(irc_message: IrcMessage) -> Membership {
+    Membership {
+        channel: irc_message.channel.to_string(),
+        login: irc_message.login.to_string(),
+    }
+}
+
 fn parse_privmsg(irc_message: IrcMessage, client: &mut impl TwitchIrcClient) -> TwitchMessage {
     let mut badges: Vec<Badge> = vec![];
     let mut color = "#FF9912".to_string();
@@ -1079,11 +1838,29 @@ fn parse_privmsg(irc_message: IrcMessage, client: &mut impl TwitchIrcClient) ->
     let mut id = String::new();
     let mut timestamp = String::new();
     let mut animation_id = String::new();
+    let mut reply_parent = ReplyParent::default();
+    let mut bits = None;
+    let mut user_id = String::new();
+    let mut room_id = String::new();
 
     for (tag, value) in irc_message.tags {
         match tag {
             "animation-id" => animation_id = value.to_string(),
 
+            "user-id" => user_id = value.to_string(),
+
+            "room-id" => room_id = value.to_string(),
+
+            "bits" => bits = value.parse::<u64>().ok(),
+
+            "reply-parent-msg-id" => reply_parent.msg_id = value.to_string(),
+            "reply-parent-user-id" => reply_parent.user_id = value.to_string(),
+            "reply-parent-user-login" => reply_parent.user_login = value.to_string(),
+            "reply-parent-display-name" => reply_parent.display_name = value.to_string(),
+            "reply-parent-msg-body" => reply_parent.msg_body = unescape(value),
+            "reply-thread-parent-msg-id" => reply_parent.thread_parent_msg_id = get_optional(value),
+            "reply-thread-parent-user-login" => reply_parent.thread_parent_user_login = get_optional(value),
+
             "tmi-sent-ts" => timestamp = value.to_string(),
 
             "user-type" => set_badges(format!("{value}/1").as_str(), &mut badges),
@@ -1122,89 +1899,176 @@ fn parse_privmsg(irc_message: IrcMessage, client: &mut impl TwitchIrcClient) ->
         }
     }
 
-    let badges_symbols = get_badges_symbols(&badges, &client.get_badges());
+    let vip = badges.iter().any(|badge| badge.set_id == "vip");
+    let broadcaster = badges.iter().any(|badge| badge.set_id == "broadcaster");
+
+    let badges_symbols = get_badges_symbols(&badges, &client.get_badges(&room_id));
     let message = irc_message.parameters.to_string();
-    check_for_chat_commands(&message, client);
-    check_for_irc_actions(&message, irc_message.sender, client);
+    let reply_parent = if reply_parent.msg_id.is_empty() {
+        None
+    } else {
+        Some(Box::new(reply_parent))
+    };
+
+    if bits.is_some() {
+        if let Some(cheermotes) = client.get_cheermotes(&room_id) {
+            process_cheermotes(&message, &cheermotes, &mut emotes);
+        }
+    }
+
+    if let Some(emote_registry) = client.get_emote_registry() {
+        process_provider_emotes(&message, irc_message.channel, emote_registry, &mut emotes);
+    }
 
     let can_animate = animation_id != *"";
-    TwitchMessage::PrivMessage {
-        message: ChatMessage {
-            id,
-            emotes,
-            first_msg,
-            returning_chatter,
-            subscriber,
-            moderator,
-            color,
-            message,
-            animation_id,
-            can_animate,
-            timestamp: Some(timestamp),
-            badges: badges_symbols.unwrap_or_default(),
-            nickname: irc_message.sender.to_string(),
-            channel: irc_message.channel.to_string(),
-            raw: irc_message.raw.to_string(),
-            area: None,
-            r: 128,
-            g: 1,
-            b: 249,
-            direction: 1,
-            is_bot: false,
-        },
+    let chat_message = ChatMessage {
+        id,
+        emotes,
+        first_msg,
+        returning_chatter,
+        subscriber,
+        moderator,
+        vip,
+        broadcaster,
+        color,
+        message,
+        animation_id,
+        can_animate,
+        timestamp: Some(timestamp),
+        badges: badges_symbols,
+        nickname: irc_message.sender.to_string(),
+        channel: irc_message.channel.to_string(),
+        raw: irc_message.raw.to_string(),
+        area: None,
+        r: 128,
+        g: 1,
+        b: 249,
+        direction: 1,
+        is_bot: false,
+        reply_parent,
+        bits,
+        user_id,
+        login: irc_message.login.to_lowercase(),
+    };
+
+    // Read-only chat only watches, it doesn't answer commands or run actions. Other joined channels
+    // are only watched too
+    if !client.is_read_only()
+        && is_own_channel(irc_message.channel, client)
+        && !is_own_message(irc_message.login, client)
+    {
+        check_for_chat_commands(&chat_message, client);
+        check_for_irc_actions(&chat_message, client);
+        check_for_bits_actions(&chat_message, client);
     }
+
+    TwitchMessage::PrivMessage { message: chat_message }
 }
 
 fn parse_usernotice(message: IrcMessage) -> TwitchMessage {
-    let mut system_msg = String::new();
-    let mut is_raid = false;
-    let mut user_id = String::new();
-    let mut display_name = String::new();
+    let tags: NoticeTags = message.tags.iter().copied().collect();
+    let msg_id = tags.get("msg-id").copied().unwrap_or_default();
+    let notice = UserNotice::new(&tags, message.channel, message.parameters);
+
+    match msg_id {
+        "raid" => {
+            let system_msg = tags.get("system-msg").copied().unwrap_or_default();
+            if system_msg.is_empty() {
+                return TwitchMessage::UnknownMessage {
+                    message: message.raw.to_string(),
+                };
+            }
 
-    for (tag, value) in message.tags {
-        if value == "raid" {
-            is_raid = true;
-        }
+            let message = RaidMessage {
+                channel: notice.channel,
+                raid_notice: unescape(system_msg),
+                user_id: notice.user_id,
+                display_name: tags
+                    .get("msg-param-displayName")
+                    .copied()
+                    .unwrap_or_default()
+                    .to_string(),
+                area: None,
+                r: 0,
+                g: 0,
+                b: 0,
+                direction: 1,
+            };
 
-        if tag == "system-msg" {
-            system_msg = value.to_string();
+            TwitchMessage::RaidMessage { message }
         }
 
-        if tag == "user-id" {
-            user_id = value.to_string();
-        }
+        "sub" => TwitchMessage::SubNotice {
+            message: Box::new(SubNotice::new(notice, &tags)),
+        },
 
-        if tag == "msg-param-displayName" {
-            display_name = value.to_string();
-        }
-    }
+        "resub" => TwitchMessage::ResubNotice {
+            message: Box::new(SubNotice::new(notice, &tags)),
+        },
 
-    if is_raid && !system_msg.is_empty() {
-        let raid_notice = unescape(&system_msg);
+        "subgift" => TwitchMessage::SubGiftNotice {
+            message: Box::new(SubGiftNotice::new(notice, &tags)),
+        },
 
-        let message = RaidMessage {
-            raid_notice,
-            user_id,
-            display_name,
-            area: None,
-            r: 0,
-            g: 0,
-            b: 0,
-            direction: 1,
-        };
+        "submysterygift" => TwitchMessage::SubMysteryGiftNotice {
+            message: Box::new(SubMysteryGiftNotice::new(notice, &tags)),
+        },
 
-        return TwitchMessage::RaidMessage { message };
-    }
+        "giftpaidupgrade" => TwitchMessage::GiftPaidUpgradeNotice {
+            message: Box::new(GiftPaidUpgradeNotice::new(notice, &tags)),
+        },
+
+        "primepaidupgrade" => TwitchMessage::PrimePaidUpgradeNotice {
+            message: Box::new(PrimePaidUpgradeNotice::new(notice, &tags)),
+        },
 
-    TwitchMessage::UnknownMessage {
-        message: message.raw.to_string(),
+        "announcement" => TwitchMessage::AnnouncementNotice {
+            message: Box::new(AnnouncementNotice::new(notice, &tags)),
+        },
+
+        "bitsbadgetier" => TwitchMessage::BitsBadgeTierNotice {
+            message: Box::new(BitsBadgeTierNotice::new(notice, &tags)),
+        },
+
+        "viewermilestone" => TwitchMessage::ViewerMilestoneNotice {
+            message: Box::new(ViewerMilestoneNotice::new(notice, &tags)),
+        },
+
+        "charitydonation" => TwitchMessage::CharityDonationNotice {
+            message: Box::new(CharityDonationNotice::new(notice, &tags)),
+        },
+
+        _ => TwitchMessage::UnknownMessage {
+            message: message.raw.to_string(),
+        },
     }
 }
 
+/// Whether the bot account sent a message. Twitch sends the bot's replies back to the broadcaster's
+/// connection, answering them could loop forever. The broadcaster's own messages aren't sent back
+fn is_own_message(login: &str, client: &impl TwitchIrcClient) -> bool {
+    client
+        .get_bot_login()
+        .is_some_and(|bot_login| login.eq_ignore_ascii_case(&bot_login))
+}
+
+/// Whether a channel is the broadcaster's, commands and actions only answer there
+fn is_own_channel(channel: &str, client: &impl TwitchIrcClient) -> bool {
+    channel
+        .trim_start_matches('#')
+        .eq_ignore_ascii_case(&client.get_login())
+}
+
 #[cfg(test)]
 mod parse_tests {
+    use std::error::Error;
+
+    use crate::tui::{MessageParts, Symbol};
+    use crate::twitch::emote_providers::{EmoteProvider, EmoteRegistry, ProviderEmote};
     use crate::twitch::irc::TwitchIrcClient;
-    use crate::twitch::parse::parse;
+    use crate::twitch::parse::{
+        is_own_channel, is_own_message, parse, parse_user_state, Badge, ChatMessage, TwitchMessage,
+    };
 
     struct TestTwitchIrc {}
 
@@ -1215,13 +2079,42 @@ mod parse_tests {
     }
 
     impl TwitchIrcClient for TestTwitchIrc {
-        fn display_msg(&self, _message: &str) {}
+        fn display_msg(&self, _channel: &str, _message: &str) {}
 
         fn listen(&mut self) {}
 
-        fn send_privmsg(&mut self, _message: &str) {}
+        fn send_privmsg(&mut self, _channel: &str, _message: &str) {}
+
+        fn send_reply(&mut self, _channel: &str, _reply_to: &str, _message: &str) {}
+
+        fn send_whisper(&mut self, _to_user_id: &str, _to_login: &str, _message: &str) {}
+
+        fn get_badges(&self, _room_id: &str) -> Option<Vec<super::BadgeItem>> {
+            None
+        }
+
+        fn get_cheermotes(&self, _room_id: &str) -> Option<Vec<super::Cheermote>> {
+            None
+        }
+
+        fn get_emote_registry(&self) -> Option<&crate::twitch::emote_providers::EmoteRegistry> {
+            None
+        }
+
+        // Parsing in tests never runs commands or actions, they would read and change the real store
+        fn is_read_only(&self) -> bool {
+            true
+        }
+
+        fn get_login(&self) -> String {
+            String::from("s9tpepper_")
+        }
+
+        fn get_bot_login(&self) -> Option<String> {
+            Some(String::from("Sergeant_Bot"))
+        }
 
-        fn get_badges(&self) -> Option<Vec<super::BadgeItem>> {
+        fn get_api_credentials(&self) -> Option<crate::twitch::pubsub::Credentials> {
             None
         }
     }
@@ -1236,4 +2129,583 @@ mod parse_tests {
 
         assert!(parse_result.is_ok());
     }
+
+    #[test]
+    fn test_parse_notice() {
+        let mut client = TestTwitchIrc::new();
+        let message = "@msg-id=slow_off :tmi.twitch.tv NOTICE #s9tpepper_ :This room is no longer in slow mode.";
+
+        match parse(message, &mut client) {
+            Ok(TwitchMessage::NoticeMessage { message }) => {
+                assert_eq!(message.channel, "#s9tpepper_");
+                assert_eq!(message.msg_id, "slow_off");
+                assert_eq!(message.message, "This room is no longer in slow mode.");
+            }
+            other => panic!("Expected a NoticeMessage, got {other:?}"),
+        }
+    }
+
+    #[test]
+    fn test_parse_notice_without_tags() {
+        let mut client = TestTwitchIrc::new();
+        let message = ":tmi.twitch.tv NOTICE * :Login authentication failed";
+
+        match parse(message, &mut client) {
+            Ok(TwitchMessage::NoticeMessage { message }) => {
+                assert_eq!(message.channel, "");
+                assert_eq!(message.message, "Login authentication failed");
+            }
+            other => panic!("Expected a NoticeMessage, got {other:?}"),
+        }
+    }
+
+    #[test]
+    fn test_parse_userstate() {
+        let mut client = TestTwitchIrc::new();
+        let message = "@badge-info=subscriber/3;badges=broadcaster/1,subscriber/0,premium/1;color=#8A2BE2;display-name=s9tpepper_;emote-sets=0,19194,823538;mod=0;subscriber=1;user-type= :tmi.twitch.tv USERSTATE #s9tpepper_";
+
+        match parse(message, &mut client) {
+            Ok(TwitchMessage::UserStateMessage { message }) => {
+                assert_eq!(message.channel, "#s9tpepper_");
+                assert_eq!(message.display_name, "s9tpepper_");
+                assert_eq!(message.color, Some("#8A2BE2".to_string()));
+                assert_eq!(message.badges.len(), 3);
+                assert_eq!(message.emote_sets, vec!["0", "19194", "823538"]);
+                assert!(message.subscriber);
+                assert!(!message.moderator);
+                assert!(message.is_moderator());
+            }
+            other => panic!("Expected a UserStateMessage, got {other:?}"),
+        }
+    }
+
+    #[test]
+    fn test_parse_user_state() {
+        let message = "@badges=moderator/1;color=;display-name=Sergeant_Bot;emote-sets=0;mod=1;subscriber=0;user-type=mod :tmi.twitch.tv USERSTATE #s9tpepper_";
+
+        let user_state = parse_user_state(message).expect("Expected a USERSTATE");
+        assert_eq!(user_state.channel, "#s9tpepper_");
+        assert!(user_state.is_moderator());
+
+        assert!(parse_user_state("PING :tmi.twitch.tv").is_none());
+        assert!(parse_user_state(":tmi.twitch.tv 001 sergeant_bot :Welcome, GLHF!").is_none());
+    }
+
+    #[test]
+    fn test_parse_globaluserstate() {
+        let mut client = TestTwitchIrc::new();
+        let message = "@badge-info=;badges=premium/1;color=#0D4200;display-name=dallas;emote-sets=0,33,50;turbo=0;user-id=12345678;user-type=admin :tmi.twitch.tv GLOBALUSERSTATE";
+
+        match parse(message, &mut client) {
+            Ok(TwitchMessage::GlobalUserStateMessage { message }) => {
+                assert_eq!(message.display_name, "dallas");
+                assert_eq!(message.user_id, "12345678");
+                assert_eq!(message.user_type, "admin");
+                assert_eq!(
+                    message.badges,
+                    vec![Badge {
+                        set_id: "premium".to_string(),
+                        version_id: "1".to_string(),
+                    }]
+                );
+            }
+            other => panic!("Expected a GlobalUserStateMessage, got {other:?}"),
+        }
+    }
+
+    #[test]
+    fn test_parse_roomstate() {
+        let mut client = TestTwitchIrc::new();
+        let message = "@emote-only=0;followers-only=-1;r9k=1;room-id=961536166;slow=0;subs-only=0 :tmi.twitch.tv ROOMSTATE #s9tpepper_";
+
+        let Ok(TwitchMessage::RoomStateMessage {
+            message: mut room_state,
+        }) = parse(message, &mut client)
+        else {
+            panic!("Expected a RoomStateMessage");
+        };
+
+        assert_eq!(room_state.channel, "#s9tpepper_");
+        assert_eq!(room_state.room_id, "961536166");
+        assert_eq!(room_state.followers_only, Some(-1));
+        assert_eq!(room_state.r9k, Some(true));
+
+        let update = "@room-id=961536166;slow=10 :tmi.twitch.tv ROOMSTATE #s9tpepper_";
+        let Ok(TwitchMessage::RoomStateMessage { message: update }) = parse(update, &mut client) else {
+            panic!("Expected a RoomStateMessage");
+        };
+
+        assert_eq!(update.r9k, None);
+
+        room_state.merge(update);
+        assert_eq!(room_state.slow, Some(10));
+        assert_eq!(room_state.r9k, Some(true));
+    }
+
+    #[test]
+    fn test_parse_clearchat_timeout() {
+        let mut client = TestTwitchIrc::new();
+        let message = "@ban-duration=350;room-id=12345678;target-user-id=87654321;tmi-sent-ts=1642719320727 :tmi.twitch.tv CLEARCHAT #dallas :ronni";
+
+        match parse(message, &mut client) {
+            Ok(TwitchMessage::ClearChatMessage { message }) => {
+                assert_eq!(message.channel, "#dallas");
+                assert_eq!(message.target_user_login, Some("ronni".to_string()));
+                assert_eq!(message.target_user_id, Some("87654321".to_string()));
+                assert_eq!(message.ban_duration, Some(350));
+                assert_eq!(message.description(), "ronni has been timed out for 350 seconds.");
+
+                let chatter = ChatMessage {
+                    channel: "#dallas".to_string(),
+                    nickname: "ロンニ".to_string(),
+                    user_id: "87654321".to_string(),
+                    ..Default::default()
+                };
+                assert!(message.targets(&chatter));
+
+                let other_chatter = ChatMessage {
+                    nickname: "ronni".to_string(),
+                    user_id: "12345678".to_string(),
+                    ..chatter.clone()
+                };
+                assert!(!message.targets(&other_chatter));
+
+                let chatter_without_id = ChatMessage {
+                    login: "ronni".to_string(),
+                    user_id: String::new(),
+                    ..chatter.clone()
+                };
+                assert!(message.targets(&chatter_without_id));
+
+                let other_chatter_without_id = ChatMessage {
+                    nickname: "ronni".to_string(),
+                    login: "ronni_fan".to_string(),
+                    user_id: String::new(),
+                    ..chatter.clone()
+                };
+                assert!(!message.targets(&other_chatter_without_id));
+            }
+            other => panic!("Expected a ClearChatMessage, got {other:?}"),
+        }
+    }
+
+    #[test]
+    fn test_parse_clearchat_all() {
+        let mut client = TestTwitchIrc::new();
+        let message = "@room-id=12345678;tmi-sent-ts=1642715695392 :tmi.twitch.tv CLEARCHAT #dallas";
+
+        match parse(message, &mut client) {
+            Ok(TwitchMessage::ClearChatMessage { message }) => {
+                assert_eq!(message.channel, "#dallas");
+                assert_eq!(message.target_user_login, None);
+                assert_eq!(message.ban_duration, None);
+                assert!(message.is_full_clear());
+            }
+            other => panic!("Expected a ClearChatMessage, got {other:?}"),
+        }
+    }
+
+    #[test]
+    fn test_parse_whisper() {
+        let mut client = TestTwitchIrc::new();
+        let message = "@badges=staff/1,bits-charity/1;color=#8A2BE2;display-name=PetsgomOO;emotes=;message-id=306;thread-id=12345678_87654321;turbo=0;user-id=87654321;user-type=staff :petsgomoo!petsgomoo@petsgomoo.tmi.twitch.tv WHISPER foo :hello: world";
+
+        match parse(message, &mut client) {
+            Ok(TwitchMessage::WhisperMessage { message }) => {
+                assert_eq!(message.from_login, "petsgomoo");
+                assert_eq!(message.display_name, "PetsgomOO");
+                assert_eq!(message.to_login, "foo");
+                assert_eq!(message.message, "hello: world");
+                assert_eq!(message.thread_id, "12345678_87654321");
+            }
+            other => panic!("Expected a WhisperMessage, got {other:?}"),
+        }
+    }
+
+    #[test]
+    fn test_parse_hosttarget() {
+        let mut client = TestTwitchIrc::new();
+
+        match parse(":tmi.twitch.tv HOSTTARGET #abc :xyz 10", &mut client) {
+            Ok(TwitchMessage::HostTargetMessage { message }) => {
+                assert_eq!(message.channel, "#abc");
+                assert_eq!(message.target, Some("xyz".to_string()));
+                assert_eq!(message.viewers, Some(10));
+            }
+            other => panic!("Expected a HostTargetMessage, got {other:?}"),
+        }
+
+        match parse(":tmi.twitch.tv HOSTTARGET #abc :- 0", &mut client) {
+            Ok(TwitchMessage::HostTargetMessage { message }) => assert_eq!(message.target, None),
+            other => panic!("Expected a HostTargetMessage, got {other:?}"),
+        }
+    }
+
+    #[test]
+    fn test_parse_membership() {
+        let mut client = TestTwitchIrc::new();
+
+        match parse(":ronni!ronni@ronni.tmi.twitch.tv JOIN #dallas", &mut client) {
+            Ok(TwitchMessage::JoinMessage { message }) => {
+                assert_eq!(message.login, "ronni");
+                assert_eq!(message.channel, "#dallas");
+            }
+            other => panic!("Expected a JoinMessage, got {other:?}"),
+        }
+
+        match parse(":ronni!ronni@ronni.tmi.twitch.tv PART #dallas", &mut client) {
+            Ok(TwitchMessage::PartMessage { message }) => assert_eq!(message.login, "ronni"),
+            other => panic!("Expected a PartMessage, got {other:?}"),
+        }
+    }
+
+    #[test]
+    fn test_parse_reconnect() {
+        let mut client = TestTwitchIrc::new();
+
+        let parse_result = parse(":tmi.twitch.tv RECONNECT", &mut client);
+
+        assert!(matches!(parse_result, Ok(TwitchMessage::ReconnectMessage { .. })));
+    }
+
+    #[test]
+    fn test_parse_ping() {
+        let mut client = TestTwitchIrc::new();
+
+        match parse("PING :tmi.twitch.tv", &mut client) {
+            Ok(TwitchMessage::PingMessage { message }) => assert_eq!(message, "tmi.twitch.tv"),
+            other => panic!("Expected a PingMessage, got {other:?}"),
+        }
+    }
+
+    #[test]
+    fn test_parse_usernotice_resub() {
+        let mut client = TestTwitchIrc::new();
+        let message = r"@badge-info=;badges=staff/1,broadcaster/1,turbo/1;color=#008000;display-name=ronni;emotes=;id=db25007f-7a18-43eb-9379-80131e44d633;login=ronni;mod=0;msg-id=resub;msg-param-cumulative-months=6;msg-param-streak-months=2;msg-param-should-share-streak=1;msg-param-sub-plan=Prime;msg-param-sub-plan-name=Prime;room-id=12345678;subscriber=1;system-msg=ronni\shas\ssubscribed\sfor\s6\smonths!;tmi-sent-ts=1507246572675;turbo=1;user-id=87654321;user-type=staff :tmi.twitch.tv USERNOTICE #dallas :Great stream -- keep it up!";
+
+        match parse(message, &mut client) {
+            Ok(TwitchMessage::ResubNotice { message }) => {
+                assert_eq!(message.notice.channel, "#dallas");
+                assert_eq!(message.notice.display_name, "ronni");
+                assert_eq!(message.notice.system_msg, "ronni has subscribed for 6 months!");
+                assert_eq!(message.notice.message, "Great stream -- keep it up!");
+                assert_eq!(message.cumulative_months, 6);
+                assert_eq!(message.streak_months, Some(2));
+                assert!(message.should_share_streak);
+                assert_eq!(message.sub_plan, "Prime");
+            }
+            other => panic!("Expected a ResubNotice, got {other:?}"),
+        }
+    }
+
+    #[test]
+    fn test_parse_usernotice_subgift() {
+        let mut client = TestTwitchIrc::new();
+        let message = r"@badge-info=;badges=staff/1,premium/1;color=#0000FF;display-name=TWW2;emotes=;id=e9176cd8-5e22-4684-ad40-ce53c2561c5e;login=tww2;mod=0;msg-id=subgift;msg-param-months=1;msg-param-recipient-display-name=Mr_Woodchuck;msg-param-recipient-id=55554444;msg-param-recipient-user-name=mr_woodchuck;msg-param-sub-plan-name=House\sof\sNyoro~n;msg-param-sub-plan=1000;room-id=19571752;subscriber=0;system-msg=TWW2\sgifted\sa\sTier\s1\ssub\sto\sMr_Woodchuck!;tmi-sent-ts=1521159445153;turbo=0;user-id=87654321;user-type=staff :tmi.twitch.tv USERNOTICE #forstycup";
+
+        match parse(message, &mut client) {
+            Ok(TwitchMessage::SubGiftNotice { message }) => {
+                assert_eq!(message.notice.channel, "#forstycup");
+                assert_eq!(message.notice.message, "");
+                assert_eq!(message.recipient_display_name, "Mr_Woodchuck");
+                assert_eq!(message.recipient_id, "55554444");
+                assert_eq!(message.sub_plan_name, "House of Nyoro~n");
+                assert_eq!(message.months, 1);
+            }
+            other => panic!("Expected a SubGiftNotice, got {other:?}"),
+        }
+    }
+
+    #[test]
+    fn test_parse_usernotice_submysterygift() {
+        let mut client = TestTwitchIrc::new();
+        let message = r"@display-name=TWW2;login=tww2;msg-id=submysterygift;msg-param-mass-gift-count=5;msg-param-sender-count=50;msg-param-sub-plan=2000;system-msg=TWW2\sis\sgifting\s5\sTier\s2\sSubs!;user-id=87654321 :tmi.twitch.tv USERNOTICE #forstycup";
+
+        match parse(message, &mut client) {
+            Ok(TwitchMessage::SubMysteryGiftNotice { message }) => {
+                assert_eq!(message.mass_gift_count, 5);
+                assert_eq!(message.sender_count, Some(50));
+                assert_eq!(message.sub_plan, "2000");
+            }
+            other => panic!("Expected a SubMysteryGiftNotice, got {other:?}"),
+        }
+    }
+
+    #[test]
+    fn test_parse_usernotice_announcement() {
+        let mut client = TestTwitchIrc::new();
+        let message = "@display-name=s9tpepper_;login=s9tpepper_;msg-id=announcement;msg-param-color=PURPLE;user-id=961536166 :tmi.twitch.tv USERNOTICE #s9tpepper_ :Stream starts in 5!";
+
+        match parse(message, &mut client) {
+            Ok(TwitchMessage::AnnouncementNotice { message }) => {
+                assert_eq!(message.color, "PURPLE");
+                assert_eq!(message.notice.message, "Stream starts in 5!");
+            }
+            other => panic!("Expected an AnnouncementNotice, got {other:?}"),
+        }
+    }
+
+    #[test]
+    fn test_parse_usernotice_milestones() {
+        let mut client = TestTwitchIrc::new();
+
+        let message = r"@display-name=ronni;msg-id=bitsbadgetier;msg-param-threshold=1000;system-msg=bits\sbadge\stier\snotification :tmi.twitch.tv USERNOTICE #dallas";
+        match parse(message, &mut client) {
+            Ok(TwitchMessage::BitsBadgeTierNotice { message }) => assert_eq!(message.threshold, 1000),
+            other => panic!("Expected a BitsBadgeTierNotice, got {other:?}"),
+        }
+
+        let message = r"@display-name=ronni;msg-id=viewermilestone;msg-param-category=watch-streak;msg-param-copoReward=450;msg-param-id=1;msg-param-value=10;system-msg=ronni\swatched\s10\sconsecutive\sstreams :tmi.twitch.tv USERNOTICE #dallas";
+        match parse(message, &mut client) {
+            Ok(TwitchMessage::ViewerMilestoneNotice { message }) => {
+                assert_eq!(message.category, "watch-streak");
+                assert_eq!(message.value, 10);
+                assert_eq!(message.copo_reward, Some(450));
+            }
+            other => panic!("Expected a ViewerMilestoneNotice, got {other:?}"),
+        }
+
+        let message = r"@display-name=ronni;msg-id=charitydonation;msg-param-charity-name=Direct\sRelief;msg-param-donation-amount=5000;msg-param-donation-currency=USD;msg-param-exponent=2 :tmi.twitch.tv USERNOTICE #dallas";
+        match parse(message, &mut client) {
+            Ok(TwitchMessage::CharityDonationNotice { message }) => {
+                assert_eq!(message.charity_name, "Direct Relief");
+                assert_eq!(message.amount(), "50.00 USD");
+            }
+            other => panic!("Expected a CharityDonationNotice, got {other:?}"),
+        }
+    }
+
+    #[test]
+    fn test_parse_usernotice_upgrades() {
+        let mut client = TestTwitchIrc::new();
+
+        let message = "@display-name=ronni;msg-id=giftpaidupgrade;msg-param-sender-login=tww2;msg-param-sender-name=TWW2 :tmi.twitch.tv USERNOTICE #dallas";
+        match parse(message, &mut client) {
+            Ok(TwitchMessage::GiftPaidUpgradeNotice { message }) => {
+                assert_eq!(message.sender_name, Some("TWW2".to_string()));
+                assert_eq!(message.promo_gift_total, None);
+            }
+            other => panic!("Expected a GiftPaidUpgradeNotice, got {other:?}"),
+        }
+
+        let message =
+            "@display-name=ronni;msg-id=primepaidupgrade;msg-param-sub-plan=1000 :tmi.twitch.tv USERNOTICE #dallas";
+        match parse(message, &mut client) {
+            Ok(TwitchMessage::PrimePaidUpgradeNotice { message }) => assert_eq!(message.sub_plan, "1000"),
+            other => panic!("Expected a PrimePaidUpgradeNotice, got {other:?}"),
+        }
+    }
+
+    #[test]
+    fn test_parse_reply() {
+        let mut client = TestTwitchIrc::new();
+        let message = r"@badge-info=;badges=;color=#FF4500;display-name=vei_bean;emotes=;first-msg=0;flags=;id=e4c10c03-a606-47f0-b0ab-2d83d415af46;mod=0;reply-parent-display-name=S9tpepper_;reply-parent-msg-body=check\sout\sthe\snew\soverlay;reply-parent-msg-id=b34ccfc7-4977-403a-8a94-33c6bac34fb8;reply-parent-user-id=961536166;reply-parent-user-login=s9tpepper_;reply-thread-parent-msg-id=b34ccfc7-4977-403a-8a94-33c6bac34fb8;reply-thread-parent-user-login=s9tpepper_;returning-chatter=0;room-id=961536166;subscriber=0;tmi-sent-ts=1708304710278;turbo=0;user-id=624578741;user-type= :vei_bean!vei_bean@vei_bean.tmi.twitch.tv PRIVMSG #s9tpepper_ :@S9tpepper_ looks great";
+
+        match parse(message, &mut client) {
+            Ok(TwitchMessage::PrivMessage { message }) => {
+                let reply_parent = message.reply_parent.expect("Expected reply-parent tags");
+
+                assert_eq!(reply_parent.msg_id, "b34ccfc7-4977-403a-8a94-33c6bac34fb8");
+                assert_eq!(reply_parent.user_login, "s9tpepper_");
+                assert_eq!(reply_parent.msg_body, "check out the new overlay");
+                assert_eq!(reply_parent.thread_parent_user_login, Some("s9tpepper_".to_string()));
+                assert_eq!(
+                    reply_parent.get_context(),
+                    "↪ replying to @S9tpepper_: check out the new overlay"
+                );
+            }
+            other => panic!("Expected a PrivMessage, got {other:?}"),
+        }
+
+        let message = "@display-name=vei_bean;emotes=;id=1234 :vei_bean!vei_bean@vei_bean.tmi.twitch.tv PRIVMSG #s9tpepper_ :hello";
+        match parse(message, &mut client) {
+            Ok(TwitchMessage::PrivMessage { message }) => assert!(message.reply_parent.is_none()),
+            other => panic!("Expected a PrivMessage, got {other:?}"),
+        }
+    }
+
+    #[test]
+    fn test_parse_bits() {
+        let mut client = TestTwitchIrc::new();
+
+        let message = r"@badge-info=;badges=bits/100;bits=150;color=#1E90FF;display-name=ronni;emotes=;id=b34ccfc7-4977-403a-8a94-33c6bac34fb8;mod=0;room-id=12345678;subscriber=0;tmi-sent-ts=1507246572675;turbo=1;user-id=12345678;user-type= :ronni!ronni@ronni.tmi.twitch.tv PRIVMSG #ronni :Cheer100 Kappa cheer50 nice stream".to_string();
+
+        match parse(&message, &mut client) {
+            Ok(TwitchMessage::PrivMessage { message }) => assert_eq!(message.bits, Some(150)),
+            _ => panic!("Expected a PrivMessage"),
+        }
+    }
+
+    #[test]
+    fn test_is_own_message() {
+        let client = TestTwitchIrc::new();
+
+        assert!(!is_own_message("s9tpepper_", &client));
+        assert!(is_own_message("sergeant_bot", &client));
+        assert!(!is_own_message("ronni", &client));
+    }
+
+    #[test]
+    fn test_is_own_channel() {
+        let client = TestTwitchIrc::new();
+
+        assert!(is_own_channel("#s9tpepper_", &client));
+        assert!(!is_own_channel("#dallas", &client));
+    }
+
+    #[test]
+    fn test_parse_roles() {
+        let mut client = TestTwitchIrc::new();
+
+        let message = r"@badge-info=;badges=vip/1,sub-gifter/5;color=;display-name=ronni;emotes=;id=1234;mod=0;subscriber=0;user-id=12345678;user-type= :ronni!ronni@ronni.tmi.twitch.tv PRIVMSG #s9tpepper_ :hi".to_string();
+
+        match parse(&message, &mut client) {
+            Ok(TwitchMessage::PrivMessage { message }) => assert!(message.vip && !message.broadcaster),
+            _ => panic!("Expected a PrivMessage"),
+        }
+
+        let message = r"@badge-info=;badges=broadcaster/1;color=;display-name=S9tpepper_;emotes=;id=5678;mod=0;subscriber=0;user-id=961536166;user-type= :s9tpepper_!s9tpepper_@s9tpepper_.tmi.twitch.tv PRIVMSG #s9tpepper_ :hi".to_string();
+
+        match parse(&message, &mut client) {
+            Ok(TwitchMessage::PrivMessage { message }) => assert!(message.broadcaster && !message.vip),
+            _ => panic!("Expected a PrivMessage"),
+        }
+    }
+
+    #[test]
+    fn test_process_cheermotes() {
+        let cheermotes: Vec<super::Cheermote> = serde_json::from_str(
+            r#"[{
+                "prefix": "Cheer",
+                "tiers": [
+                    {"min_bits": 1, "id": "1", "images": {"dark": {"animated": {"1": "https://example.com/cheer/1.gif"}, "static": {"1": "https://example.com/cheer/1.png"}}}},
+                    {"min_bits": 100, "id": "100", "images": {"dark": {"animated": {"1": "https://example.com/cheer/100.gif"}, "static": {"1": "https://example.com/cheer/100.png"}}}}
+                ]
+            }]"#,
+        )
+        .expect("Expected valid cheermotes");
+
+        let mut emotes = vec![];
+        super::process_cheermotes("Cheer100 Kappa cheer50 Cheerful", &cheermotes, &mut emotes);
+
+        assert_eq!(emotes.len(), 2);
+
+        assert_eq!(emotes[0].start, 0);
+        assert_eq!(emotes[0].end, 4);
+        assert_eq!(emotes[0].emote_id, "cheermote-cheer-100");
+        assert_eq!(emotes[0].url, "https://example.com/cheer/100.gif");
+
+        assert_eq!(emotes[1].start, 15);
+        assert_eq!(emotes[1].end, 19);
+        assert_eq!(emotes[1].emote_id, "cheermote-cheer-1");
+    }
+
+    fn symbols_to_string(symbols: &[Symbol]) -> String {
+        symbols
+            .iter()
+            .map(|symbol| match symbol {
+                Symbol::Text(text) => text.char.clone(),
+                Symbol::Emote(emote) => format!("[{}]", emote.emote_id),
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_segment_unicode_messages() {
+        let mut client = TestTwitchIrc::new();
+
+        let expected = [
+            "😂 hey [25]",
+            "こんにちは [25] 世界 [425618]",
+            "👨‍👩‍👧 family [25] [25]",
+            "🇯🇵 é́ [88] gg",
+        ];
+
+        let fixtures = include_str!("../../fixtures/privmsg_unicode_emotes.txt");
+        for (line, expected) in fixtures.lines().zip(expected) {
+            let Ok(TwitchMessage::PrivMessage { message }) = parse(line, &mut client) else {
+                panic!("Expected a PrivMessage for {line}");
+            };
+
+            let symbols = super::segment_message(&message.message, &message.emotes, None);
+            assert_eq!(symbols_to_string(&symbols), expected);
+        }
+    }
+
+    #[test]
+    fn test_segment_graphemes() {
+        let symbols = super::segment_message("👨‍👩‍👧🇯🇵é́", &[], None);
+
+        assert_eq!(symbols.len(), 3);
+    }
+
+    #[test]
+    fn test_get_lines_wide_characters() {
+        let symbols = super::segment_message("こんにちは 世界 hello", &[], None);
+        let area = ratatui::layout::Rect::new(0, 0, 8, 10);
+
+        let lines = super::get_lines(&symbols, &area);
+        let lines: Vec<String> = lines
+            .iter()
+            .map(|line| {
+                line.iter()
+                    .map(|part| match part {
+                        MessageParts::Text(word) => symbols_to_string(word),
+                        MessageParts::Emote(_) => String::new(),
+                    })
+                    .collect()
+            })
+            .collect();
+
+        // The 10 cell wide word is split without breaking a character in half
+        assert_eq!(lines, vec!["こんに-", "ちは ", "世界 ", "hello"]);
+    }
+
+    struct TestEmoteProvider {}
+
+    impl EmoteProvider for TestEmoteProvider {
+        fn name(&self) -> &str {
+            "7tv"
+        }
+
+        fn load_global(&self) -> Result<Vec<ProviderEmote>, Box<dyn Error>> {
+            Ok(vec![ProviderEmote {
+                provider: "7tv".to_string(),
+                id: "60ae958e229664e8667aea38".to_string(),
+                name: "Kappa".to_string(),
+                url: "https://cdn.7tv.app/emote/60ae958e229664e8667aea38/1x.png".to_string(),
+            }])
+        }
+
+        fn load_channel(&self, _channel_id: &str) -> Result<Vec<ProviderEmote>, Box<dyn Error>> {
+            Ok(vec![ProviderEmote {
+                provider: "7tv".to_string(),
+                id: "63071b80942ffb69e13d700f".to_string(),
+                name: "catJAM".to_string(),
+                url: "https://cdn.7tv.app/emote/63071b80942ffb69e13d700f/1x.gif".to_string(),
+            }])
+        }
+    }
+
+    #[test]
+    fn test_process_provider_emotes() {
+        let mut emote_registry = EmoteRegistry::new(vec![Box::new(TestEmoteProvider {})]);
+        emote_registry.load_global();
+        emote_registry.load_channel("#ronni", "12345678", &["7tv".to_string()]);
+
+        // The Twitch Kappa is kept, the 7TV emotes only fill in the plain words
+        let mut emotes = vec![];
+        super::process_emotes("25:6-10", &mut emotes);
+        super::process_provider_emotes("😂 hey Kappa catJAM catJAMs", "#ronni", &emote_registry, &mut emotes);
+
+        let symbols = super::segment_message("😂 hey Kappa catJAM catJAMs", &emotes, None);
+        assert_eq!(
+            symbols_to_string(&symbols),
+            "😂 hey [25] [7tv-63071b80942ffb69e13d700f] catJAMs"
+        );
+
+        let mut emotes = vec![];
+        super::process_provider_emotes("catJAM", "#dallas", &emote_registry, &mut emotes);
+        assert!(emotes.is_empty());
+    }
 }
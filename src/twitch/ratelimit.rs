@@ -0,0 +1,181 @@
+use std::{
+    collections::VecDeque,
+    time::{Duration, Instant},
+};
+
+/// Twitch allows 20 messages every 30 seconds, or 100 in channels where the bot is a moderator
+pub const USER_MESSAGE_LIMIT: u32 = 20;
+pub const MODERATOR_MESSAGE_LIMIT: u32 = 100;
+pub const RATE_LIMIT_WINDOW: Duration = Duration::from_secs(30);
+
+/// Messages that wait longer than this are dropped instead of being sent late
+pub const QUEUE_TIMEOUT: Duration = Duration::from_secs(60);
+pub const QUEUE_MAX: usize = 25;
+
+/// When the messages of the last window were sent, Twitch counts every message an account sends
+#[derive(Debug)]
+pub struct RateLimit {
+    window: Duration,
+    sent: VecDeque<Instant>,
+}
+
+impl RateLimit {
+    pub fn new(window: Duration) -> Self {
+        RateLimit {
+            window,
+            sent: VecDeque::new(),
+        }
+    }
+
+    /// Records a message as sent if fewer than limit messages went out in the window before now
+    pub fn try_acquire(&mut self, limit: u32, now: Instant) -> bool {
+        while self
+            .sent
+            .front()
+            .is_some_and(|sent_at| now.saturating_duration_since(*sent_at) >= self.window)
+        {
+            self.sent.pop_front();
+        }
+
+        if self.sent.len() >= limit as usize {
+            return false;
+        }
+
+        self.sent.push_back(now);
+
+        true
+    }
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct QueuedMessage {
+    pub channel: String,
+    pub message: String,
+    /// The id of the message this is a threaded reply to
+    pub reply_to: Option<String>,
+    pub queued_at: Instant,
+}
+
+#[derive(Debug)]
+pub struct SendQueue {
+    queue: VecDeque<QueuedMessage>,
+    rate_limit: RateLimit,
+}
+
+impl Default for SendQueue {
+    fn default() -> Self {
+        SendQueue {
+            queue: VecDeque::new(),
+            rate_limit: RateLimit::new(RATE_LIMIT_WINDOW),
+        }
+    }
+}
+
+impl SendQueue {
+    pub fn len(&self) -> usize {
+        self.queue.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.queue.is_empty()
+    }
+
+    /// Adds a message to the back of the queue, the message is handed back if the queue is full
+    pub fn push(
+        &mut self,
+        channel: &str,
+        message: &str,
+        reply_to: Option<&str>,
+        now: Instant,
+    ) -> Result<(), QueuedMessage> {
+        let queued_message = QueuedMessage {
+            channel: channel.to_string(),
+            message: message.to_string(),
+            reply_to: reply_to.map(|reply_to| reply_to.to_string()),
+            queued_at: now,
+        };
+
+        if self.queue.len() >= QUEUE_MAX {
+            return Err(queued_message);
+        }
+
+        self.queue.push_back(queued_message);
+
+        Ok(())
+    }
+
+    /// Removes the messages that have been waiting longer than QUEUE_TIMEOUT
+    pub fn drop_stale(&mut self, now: Instant) -> Vec<QueuedMessage> {
+        let (stale, fresh) = self
+            .queue
+            .drain(..)
+            .partition(|message| now.saturating_duration_since(message.queued_at) > QUEUE_TIMEOUT);
+
+        self.queue = fresh;
+
+        stale.into()
+    }
+
+    /// Returns the next message if the rate limit for its channel allows sending it
+    pub fn pop_ready(&mut self, now: Instant, is_moderator: impl Fn(&str) -> bool) -> Option<QueuedMessage> {
+        let next_message = self.queue.front()?;
+
+        let limit = if is_moderator(&next_message.channel) {
+            MODERATOR_MESSAGE_LIMIT
+        } else {
+            USER_MESSAGE_LIMIT
+        };
+
+        if self.rate_limit.try_acquire(limit, now) {
+            return self.queue.pop_front();
+        }
+
+        None
+    }
+}
+
+#[test]
+fn test_rate_limit_window() {
+    let start = Instant::now();
+    let after = |seconds| start + Duration::from_secs(seconds);
+    let mut rate_limit = RateLimit::new(Duration::from_secs(30));
+
+    assert!(rate_limit.try_acquire(2, start));
+    assert!(rate_limit.try_acquire(2, after(10)));
+    assert!(!rate_limit.try_acquire(2, after(20)));
+
+    // Only one message left the window, so only one more can go out
+    assert!(rate_limit.try_acquire(2, after(30)));
+    assert!(!rate_limit.try_acquire(2, after(35)));
+    assert!(rate_limit.try_acquire(2, after(40)));
+
+    // A higher limit counts the same messages
+    assert!(rate_limit.try_acquire(3, after(40)));
+}
+
+#[test]
+fn test_send_queue_limits() {
+    let start = Instant::now();
+    let mut queue = SendQueue::default();
+
+    for index in 0..QUEUE_MAX {
+        assert!(queue.push("#dallas", &format!("message {index}"), None, start).is_ok());
+    }
+
+    assert!(queue.push("#dallas", "one too many", Some("1234"), start).is_err());
+
+    let mut sent = 0;
+    while queue.pop_ready(start, |_| false).is_some() {
+        sent += 1;
+    }
+
+    assert_eq!(sent, USER_MESSAGE_LIMIT as usize);
+    assert_eq!(queue.len(), QUEUE_MAX - USER_MESSAGE_LIMIT as usize);
+
+    // Channels where the bot is a moderator allow more of the same messages
+    assert!(queue.pop_ready(start, |_| true).is_some());
+
+    let stale = queue.drop_stale(start + QUEUE_TIMEOUT + Duration::from_secs(1));
+    assert_eq!(stale.len(), QUEUE_MAX - USER_MESSAGE_LIMIT as usize - 1);
+    assert!(queue.is_empty());
+}
@@ -0,0 +1,226 @@
+use std::{
+    error::Error,
+    io::{ErrorKind, Read, Write},
+    net::TcpStream,
+    time::Duration,
+};
+
+use native_tls::TlsConnector;
+use tungstenite::{stream::MaybeTlsStream, Message, WebSocket};
+
+use super::irc::MESSAGE_DELIMITER;
+
+/// Twitch IRC over a WebSocket, the default
+pub const TWITCH_IRC_URL: &str = "wss://irc-ws.chat.twitch.tv:443";
+
+/// Twitch IRC over TLS, for networks that block WebSocket upgrades
+pub const TWITCH_IRC_TLS_URL: &str = "ircs://irc.chat.twitch.tv:6697";
+
+const IRC_TLS_PORT: u16 = 6697;
+const IRC_PORT: u16 = 6667;
+
+// Reads time out so queued messages still go out when chat is quiet
+pub const READ_TIMEOUT: Duration = Duration::from_secs(1);
+
+pub enum IrcRead {
+    /// Complete IRC lines, without the \r\n
+    Lines(Vec<String>),
+    /// Nothing arrived within the read timeout
+    Timeout,
+    /// The server closed the connection
+    Closed,
+}
+
+/// Carries IRC lines to and from Twitch, parsing and everything above it doesn't care how
+pub trait IrcTransport: Send {
+    fn send_line(&mut self, line: &str) -> Result<(), Box<dyn Error>>;
+    fn read_lines(&mut self) -> Result<IrcRead, Box<dyn Error>>;
+    fn close(&mut self);
+}
+
+/// The transports that can be picked with --transport, each has its own Twitch endpoint
+pub fn get_transport_url(transport: &str) -> Option<&'static str> {
+    match transport {
+        "websocket" => Some(TWITCH_IRC_URL),
+        "irc" => Some(TWITCH_IRC_TLS_URL),
+        _ => None,
+    }
+}
+
+fn is_timeout(error: &std::io::Error) -> bool {
+    matches!(error.kind(), ErrorKind::WouldBlock | ErrorKind::TimedOut)
+}
+
+fn split_lines(text: &str) -> Vec<String> {
+    text.split(MESSAGE_DELIMITER)
+        .filter(|line| !line.is_empty())
+        .map(|line| line.to_string())
+        .collect()
+}
+
+/// IRC lines sent as WebSocket text messages, Twitch can put several lines in one message
+pub struct WebSocketTransport<S: Read + Write> {
+    socket: WebSocket<S>,
+}
+
+impl<S: Read + Write> WebSocketTransport<S> {
+    pub fn new(socket: WebSocket<S>) -> Self {
+        WebSocketTransport { socket }
+    }
+}
+
+impl<S: Read + Write + Send> IrcTransport for WebSocketTransport<S> {
+    fn send_line(&mut self, line: &str) -> Result<(), Box<dyn Error>> {
+        self.socket.send(Message::text(line))?;
+
+        Ok(())
+    }
+
+    fn read_lines(&mut self) -> Result<IrcRead, Box<dyn Error>> {
+        match self.socket.read() {
+            Ok(Message::Text(text)) => Ok(IrcRead::Lines(split_lines(&text))),
+            Ok(Message::Close(_)) => Ok(IrcRead::Closed),
+
+            // tungstenite answers pings itself
+            Ok(_) => Ok(IrcRead::Lines(vec![])),
+
+            Err(tungstenite::Error::Io(io_error)) if is_timeout(&io_error) => Ok(IrcRead::Timeout),
+            Err(tungstenite::Error::ConnectionClosed | tungstenite::Error::AlreadyClosed) => Ok(IrcRead::Closed),
+            Err(error) => Err(error.into()),
+        }
+    }
+
+    fn close(&mut self) {
+        let _ = self.socket.close(None);
+        let _ = self.socket.flush();
+    }
+}
+
+/// Raw IRC, lines end with \r\n and can be split across reads
+pub struct LineTransport<S: Read + Write> {
+    stream: S,
+    buffer: Vec<u8>,
+}
+
+impl<S: Read + Write> LineTransport<S> {
+    pub fn new(stream: S) -> Self {
+        LineTransport { stream, buffer: vec![] }
+    }
+
+    fn take_lines(&mut self) -> Vec<String> {
+        let Some(end) = self.buffer.windows(2).rposition(|bytes| bytes == b"\r\n") else {
+            return vec![];
+        };
+
+        // Anything after the last \r\n is the start of a line that hasn't fully arrived yet
+        let complete: Vec<u8> = self.buffer.drain(..end + 2).collect();
+
+        split_lines(&String::from_utf8_lossy(&complete))
+    }
+}
+
+impl<S: Read + Write + Send> IrcTransport for LineTransport<S> {
+    fn send_line(&mut self, line: &str) -> Result<(), Box<dyn Error>> {
+        self.stream.write_all(format!("{line}{MESSAGE_DELIMITER}").as_bytes())?;
+        self.stream.flush()?;
+
+        Ok(())
+    }
+
+    fn read_lines(&mut self) -> Result<IrcRead, Box<dyn Error>> {
+        let mut chunk = [0; 4096];
+
+        match self.stream.read(&mut chunk) {
+            Ok(0) => Ok(IrcRead::Closed),
+            Ok(length) => {
+                self.buffer.extend_from_slice(&chunk[..length]);
+
+                Ok(IrcRead::Lines(self.take_lines()))
+            }
+            Err(io_error) if is_timeout(&io_error) => Ok(IrcRead::Timeout),
+            Err(io_error) => Err(io_error.into()),
+        }
+    }
+
+    // The connection is closed when the transport is dropped
+    fn close(&mut self) {
+        let _ = self.stream.flush();
+    }
+}
+
+/// Connects to an IRC server, the scheme picks the transport: ws:// and wss:// for WebSockets,
+/// ircs:// for IRC over TLS and irc:// for plain IRC. Reads give up after read_timeout
+pub fn connect_transport(irc_url: &str, read_timeout: Duration) -> Result<Box<dyn IrcTransport>, Box<dyn Error>> {
+    let Some((scheme, address)) = irc_url.split_once("://") else {
+        return Err(format!("Invalid IRC url: {irc_url}").into());
+    };
+
+    let address = address.trim_end_matches('/');
+    let host = address.split(':').next().unwrap_or(address);
+
+    match scheme {
+        "ws" | "wss" => {
+            let (socket, _) = tungstenite::connect(irc_url)?;
+
+            match socket.get_ref() {
+                MaybeTlsStream::Plain(stream) => stream.set_read_timeout(Some(read_timeout))?,
+                MaybeTlsStream::NativeTls(stream) => stream.get_ref().set_read_timeout(Some(read_timeout))?,
+                _ => {}
+            }
+
+            Ok(Box::new(WebSocketTransport::new(socket)))
+        }
+
+        "ircs" => {
+            let stream = connect_tcp(address, IRC_TLS_PORT)?;
+            let stream = TlsConnector::new()?.connect(host, stream)?;
+
+            // The timeout is set after the TLS handshake so it doesn't interrupt it
+            stream.get_ref().set_read_timeout(Some(read_timeout))?;
+
+            Ok(Box::new(LineTransport::new(stream)))
+        }
+
+        "irc" => {
+            let stream = connect_tcp(address, IRC_PORT)?;
+            stream.set_read_timeout(Some(read_timeout))?;
+
+            Ok(Box::new(LineTransport::new(stream)))
+        }
+
+        _ => Err(format!("Unsupported IRC url: {irc_url}").into()),
+    }
+}
+
+fn connect_tcp(address: &str, default_port: u16) -> Result<TcpStream, Box<dyn Error>> {
+    let stream = if address.contains(':') {
+        TcpStream::connect(address)?
+    } else {
+        TcpStream::connect((address, default_port))?
+    };
+
+    Ok(stream)
+}
+
+#[test]
+fn test_line_transport() {
+    use std::io::Cursor;
+
+    // A line split across two reads only comes out once it's complete
+    let mut transport = LineTransport::new(Cursor::new(b"PING :tmi.twitch.tv\r\n:tmi.twitch.tv 001 dal".to_vec()));
+    let Ok(IrcRead::Lines(lines)) = transport.read_lines() else {
+        panic!("Expected lines");
+    };
+    assert_eq!(lines, vec!["PING :tmi.twitch.tv"]);
+    assert_eq!(transport.buffer, b":tmi.twitch.tv 001 dal");
+
+    transport.buffer.extend_from_slice(b"las :Welcome, GLHF!\r\n");
+    assert_eq!(
+        transport.take_lines(),
+        vec![":tmi.twitch.tv 001 dallas :Welcome, GLHF!"]
+    );
+    assert!(matches!(transport.read_lines(), Ok(IrcRead::Closed)));
+
+    assert_eq!(get_transport_url("irc"), Some(TWITCH_IRC_TLS_URL));
+    assert!(connect_transport("https://irc.chat.twitch.tv", READ_TIMEOUT).is_err());
+}
@@ -0,0 +1,533 @@
+use std::{collections::HashMap, str::FromStr};
+
+use ratatui::{
+    buffer::Buffer,
+    layout::Rect,
+    style::{Color, Style},
+    symbols,
+    widgets::{Block, Widget},
+};
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    tui::{MessageParts, Symbol},
+    utils::unescape,
+};
+
+use super::parse::{get_lines, get_message_symbols, get_screen_lines, write_to_buffer, RenderCursor};
+
+pub type NoticeTags<'a> = HashMap<&'a str, &'a str>;
+
+fn get_string(tags: &NoticeTags, name: &str) -> String {
+    tags.get(name).map(|value| value.to_string()).unwrap_or_default()
+}
+
+fn get_number<T: FromStr>(tags: &NoticeTags, name: &str) -> Option<T> {
+    tags.get(name).and_then(|value| value.parse().ok())
+}
+
+fn get_flag(tags: &NoticeTags, name: &str) -> bool {
+    tags.get(name).is_some_and(|value| *value != "0" && *value != "false")
+}
+
+/// Tags shared by every USERNOTICE regardless of its msg-id
+#[derive(Default, Clone, Debug, Serialize, Deserialize)]
+pub struct UserNotice {
+    pub msg_id: String,
+    pub id: String,
+    pub channel: String,
+    pub login: String,
+    pub display_name: String,
+    pub user_id: String,
+    pub color: String,
+    pub system_msg: String,
+    pub message: String,
+    pub timestamp: String,
+}
+
+impl UserNotice {
+    pub fn new(tags: &NoticeTags, channel: &str, message: &str) -> Self {
+        UserNotice {
+            msg_id: get_string(tags, "msg-id"),
+            id: get_string(tags, "id"),
+            channel: channel.to_string(),
+            login: get_string(tags, "login"),
+            display_name: get_string(tags, "display-name"),
+            user_id: get_string(tags, "user-id"),
+            color: get_string(tags, "color"),
+            system_msg: unescape(&get_string(tags, "system-msg")),
+            message: message.to_string(),
+            timestamp: get_string(tags, "tmi-sent-ts"),
+        }
+    }
+}
+
+/// Used by both the sub and resub msg-ids
+#[derive(Default, Clone, Debug, Serialize, Deserialize)]
+pub struct SubNotice {
+    pub notice: UserNotice,
+    pub cumulative_months: u64,
+    pub should_share_streak: bool,
+    pub streak_months: Option<u64>,
+    pub sub_plan: String,
+    pub sub_plan_name: String,
+    pub multimonth_duration: Option<u64>,
+    #[serde(skip)]
+    pub area: Option<Rect>,
+}
+
+impl SubNotice {
+    pub fn new(notice: UserNotice, tags: &NoticeTags) -> Self {
+        SubNotice {
+            notice,
+            cumulative_months: get_number(tags, "msg-param-cumulative-months").unwrap_or(1),
+            should_share_streak: get_flag(tags, "msg-param-should-share-streak"),
+            streak_months: get_number(tags, "msg-param-streak-months"),
+            sub_plan: get_string(tags, "msg-param-sub-plan"),
+            sub_plan_name: unescape(&get_string(tags, "msg-param-sub-plan-name")),
+            multimonth_duration: get_number(tags, "msg-param-multimonth-duration"),
+            area: None,
+        }
+    }
+}
+
+#[derive(Default, Clone, Debug, Serialize, Deserialize)]
+pub struct SubGiftNotice {
+    pub notice: UserNotice,
+    pub months: u64,
+    pub gift_months: u64,
+    pub recipient_display_name: String,
+    pub recipient_id: String,
+    pub recipient_user_name: String,
+    pub sender_count: Option<u64>,
+    pub sub_plan: String,
+    pub sub_plan_name: String,
+    pub origin_id: String,
+    #[serde(skip)]
+    pub area: Option<Rect>,
+}
+
+impl SubGiftNotice {
+    pub fn new(notice: UserNotice, tags: &NoticeTags) -> Self {
+        SubGiftNotice {
+            notice,
+            months: get_number(tags, "msg-param-months").unwrap_or(1),
+            gift_months: get_number(tags, "msg-param-gift-months").unwrap_or(1),
+            recipient_display_name: get_string(tags, "msg-param-recipient-display-name"),
+            recipient_id: get_string(tags, "msg-param-recipient-id"),
+            recipient_user_name: get_string(tags, "msg-param-recipient-user-name"),
+            sender_count: get_number(tags, "msg-param-sender-count"),
+            sub_plan: get_string(tags, "msg-param-sub-plan"),
+            sub_plan_name: unescape(&get_string(tags, "msg-param-sub-plan-name")),
+            origin_id: get_string(tags, "msg-param-origin-id"),
+            area: None,
+        }
+    }
+}
+
+#[derive(Default, Clone, Debug, Serialize, Deserialize)]
+pub struct SubMysteryGiftNotice {
+    pub notice: UserNotice,
+    pub mass_gift_count: u64,
+    pub sender_count: Option<u64>,
+    pub sub_plan: String,
+    pub origin_id: String,
+    #[serde(skip)]
+    pub area: Option<Rect>,
+}
+
+impl SubMysteryGiftNotice {
+    pub fn new(notice: UserNotice, tags: &NoticeTags) -> Self {
+        SubMysteryGiftNotice {
+            notice,
+            mass_gift_count: get_number(tags, "msg-param-mass-gift-count").unwrap_or(1),
+            sender_count: get_number(tags, "msg-param-sender-count"),
+            sub_plan: get_string(tags, "msg-param-sub-plan"),
+            origin_id: get_string(tags, "msg-param-origin-id"),
+            area: None,
+        }
+    }
+}
+
+#[derive(Default, Clone, Debug, Serialize, Deserialize)]
+pub struct GiftPaidUpgradeNotice {
+    pub notice: UserNotice,
+    pub promo_gift_total: Option<u64>,
+    pub promo_name: Option<String>,
+    pub sender_login: Option<String>,
+    pub sender_name: Option<String>,
+    #[serde(skip)]
+    pub area: Option<Rect>,
+}
+
+impl GiftPaidUpgradeNotice {
+    pub fn new(notice: UserNotice, tags: &NoticeTags) -> Self {
+        GiftPaidUpgradeNotice {
+            notice,
+            promo_gift_total: get_number(tags, "msg-param-promo-gift-total"),
+            promo_name: tags.get("msg-param-promo-name").map(|name| unescape(name)),
+            sender_login: tags.get("msg-param-sender-login").map(|login| login.to_string()),
+            sender_name: tags.get("msg-param-sender-name").map(|name| name.to_string()),
+            area: None,
+        }
+    }
+}
+
+#[derive(Default, Clone, Debug, Serialize, Deserialize)]
+pub struct PrimePaidUpgradeNotice {
+    pub notice: UserNotice,
+    pub sub_plan: String,
+    #[serde(skip)]
+    pub area: Option<Rect>,
+}
+
+impl PrimePaidUpgradeNotice {
+    pub fn new(notice: UserNotice, tags: &NoticeTags) -> Self {
+        PrimePaidUpgradeNotice {
+            notice,
+            sub_plan: get_string(tags, "msg-param-sub-plan"),
+            area: None,
+        }
+    }
+}
+
+#[derive(Default, Clone, Debug, Serialize, Deserialize)]
+pub struct AnnouncementNotice {
+    pub notice: UserNotice,
+    /// PRIMARY, BLUE, GREEN, ORANGE or PURPLE
+    pub color: String,
+    #[serde(skip)]
+    pub area: Option<Rect>,
+}
+
+impl AnnouncementNotice {
+    pub fn new(notice: UserNotice, tags: &NoticeTags) -> Self {
+        let color = get_string(tags, "msg-param-color");
+
+        AnnouncementNotice {
+            notice,
+            color: if color.is_empty() { "PRIMARY".to_string() } else { color },
+            area: None,
+        }
+    }
+
+    fn get_color(&self) -> Color {
+        match self.color.as_str() {
+            "BLUE" => Color::Rgb(0, 219, 224),
+            "GREEN" => Color::Rgb(0, 219, 132),
+            "ORANGE" => Color::Rgb(255, 179, 38),
+            "PURPLE" => Color::Rgb(158, 156, 255),
+            _ => Color::Rgb(145, 70, 255),
+        }
+    }
+}
+
+#[derive(Default, Clone, Debug, Serialize, Deserialize)]
+pub struct BitsBadgeTierNotice {
+    pub notice: UserNotice,
+    pub threshold: u64,
+    #[serde(skip)]
+    pub area: Option<Rect>,
+}
+
+impl BitsBadgeTierNotice {
+    pub fn new(notice: UserNotice, tags: &NoticeTags) -> Self {
+        BitsBadgeTierNotice {
+            notice,
+            threshold: get_number(tags, "msg-param-threshold").unwrap_or_default(),
+            area: None,
+        }
+    }
+}
+
+#[derive(Default, Clone, Debug, Serialize, Deserialize)]
+pub struct ViewerMilestoneNotice {
+    pub notice: UserNotice,
+    /// The milestone type, currently only watch-streak
+    pub category: String,
+    pub value: u64,
+    pub copo_reward: Option<u64>,
+    pub milestone_id: String,
+    #[serde(skip)]
+    pub area: Option<Rect>,
+}
+
+impl ViewerMilestoneNotice {
+    pub fn new(notice: UserNotice, tags: &NoticeTags) -> Self {
+        ViewerMilestoneNotice {
+            notice,
+            category: get_string(tags, "msg-param-category"),
+            value: get_number(tags, "msg-param-value").unwrap_or_default(),
+            copo_reward: get_number(tags, "msg-param-copoReward"),
+            milestone_id: get_string(tags, "msg-param-id"),
+            area: None,
+        }
+    }
+}
+
+#[derive(Default, Clone, Debug, Serialize, Deserialize)]
+pub struct CharityDonationNotice {
+    pub notice: UserNotice,
+    pub charity_name: String,
+    pub donation_amount: u64,
+    pub donation_currency: String,
+    pub exponent: u32,
+    #[serde(skip)]
+    pub area: Option<Rect>,
+}
+
+impl CharityDonationNotice {
+    pub fn new(notice: UserNotice, tags: &NoticeTags) -> Self {
+        CharityDonationNotice {
+            notice,
+            charity_name: unescape(&get_string(tags, "msg-param-charity-name")),
+            donation_amount: get_number(tags, "msg-param-donation-amount").unwrap_or_default(),
+            donation_currency: get_string(tags, "msg-param-donation-currency"),
+            exponent: get_number(tags, "msg-param-exponent").unwrap_or_default(),
+            area: None,
+        }
+    }
+
+    /// The donation amount with the decimal point applied, e.g. 5000 with an exponent of 2 is 50.00
+    pub fn amount(&self) -> String {
+        let divisor = 10u64.pow(self.exponent);
+        let whole = self.donation_amount / divisor;
+        let fraction = self.donation_amount % divisor;
+
+        if self.exponent == 0 {
+            format!("{whole} {}", self.donation_currency)
+        } else {
+            format!(
+                "{whole}.{fraction:0width$} {}",
+                self.donation_currency,
+                width = self.exponent as usize
+            )
+        }
+    }
+}
+
+fn get_plan_name(sub_plan: &str) -> &str {
+    match sub_plan {
+        "Prime" => "Prime",
+        "2000" => "Tier 2",
+        "3000" => "Tier 3",
+        _ => "Tier 1",
+    }
+}
+
+// Renders a bordered notice with the details in gray and the
+// optional chatter message in white, returns the area it used
+fn render_notice(
+    area: Rect,
+    buf: &mut Buffer,
+    title: String,
+    border_color: Color,
+    details: &str,
+    message: &str,
+) -> Rect {
+    // Shrink horizontal area by 4 to make space for border and scroll bar
+    let mut line_area = area;
+    line_area.width = area.width.saturating_sub(4);
+
+    let mut lines: Vec<Vec<MessageParts>> = vec![];
+
+    if !details.is_empty() {
+        let details_symbols: Vec<Symbol> = get_message_symbols(details, &mut [], Some((128, 128, 128)));
+        lines.append(&mut get_lines(&details_symbols, &line_area));
+    }
+
+    if !message.is_empty() {
+        let message_symbols: Vec<Symbol> = get_message_symbols(message, &mut [], Some((255, 255, 255)));
+        lines.append(&mut get_lines(&message_symbols, &line_area));
+    }
+
+    let mut screen_lines = get_screen_lines(&mut lines, &line_area);
+    let height = screen_lines.len() as u16;
+
+    // Move cursor one over and one down to make space for border
+    let mut cursor = RenderCursor {
+        x: area.left() + 1,
+        y: area.bottom().saturating_sub(height + 1),
+    };
+
+    write_to_buffer(&mut screen_lines, buf, &mut cursor);
+
+    let block_area = Rect {
+        x: 0,
+        y: area.bottom().saturating_sub(height + 2),
+        width: area.width.saturating_sub(2),
+        height: height + 2,
+    };
+
+    Block::bordered()
+        .border_set(symbols::border::ROUNDED)
+        .border_style(Style::reset().fg(border_color))
+        .title(title)
+        .render(block_area, buf);
+
+    block_area
+}
+
+impl Widget for &mut SubNotice {
+    fn render(self, area: Rect, buf: &mut Buffer) {
+        let notice = &self.notice;
+        let title = if notice.msg_id == "resub" {
+            format!("📅 {} has resubbed!", notice.display_name)
+        } else {
+            format!("🎉 {} has subbed!", notice.display_name)
+        };
+
+        let details = format!("{} ({} months)", get_plan_name(&self.sub_plan), self.cumulative_months);
+
+        self.area = Some(render_notice(
+            area,
+            buf,
+            title,
+            Color::LightBlue,
+            &details,
+            &notice.message,
+        ));
+    }
+}
+
+impl Widget for &mut SubGiftNotice {
+    fn render(self, area: Rect, buf: &mut Buffer) {
+        let title = format!(
+            "🎁 {} gifted a sub to {}!",
+            self.notice.display_name, self.recipient_display_name
+        );
+
+        self.area = Some(render_notice(
+            area,
+            buf,
+            title,
+            Color::LightMagenta,
+            &self.notice.system_msg,
+            &self.notice.message,
+        ));
+    }
+}
+
+impl Widget for &mut SubMysteryGiftNotice {
+    fn render(self, area: Rect, buf: &mut Buffer) {
+        let title = format!(
+            "🎁 {} is gifting {} subs!",
+            self.notice.display_name, self.mass_gift_count
+        );
+
+        self.area = Some(render_notice(
+            area,
+            buf,
+            title,
+            Color::LightMagenta,
+            &self.notice.system_msg,
+            &self.notice.message,
+        ));
+    }
+}
+
+impl Widget for &mut GiftPaidUpgradeNotice {
+    fn render(self, area: Rect, buf: &mut Buffer) {
+        let title = format!("⏫ {} is continuing their gift sub!", self.notice.display_name);
+
+        self.area = Some(render_notice(
+            area,
+            buf,
+            title,
+            Color::LightBlue,
+            &self.notice.system_msg,
+            &self.notice.message,
+        ));
+    }
+}
+
+impl Widget for &mut PrimePaidUpgradeNotice {
+    fn render(self, area: Rect, buf: &mut Buffer) {
+        let title = format!("👑 {} upgraded from Prime!", self.notice.display_name);
+
+        self.area = Some(render_notice(
+            area,
+            buf,
+            title,
+            Color::LightBlue,
+            &self.notice.system_msg,
+            &self.notice.message,
+        ));
+    }
+}
+
+impl Widget for &mut AnnouncementNotice {
+    fn render(self, area: Rect, buf: &mut Buffer) {
+        let title = format!("📣 Announcement from {}", self.notice.display_name);
+        let color = self.get_color();
+
+        self.area = Some(render_notice(area, buf, title, color, "", &self.notice.message));
+    }
+}
+
+impl Widget for &mut BitsBadgeTierNotice {
+    fn render(self, area: Rect, buf: &mut Buffer) {
+        let title = format!(
+            "♦️ {} unlocked the {} bits badge!",
+            self.notice.display_name, self.threshold
+        );
+
+        // Purple border, same as cheers
+        self.area = Some(render_notice(
+            area,
+            buf,
+            title,
+            Color::Rgb(138, 43, 226),
+            &self.notice.system_msg,
+            &self.notice.message,
+        ));
+    }
+}
+
+impl Widget for &mut ViewerMilestoneNotice {
+    fn render(self, area: Rect, buf: &mut Buffer) {
+        let title = format!("🏆 {} reached a milestone!", self.notice.display_name);
+
+        self.area = Some(render_notice(
+            area,
+            buf,
+            title,
+            Color::LightGreen,
+            &self.notice.system_msg,
+            &self.notice.message,
+        ));
+    }
+}
+
+impl Widget for &mut CharityDonationNotice {
+    fn render(self, area: Rect, buf: &mut Buffer) {
+        let title = format!(
+            "💝 {} donated {} to {}!",
+            self.notice.display_name,
+            self.amount(),
+            self.charity_name
+        );
+
+        self.area = Some(render_notice(
+            area,
+            buf,
+            title,
+            Color::LightRed,
+            &self.notice.system_msg,
+            &self.notice.message,
+        ));
+    }
+}
+
+#[test]
+fn test_charity_donation_amount() {
+    let tags: NoticeTags = HashMap::from([
+        ("msg-param-donation-amount", "5025"),
+        ("msg-param-donation-currency", "USD"),
+        ("msg-param-exponent", "2"),
+    ]);
+
+    let donation = CharityDonationNotice::new(UserNotice::default(), &tags);
+
+    assert_eq!(donation.amount(), "50.25 USD");
+}
@@ -3,13 +3,22 @@ use std::{error::Error, fs, path::PathBuf};
 
 use directories::ProjectDirs;
 
-use crate::commands::TokenStatus;
+use crate::commands::{TokenStatus, BOT_TOKEN_FILE, TOKEN_FILE};
 
 pub fn read_auth_token() -> core::result::Result<TokenStatus, Box<dyn Error>> {
+    read_token(TOKEN_FILE)
+}
+
+/// The token stored by login --bot
+pub fn read_bot_token() -> core::result::Result<TokenStatus, Box<dyn Error>> {
+    read_token(BOT_TOKEN_FILE)
+}
+
+fn read_token(file_name: &str) -> core::result::Result<TokenStatus, Box<dyn Error>> {
     let error_message =
         "You need to provide credentials via positional args, env vars, or by running the login command";
     let mut data_dir = get_data_directory(Some("token")).expect(error_message);
-    data_dir.push("oath_token.txt");
+    data_dir.push(file_name);
 
     let token_file = fs::read_to_string(data_dir)?;
 
@@ -13,6 +13,16 @@ use crate::twitch::{pubsub::send_to_error_log, ChannelMessages};
 
 static SENDERS: OnceLock<RwLock<Vec<Sender<ChannelMessages>>>> = OnceLock::new();
 
+/// Sends a message straight to every connected overlay, for things that aren't shown in chat
+pub fn send_to_overlay(message: ChannelMessages) {
+    let senders = SENDERS.get_or_init(|| RwLock::new(Vec::new()));
+    if let Ok(senders) = senders.read() {
+        for sender in senders.iter() {
+            let _ = sender.send(message.clone());
+        }
+    }
+}
+
 pub fn start_websocket(messages_rx: Receiver<ChannelMessages>) {
     spawn(move || loop {
         if let Ok(new_message) = messages_rx.try_recv() {